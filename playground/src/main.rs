@@ -56,6 +56,7 @@ fn app(cx: Scope) -> Element {
         min_length: Some(3),
         alpha_numeric_only: true,
         no_whitespace: true,
+        custom: None,
     };
 
     let input_options = Options {
@@ -184,6 +185,7 @@ fn app(cx: Scope) -> Element {
             name: String::from("Typing Indicator"),
             desc: String::from("Inline message typing indicator"),
             MessageTyping {
+                text: "Alice and Bob are typing...".into(),
                 user_image: cx.render(rsx!(
                     UserImageGroup {
                         participants: sample_participants_more_2
@@ -0,0 +1,62 @@
+use dioxus::prelude::*;
+
+#[derive(Props)]
+pub struct Props<'a> {
+    url: String,
+    title: String,
+    #[props(optional)]
+    description: Option<String>,
+    // An optional image shown next to the text. Expected to be a data URI, so rendering the
+    // card never reaches out to the network.
+    #[props(optional)]
+    image: Option<String>,
+    // If true, the card is aligned with remote messages.
+    #[props(optional)]
+    remote: Option<bool>,
+    // Called with the url when the card is pressed.
+    #[props(optional)]
+    onpress: Option<EventHandler<'a, String>>,
+}
+
+#[allow(non_snake_case)]
+pub fn LinkPreview<'a>(cx: Scope<'a, Props<'a>>) -> Element<'a> {
+    let remote = cx.props.remote.unwrap_or_default();
+    let description = cx.props.description.clone().unwrap_or_default();
+    let image = cx.props.image.clone().filter(|image| !image.is_empty());
+
+    cx.render(rsx! (
+        div {
+            class: {
+                format_args!("link-preview {}", if remote { "remote" } else { "" })
+            },
+            title: "{cx.props.url}",
+            onclick: move |_| match &cx.props.onpress {
+                Some(f) => f.call(cx.props.url.clone()),
+                None    => {},
+            },
+            image.map(|image| rsx!(
+                img {
+                    class: "link-preview-image",
+                    src: "{image}",
+                }
+            )),
+            div {
+                class: "link-preview-content",
+                p {
+                    class: "link-preview-title",
+                    "{cx.props.title}"
+                },
+                (!description.is_empty()).then(|| rsx!(
+                    p {
+                        class: "link-preview-description",
+                        "{description}"
+                    }
+                )),
+                p {
+                    class: "link-preview-url",
+                    "{cx.props.url}"
+                }
+            }
+        }
+    ))
+}
@@ -0,0 +1,743 @@
+//! A small, safe subset of Markdown for message bodies.
+//!
+//! Text is parsed into a tree of `Block`s and `Inline`s which are rendered as Dioxus nodes.
+//! Nothing is ever passed through as HTML, anything that isn't understood stays plain text.
+
+use std::collections::HashSet;
+
+use dioxus::prelude::*;
+
+// Only links using these schemes are rendered as links.
+const LINK_SCHEMES: [&str; 3] = ["http://", "https://", "mailto:"];
+
+// Bare urls starting with these are turned into links.
+const AUTOLINK_SCHEMES: [&str; 2] = ["http://", "https://"];
+
+// Punctuation that ends a sentence rather than the url it follows.
+const TRAILING_PUNCTUATION: [char; 8] = ['.', ',', ';', ':', '!', '?', '\'', '"'];
+
+// How deeply quotes and emphasis may nest. Anything deeper is shown as text, so a message
+// can't exhaust the stack while being parsed or rendered.
+const MAX_DEPTH: usize = 8;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Inline {
+    Text(String),
+    Bold(Vec<Inline>),
+    Italic(Vec<Inline>),
+    Strike(Vec<Inline>),
+    Code(String),
+    Link { text: Vec<Inline>, url: String },
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Block {
+    /// Consecutive lines of text, rendered with their line breaks.
+    Paragraph(Vec<Vec<Inline>>),
+    /// A fenced code block, rendered as is.
+    Code(String),
+    Quote(Vec<Block>),
+    List {
+        ordered: bool,
+        items: Vec<Vec<Inline>>,
+    },
+}
+
+/// Parses a message body into blocks.
+pub fn parse(text: &str) -> Vec<Block> {
+    let lines: Vec<&str> = text.lines().collect();
+    parse_lines(&lines, 0)
+}
+
+fn parse_lines(lines: &[&str], depth: usize) -> Vec<Block> {
+    let mut blocks = vec![];
+    let mut paragraph: Vec<Vec<Inline>> = vec![];
+    let mut i = 0;
+
+    while i < lines.len() {
+        let line = lines[i];
+        let trimmed = line.trim_start();
+
+        let block = if trimmed.starts_with("```") {
+            // An unterminated fence runs until the end of the message.
+            let end = lines[i + 1..]
+                .iter()
+                .position(|l| l.trim_start().starts_with("```"))
+                .map(|n| i + 1 + n)
+                .unwrap_or(lines.len());
+            let code = lines[i + 1..end].join("\n");
+            i = end + 1;
+            Some(Block::Code(code))
+        } else if trimmed.starts_with('>') && depth < MAX_DEPTH {
+            let quoted: Vec<&str> = lines[i..]
+                .iter()
+                .take_while(|l| l.trim_start().starts_with('>'))
+                .map(|l| {
+                    let l = l.trim_start();
+                    let l = l.strip_prefix('>').unwrap_or(l);
+                    l.strip_prefix(' ').unwrap_or(l)
+                })
+                .collect();
+            i += quoted.len();
+            Some(Block::Quote(parse_lines(&quoted, depth + 1)))
+        } else if let Some((ordered, _)) = list_item(line) {
+            let mut items = vec![];
+            while let Some((o, item)) = lines.get(i).and_then(|l| list_item(l)) {
+                if o != ordered {
+                    break;
+                }
+                items.push(parse_inline(item));
+                i += 1;
+            }
+            Some(Block::List { ordered, items })
+        } else if trimmed.is_empty() {
+            i += 1;
+            None
+        } else {
+            paragraph.push(parse_inline(line));
+            i += 1;
+            continue;
+        };
+
+        if !paragraph.is_empty() {
+            blocks.push(Block::Paragraph(std::mem::take(&mut paragraph)));
+        }
+        if let Some(block) = block {
+            blocks.push(block);
+        }
+    }
+
+    if !paragraph.is_empty() {
+        blocks.push(Block::Paragraph(paragraph));
+    }
+    blocks
+}
+
+// Recognizes "- item", "* item", "+ item" and "1. item" or "1) item".
+fn list_item(line: &str) -> Option<(bool, &str)> {
+    let trimmed = line.trim_start();
+    for bullet in ["- ", "* ", "+ "] {
+        if let Some(item) = trimmed.strip_prefix(bullet) {
+            return Some((false, item));
+        }
+    }
+
+    let digits = trimmed.chars().take_while(|c| c.is_ascii_digit()).count();
+    if digits == 0 {
+        return None;
+    }
+    let rest = &trimmed[digits..];
+    rest.strip_prefix(". ")
+        .or_else(|| rest.strip_prefix(") "))
+        .map(|item| (true, item))
+}
+
+/// Parses emphasis, code spans and links within a single line.
+pub fn parse_inline(text: &str) -> Vec<Inline> {
+    let chars: Vec<char> = text.chars().collect();
+    parse_chars(&chars, true, 0)
+}
+
+fn parse_chars(chars: &[char], links: bool, depth: usize) -> Vec<Inline> {
+    let mut inlines = vec![];
+    let mut text = String::new();
+    let mut i = 0;
+    // One scan per kind of closing markup, so unmatched openers don't rescan the line.
+    let mut code_scan = Scan::default();
+    let mut link_scan = LinkScan::default();
+    let mut closing_scans: [Scan; DELIMITERS.len()] = Default::default();
+
+    while i < chars.len() {
+        let c = chars[i];
+
+        // A backslash keeps the next character from being read as markup.
+        if c == '\\' && i + 1 < chars.len() && !chars[i + 1].is_alphanumeric() {
+            text.push(chars[i + 1]);
+            i += 2;
+            continue;
+        }
+
+        if c == '`' {
+            if let Some(end) = code_scan.next(i + 1, chars.len(), |j| chars[j] == '`') {
+                push_text(&mut inlines, &mut text);
+                inlines.push(Inline::Code(chars[i + 1..end].iter().collect()));
+                i = end + 1;
+                continue;
+            }
+        }
+
+        if c == '[' && links {
+            if let Some((link, end)) = parse_link(chars, i, depth, &mut link_scan) {
+                push_text(&mut inlines, &mut text);
+                inlines.push(link);
+                i = end;
+                continue;
+            }
+        }
+
+        if links {
+            if let Some(end) = autolink_at(chars, i) {
+                push_text(&mut inlines, &mut text);
+                inlines.push(url_link(chars[i..end].iter().collect()));
+                i = end;
+                continue;
+            }
+        }
+
+        if let Some(kind) = delimiter_at(chars, i).filter(|_| depth < MAX_DEPTH) {
+            let (delimiter, wrap) = DELIMITERS[kind];
+            let start = i + delimiter.len();
+            let end = closing_scans[kind]
+                .next(start + 1, chars.len(), |j| closes_at(chars, j, delimiter));
+            if let Some(end) = end {
+                push_text(&mut inlines, &mut text);
+                inlines.push(wrap(parse_chars(&chars[start..end], links, depth + 1)));
+                i = end + delimiter.len();
+                continue;
+            }
+        }
+
+        text.push(c);
+        i += 1;
+    }
+
+    push_text(&mut inlines, &mut text);
+    inlines
+}
+
+fn push_text(inlines: &mut Vec<Inline>, text: &mut String) {
+    if !text.is_empty() {
+        inlines.push(Inline::Text(std::mem::take(text)));
+    }
+}
+
+/// Remembers where a forward scan last matched, so scanning again from a later position
+/// doesn't go over the same characters. Positions have to be asked for in increasing order.
+#[derive(Default)]
+struct Scan {
+    from: usize,
+    found: Option<Option<usize>>,
+}
+
+impl Scan {
+    fn next(&mut self, start: usize, end: usize, matches: impl Fn(usize) -> bool) -> Option<usize> {
+        if let Some(found) = self.found {
+            if start >= self.from && found.map_or(true, |j| j >= start) {
+                return found;
+            }
+        }
+        let found = (start..end).find(|&j| matches(j));
+        self.from = start;
+        self.found = Some(found);
+        found
+    }
+}
+
+// Scans for the "](" and ")" of links.
+#[derive(Default)]
+struct LinkScan {
+    text_end: Scan,
+    url_end: Scan,
+}
+
+type Wrap = fn(Vec<Inline>) -> Inline;
+
+const DELIMITERS: [(&[char], Wrap); 5] = [
+    (&['*', '*'], Inline::Bold),
+    (&['_', '_'], Inline::Bold),
+    (&['~', '~'], Inline::Strike),
+    (&['*'], Inline::Italic),
+    (&['_'], Inline::Italic),
+];
+
+// The index in `DELIMITERS` of the emphasis opening at `i`, if any.
+fn delimiter_at(chars: &[char], i: usize) -> Option<usize> {
+    DELIMITERS.iter().position(|(delimiter, _)| {
+        chars[i..].starts_with(delimiter)
+            // Emphasis has to hug its text, so "2 * 3" stays as is.
+            && chars
+                .get(i + delimiter.len())
+                .map_or(false, |c| !c.is_whitespace())
+            // Underscores inside words, like snake_case, aren't markup.
+            && !(delimiter[0] == '_' && i > 0 && chars[i - 1].is_alphanumeric())
+    })
+}
+
+// Whether emphasis using `delimiter` can close at `j`.
+fn closes_at(chars: &[char], j: usize, delimiter: &[char]) -> bool {
+    chars[j..].starts_with(delimiter)
+        && !chars[j - 1].is_whitespace()
+        // A single delimiter isn't the start of a double one, e.g. "*a **b** c*".
+        && (delimiter.len() > 1
+            || (chars.get(j + 1) != Some(&delimiter[0]) && chars[j - 1] != delimiter[0]))
+        && !(delimiter[0] == '_'
+            && chars
+                .get(j + delimiter.len())
+                .map_or(false, |c| c.is_alphanumeric()))
+}
+
+// Parses "[text](url)" starting at `start`. Returns the link and where it ends.
+fn parse_link(
+    chars: &[char],
+    start: usize,
+    depth: usize,
+    scan: &mut LinkScan,
+) -> Option<(Inline, usize)> {
+    let text_end = scan.text_end.next(start + 1, chars.len(), |j| {
+        chars[j..].starts_with(&[']', '('])
+    })?;
+    let url_end = scan
+        .url_end
+        .next(text_end + 2, chars.len(), |j| chars[j] == ')')?;
+    let url: String = chars[text_end + 2..url_end].iter().collect();
+    if !is_safe_url(&url) {
+        return None;
+    }
+
+    let link = Inline::Link {
+        text: parse_chars(&chars[start + 1..text_end], false, depth + 1),
+        url,
+    };
+    Some((link, url_end + 1))
+}
+
+// Detects a bare url starting at `i` and returns where it ends.
+fn autolink_at(chars: &[char], i: usize) -> Option<usize> {
+    if i > 0 && chars[i - 1].is_alphanumeric() {
+        return None;
+    }
+    let rest: String = chars[i..].iter().take(8).collect::<String>().to_lowercase();
+    if !AUTOLINK_SCHEMES
+        .iter()
+        .any(|scheme| rest.starts_with(scheme))
+    {
+        return None;
+    }
+
+    let mut end = i + chars[i..].iter().take_while(|c| !c.is_whitespace()).count();
+    // Only trailing punctuation is dropped below, so this holds for every `end`.
+    let has_open_paren = chars[i..end].contains(&'(');
+    loop {
+        let last = chars[end - 1];
+        let unbalanced_paren = last == ')' && !has_open_paren;
+        if TRAILING_PUNCTUATION.contains(&last) || unbalanced_paren {
+            end -= 1;
+        } else {
+            break;
+        }
+    }
+
+    let url: String = chars[i..end].iter().collect();
+    is_safe_url(&url).then_some(end)
+}
+
+fn url_link(url: String) -> Inline {
+    Inline::Link {
+        text: vec![Inline::Text(url.clone())],
+        url,
+    }
+}
+
+/// Splits plain text into text and links, without reading any other markup.
+pub fn linkify(text: &str) -> Vec<Inline> {
+    let chars: Vec<char> = text.chars().collect();
+    let mut inlines = vec![];
+    let mut plain = String::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        match autolink_at(&chars, i) {
+            Some(end) => {
+                push_text(&mut inlines, &mut plain);
+                inlines.push(url_link(chars[i..end].iter().collect()));
+                i = end;
+            }
+            None => {
+                plain.push(chars[i]);
+                i += 1;
+            }
+        }
+    }
+
+    push_text(&mut inlines, &mut plain);
+    inlines
+}
+
+/// Every url that's rendered as a link, in order of first appearance. With `markdown` off
+/// only bare urls count.
+pub fn find_urls(text: &str, markdown: bool) -> Vec<String> {
+    let mut urls = vec![];
+    if markdown {
+        for block in parse(text) {
+            collect_block_urls(&block, &mut urls);
+        }
+    } else {
+        collect_urls(&linkify(text), &mut urls);
+    }
+    let mut seen = HashSet::new();
+    urls.retain(|url| seen.insert(url.clone()));
+    urls
+}
+
+fn collect_block_urls(block: &Block, urls: &mut Vec<String>) {
+    match block {
+        Block::Paragraph(lines) => lines.iter().for_each(|line| collect_urls(line, urls)),
+        Block::Code(_) => {}
+        Block::Quote(blocks) => blocks.iter().for_each(|b| collect_block_urls(b, urls)),
+        Block::List { items, .. } => items.iter().for_each(|item| collect_urls(item, urls)),
+    }
+}
+
+fn collect_urls(inlines: &[Inline], urls: &mut Vec<String>) {
+    for inline in inlines {
+        match inline {
+            Inline::Link { url, .. } => urls.push(url.clone()),
+            Inline::Bold(children) | Inline::Italic(children) | Inline::Strike(children) => {
+                collect_urls(children, urls)
+            }
+            Inline::Text(_) | Inline::Code(_) => {}
+        }
+    }
+}
+
+/// Whether the url is one we're willing to render as a link.
+pub fn is_safe_url(url: &str) -> bool {
+    let lowercase = url.to_lowercase();
+    !url.chars().any(char::is_whitespace)
+        && LINK_SCHEMES
+            .iter()
+            .any(|scheme| lowercase.starts_with(scheme) && lowercase.len() > scheme.len())
+}
+
+/// Renders parsed blocks. Pressing a link calls `onlink` with its url.
+pub fn render_blocks<'a, 'b>(
+    blocks: &'b [Block],
+    onlink: Option<&'a EventHandler<'a, String>>,
+) -> LazyNodes<'a, 'b> {
+    rsx!(blocks.iter().map(|block| match block {
+        Block::Paragraph(lines) => rsx!(
+            p {
+                class: "text",
+                lines.iter().enumerate().map(|(i, line)| rsx!(
+                    (i > 0).then(|| rsx!(br {})),
+                    render_inlines(line, onlink)
+                ))
+            }
+        ),
+        Block::Code(source) => rsx!(
+            pre {
+                class: "code-block",
+                code { "{source}" }
+            }
+        ),
+        Block::Quote(blocks) => rsx!(
+            blockquote {
+                class: "quote",
+                render_blocks(blocks, onlink)
+            }
+        ),
+        Block::List {
+            ordered: true,
+            items,
+        } => rsx!(
+            ol {
+                items.iter().map(|item| rsx!(li { render_inlines(item, onlink) }))
+            }
+        ),
+        Block::List {
+            ordered: false,
+            items,
+        } => rsx!(
+            ul {
+                items.iter().map(|item| rsx!(li { render_inlines(item, onlink) }))
+            }
+        ),
+    }))
+}
+
+/// Renders the contents of a single line. Pressing a link calls `onlink` with its url.
+pub fn render_inlines<'a, 'b>(
+    inlines: &'b [Inline],
+    onlink: Option<&'a EventHandler<'a, String>>,
+) -> LazyNodes<'a, 'b> {
+    rsx!(inlines.iter().map(|inline| match inline {
+        Inline::Text(text) => rsx!(span { "{text}" }),
+        Inline::Bold(children) => rsx!(strong { render_inlines(children, onlink) }),
+        Inline::Italic(children) => rsx!(em { render_inlines(children, onlink) }),
+        Inline::Strike(children) => rsx!(del { render_inlines(children, onlink) }),
+        Inline::Code(source) => rsx!(code { "{source}" }),
+        Inline::Link { text, url } => {
+            let target = url.clone();
+            rsx!(
+                span {
+                    class: "link",
+                    title: "{url}",
+                    onclick: move |_| {
+                        if let Some(f) = onlink {
+                            f.call(target.clone());
+                        }
+                    },
+                    render_inlines(text, onlink)
+                }
+            )
+        }
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn text(s: &str) -> Inline {
+        Inline::Text(s.into())
+    }
+
+    fn paragraph(lines: Vec<Vec<Inline>>) -> Block {
+        Block::Paragraph(lines)
+    }
+
+    // How many quotes are nested in the first block.
+    fn quote_depth(blocks: &[Block]) -> usize {
+        match blocks.first() {
+            Some(Block::Quote(inner)) => 1 + quote_depth(inner),
+            _ => 0,
+        }
+    }
+
+    // How many emphasis wrappers are nested in the first inline.
+    fn emphasis_depth(inlines: &[Inline]) -> usize {
+        match inlines.first() {
+            Some(Inline::Bold(inner) | Inline::Italic(inner) | Inline::Strike(inner)) => {
+                1 + emphasis_depth(inner)
+            }
+            _ => 0,
+        }
+    }
+
+    #[test]
+    fn plain_text_keeps_its_line_breaks() {
+        assert_eq!(
+            parse("hello\nworld\n\nagain"),
+            vec![
+                paragraph(vec![vec![text("hello")], vec![text("world")]]),
+                paragraph(vec![vec![text("again")]]),
+            ]
+        );
+    }
+
+    #[test]
+    fn emphasis_and_code() {
+        assert_eq!(
+            parse_inline("**bold** __bold__ *it* _it_ ~~gone~~ `a *b*`"),
+            vec![
+                Inline::Bold(vec![text("bold")]),
+                text(" "),
+                Inline::Bold(vec![text("bold")]),
+                text(" "),
+                Inline::Italic(vec![text("it")]),
+                text(" "),
+                Inline::Italic(vec![text("it")]),
+                text(" "),
+                Inline::Strike(vec![text("gone")]),
+                text(" "),
+                Inline::Code("a *b*".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn nested_emphasis() {
+        assert_eq!(
+            parse_inline("*a **b** c*"),
+            vec![Inline::Italic(vec![
+                text("a "),
+                Inline::Bold(vec![text("b")]),
+                text(" c"),
+            ])]
+        );
+    }
+
+    #[test]
+    fn emphasis_has_to_hug_its_text() {
+        assert_eq!(parse_inline("2 * 3 * 4"), vec![text("2 * 3 * 4")]);
+        assert_eq!(
+            parse_inline("snake_case_name"),
+            vec![text("snake_case_name")]
+        );
+        assert_eq!(parse_inline("*unclosed"), vec![text("*unclosed")]);
+    }
+
+    #[test]
+    fn escapes_are_kept_as_text() {
+        assert_eq!(parse_inline(r"\*not italic\*"), vec![text("*not italic*")]);
+        assert_eq!(parse_inline(r"\`code\`"), vec![text("`code`")]);
+        // Backslashes before letters aren't escapes, so paths survive.
+        assert_eq!(parse_inline(r"C:\Users"), vec![text(r"C:\Users")]);
+    }
+
+    #[test]
+    fn links() {
+        assert_eq!(
+            parse_inline("see [the *docs*](https://example.com/a)"),
+            vec![
+                text("see "),
+                Inline::Link {
+                    text: vec![text("the "), Inline::Italic(vec![text("docs")])],
+                    url: "https://example.com/a".into(),
+                },
+            ]
+        );
+        assert_eq!(
+            parse_inline("[x](javascript:alert(1))"),
+            vec![text("[x](javascript:alert(1))")]
+        );
+    }
+
+    #[test]
+    fn bare_urls_leave_trailing_punctuation_out() {
+        assert_eq!(
+            parse_inline("go to https://example.com/a."),
+            vec![
+                text("go to "),
+                url_link("https://example.com/a".into()),
+                text(".")
+            ]
+        );
+        assert_eq!(
+            parse_inline("(see https://example.com/a)"),
+            vec![
+                text("(see "),
+                url_link("https://example.com/a".into()),
+                text(")")
+            ]
+        );
+        assert_eq!(
+            parse_inline("https://example.com/wiki/A_(b)"),
+            vec![url_link("https://example.com/wiki/A_(b)".into())]
+        );
+        assert_eq!(
+            parse_inline("xhttps://example.com"),
+            vec![text("xhttps://example.com")]
+        );
+    }
+
+    #[test]
+    fn linkify_only_reads_urls() {
+        assert_eq!(
+            linkify("*a* https://example.com"),
+            vec![text("*a* "), url_link("https://example.com".into())]
+        );
+    }
+
+    #[test]
+    fn fenced_code() {
+        assert_eq!(
+            parse("before\n```\nlet *a* = 1;\n\nb\n```\nafter"),
+            vec![
+                paragraph(vec![vec![text("before")]]),
+                Block::Code("let *a* = 1;\n\nb".into()),
+                paragraph(vec![vec![text("after")]]),
+            ]
+        );
+    }
+
+    #[test]
+    fn unterminated_fence_runs_to_the_end() {
+        assert_eq!(
+            parse("```\ncode\n*more*"),
+            vec![Block::Code("code\n*more*".into())]
+        );
+        assert_eq!(parse("```"), vec![Block::Code(String::new())]);
+    }
+
+    #[test]
+    fn quotes() {
+        assert_eq!(
+            parse("> one\n>> two\nthree"),
+            vec![
+                Block::Quote(vec![
+                    paragraph(vec![vec![text("one")]]),
+                    Block::Quote(vec![paragraph(vec![vec![text("two")]])]),
+                ]),
+                paragraph(vec![vec![text("three")]]),
+            ]
+        );
+    }
+
+    #[test]
+    fn lists() {
+        assert_eq!(
+            parse("- a\n* b\n1. c\n2) d"),
+            vec![
+                Block::List {
+                    ordered: false,
+                    items: vec![vec![text("a")], vec![text("b")]],
+                },
+                Block::List {
+                    ordered: true,
+                    items: vec![vec![text("c")], vec![text("d")]],
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn deep_quotes_are_capped() {
+        let line = ">".repeat(100_000);
+        let blocks = parse(&line);
+        assert_eq!(quote_depth(&blocks), MAX_DEPTH);
+    }
+
+    #[test]
+    fn deep_emphasis_is_capped() {
+        let chars: Vec<char> = "~~**__*_x_*__**~~".chars().collect();
+        assert_eq!(emphasis_depth(&parse_chars(&chars, true, 0)), 5);
+        assert_eq!(emphasis_depth(&parse_chars(&chars, true, MAX_DEPTH - 2)), 2);
+        assert_eq!(
+            parse_chars(&chars, true, MAX_DEPTH),
+            vec![text("~~**__*_x_*__**~~")]
+        );
+
+        let line = format!(
+            "{}x{}",
+            "~~**__*_".repeat(10_000),
+            "_*__**~~".repeat(10_000)
+        );
+        assert!(emphasis_depth(&parse_inline(&line)) <= MAX_DEPTH);
+    }
+
+    #[test]
+    fn unmatched_markup_is_text() {
+        for opener in ["*a ", "_a ", "~~a ", "[a ", "[a](b "] {
+            let line = opener.repeat(20_000);
+            let inlines = parse_inline(&line);
+            assert_eq!(inlines, vec![text(&line)], "{opener}");
+        }
+    }
+
+    #[test]
+    fn urls_are_found_once_in_order() {
+        let text =
+            "https://b.example https://a.example\n> [b](https://b.example)\n`https://c.example`";
+        assert_eq!(
+            find_urls(text, true),
+            vec![
+                "https://b.example".to_string(),
+                "https://a.example".to_string()
+            ]
+        );
+        assert_eq!(
+            find_urls("https://a.example, `x` https://a.example", false),
+            vec!["https://a.example".to_string()]
+        );
+    }
+
+    #[test]
+    fn safe_urls() {
+        assert!(is_safe_url("https://example.com"));
+        assert!(is_safe_url("mailto:a@example.com"));
+        assert!(!is_safe_url("https://"));
+        assert!(!is_safe_url("javascript:alert(1)"));
+        assert!(!is_safe_url("https://exa mple.com"));
+    }
+}
@@ -2,6 +2,8 @@ use std::fmt;
 
 use dioxus::prelude::*;
 
+pub mod markdown;
+
 #[derive(Eq, PartialEq, Clone, Copy)]
 pub enum Order {
     First,
@@ -42,6 +44,28 @@ pub struct Props<'a> {
     // If not set, the default value of Order::Last will be used.
     #[props(optional)]
     order: Option<Order>,
+
+    // An optional field that, if set to true, will render `with_text` as Markdown instead of plain text.
+    // Only a safe subset is supported, see the markdown module.
+    #[props(optional)]
+    markdown: Option<bool>,
+
+    // An optional field that, if set, will be called with the url of a link when it's pressed.
+    // Links are detected in both Markdown and plain text.
+    #[props(optional)]
+    onlink: Option<EventHandler<'a, String>>,
+
+    // An optional field that, if set to true, will add a marker after the text, showing `edited_text`.
+    #[props(optional)]
+    edited: Option<bool>,
+
+    // An optional field that, if set, will be used as the text of the edited marker, e.g. "(edited)".
+    #[props(optional)]
+    edited_text: Option<String>,
+
+    // An optional field that, if set to true, will add a CSS class of "tombstone" to the div element. Used in place of a deleted message.
+    #[props(optional)]
+    tombstone: Option<bool>,
 }
 
 #[allow(non_snake_case)]
@@ -50,12 +74,18 @@ pub fn Message<'a>(cx: Scope<'a, Props<'a>>) -> Element<'a> {
     let loading = cx.props.loading.unwrap_or_default();
     let remote = cx.props.remote.unwrap_or_default();
     let order = cx.props.order.unwrap_or(Order::Last);
+    let edited = cx.props.edited.unwrap_or_default();
+    let edited_text = cx.props.edited_text.clone().unwrap_or_default();
+    let tombstone = cx.props.tombstone.unwrap_or_default();
+    let blocks = cx.props.markdown.unwrap_or_default().then(|| markdown::parse(&text));
+    let plain = markdown::linkify(&text);
+    let onlink = cx.props.onlink.as_ref();
 
     cx.render(rsx! (
         div {
             class: {
                 format_args!(
-                    "message {} {} {}",
+                    "message {} {} {} {}",
                     if loading {
                         "loading"
                     } else { "" },
@@ -64,7 +94,10 @@ pub fn Message<'a>(cx: Scope<'a, Props<'a>>) -> Element<'a> {
                     } else { "" },
                     if cx.props.order.is_some() {
                         order.to_string()
-                    } else { "".into() }
+                    } else { "".into() },
+                    if tombstone {
+                        "tombstone"
+                    } else { "" }
                 )
             },
             (cx.props.with_content.is_some()).then(|| rsx! (
@@ -73,10 +106,24 @@ pub fn Message<'a>(cx: Scope<'a, Props<'a>>) -> Element<'a> {
                     &cx.props.with_content,
                 },
             )),
-            (cx.props.with_text.is_some()).then(|| rsx! (
-                p {
-                    class: "text",
-                    "{text}"
+            (cx.props.with_text.is_some()).then(|| match &blocks {
+                Some(blocks) => rsx! (
+                    div {
+                        class: "markdown",
+                        markdown::render_blocks(blocks, onlink)
+                    }
+                ),
+                None => rsx! (
+                    p {
+                        class: "text",
+                        markdown::render_inlines(&plain, onlink)
+                    }
+                ),
+            }),
+            edited.then(|| rsx! (
+                span {
+                    class: "edited noselect",
+                    "{edited_text}"
                 }
             ))
         }
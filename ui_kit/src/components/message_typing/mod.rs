@@ -2,23 +2,33 @@ use dioxus::prelude::*;
 
 #[derive(Props)]
 pub struct Props<'a> {
-    // Represents the image of the user who is typing
+    // Represents the image of the user who is typing, or a `UserImageGroup` when several are
     user_image: Element<'a>,
+    // Names who is typing, e.g. "Alice and Bob are typing..."
+    #[props(optional)]
+    text: Option<String>,
 }
 
 #[allow(non_snake_case)]
 pub fn MessageTyping<'a>(cx: Scope<'a, Props<'a>>) -> Element<'a> {
+    let text = cx.props.text.clone().unwrap_or_default();
+
     cx.render(rsx! (
         div {
             class: "message-typing-wrap",
-            // TODO: Support a vec of user images in case multiple are typing
             &cx.props.user_image,
             div {
                 class: "message-typing",
                 div { class: "dot dot-1" },
                 div { class: "dot dot-2" },
                 div { class: "dot dot-3" }
-            }
+            },
+            (!text.is_empty()).then(|| rsx!(
+                p {
+                    class: "message-typing-text",
+                    "{text}"
+                }
+            ))
         }
     ))
 }
@@ -12,6 +12,12 @@ pub mod message_group;
 pub mod message_reply;
 pub mod message_typing;
 
+pub mod reaction_picker;
+pub mod reactions;
+
 pub mod file_embed;
+pub mod link_preview;
 
 pub mod context_menu;
+
+pub mod toast;
@@ -0,0 +1,59 @@
+use dioxus::prelude::*;
+
+use crate::icons::{Icon, IconElement};
+
+/// The emojis offered when no other set is given.
+pub const DEFAULT_EMOJIS: [&str; 8] = ["👍", "👎", "❤️", "😂", "😮", "😢", "🎉", "🔥"];
+
+#[derive(Props)]
+pub struct Props<'a> {
+    // The emojis to pick from, defaults to `DEFAULT_EMOJIS`.
+    #[props(optional)]
+    emojis: Option<Vec<String>>,
+    // Called with the emoji that was picked.
+    onselect: EventHandler<'a, String>,
+    // Called when the picker is dismissed without picking anything.
+    #[props(optional)]
+    onclose: Option<EventHandler<'a>>,
+    // If true, the picker is aligned with remote messages.
+    #[props(optional)]
+    remote: Option<bool>,
+}
+
+#[allow(non_snake_case)]
+pub fn ReactionPicker<'a>(cx: Scope<'a, Props<'a>>) -> Element<'a> {
+    let emojis = cx
+        .props
+        .emojis
+        .clone()
+        .unwrap_or_else(|| DEFAULT_EMOJIS.iter().map(|emoji| emoji.to_string()).collect());
+    let remote = cx.props.remote.unwrap_or_default();
+
+    cx.render(rsx! (
+        div {
+            class: {
+                format_args!("reaction-picker noselect {}", if remote { "remote" } else { "" })
+            },
+            emojis.into_iter().map(|emoji| rsx!(
+                button {
+                    key: "{emoji}",
+                    class: "reaction-picker-emoji",
+                    onclick: move |_| cx.props.onselect.call(emoji.clone()),
+                    "{emoji}"
+                }
+            )),
+            cx.props.onclose.is_some().then(|| rsx!(
+                button {
+                    class: "reaction-picker-close",
+                    onclick: move |_| match &cx.props.onclose {
+                        Some(f) => f.call(()),
+                        None    => {},
+                    },
+                    IconElement {
+                        icon: Icon::XMark
+                    }
+                }
+            ))
+        }
+    ))
+}
@@ -0,0 +1,57 @@
+use dioxus::prelude::*;
+
+/// Everyone who reacted to a message with the same emoji.
+#[derive(Clone, PartialEq, Eq)]
+pub struct ReactionSummary {
+    pub emoji: String,
+    // The names of the users who reacted, shown when hovering the reaction.
+    pub users: Vec<String>,
+    // If true, the current user is one of them and pressing the reaction takes it back.
+    pub reacted: bool,
+}
+
+#[derive(Props)]
+pub struct Props<'a> {
+    reactions: Vec<ReactionSummary>,
+    // Called with the emoji of the reaction that was pressed.
+    onpress: EventHandler<'a, String>,
+    // If true, the reactions are aligned with remote messages.
+    #[props(optional)]
+    remote: Option<bool>,
+}
+
+#[allow(non_snake_case)]
+pub fn Reactions<'a>(cx: Scope<'a, Props<'a>>) -> Element<'a> {
+    let remote = cx.props.remote.unwrap_or_default();
+
+    cx.render(rsx! (
+        div {
+            class: {
+                format_args!("reactions noselect {}", if remote { "remote" } else { "" })
+            },
+            cx.props.reactions.iter().map(|reaction| {
+                let emoji = reaction.emoji.clone();
+                let count = reaction.users.len();
+                let users = reaction.users.join(", ");
+                rsx!(
+                    button {
+                        key: "{reaction.emoji}",
+                        class: {
+                            format_args!("reaction {}", if reaction.reacted { "reacted" } else { "" })
+                        },
+                        title: "{users}",
+                        onclick: move |_| cx.props.onpress.call(emoji.clone()),
+                        span {
+                            class: "reaction-emoji",
+                            "{reaction.emoji}"
+                        },
+                        span {
+                            class: "reaction-count",
+                            "{count}"
+                        }
+                    }
+                )
+            })
+        }
+    ))
+}
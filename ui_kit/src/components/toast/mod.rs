@@ -0,0 +1,72 @@
+use dioxus::prelude::*;
+
+use crate::{
+    elements::{button::Button, Appearance},
+    icons::{Icon, IconElement},
+};
+
+#[derive(Props)]
+pub struct Props<'a> {
+    // The message shown in the toast.
+    text: String,
+    // An optional icon rendered before the text.
+    #[props(optional)]
+    icon: Option<Icon>,
+    // If true, the toast is styled as an error.
+    #[props(optional)]
+    error: Option<bool>,
+    // An optional label for an action button, e.g. "Undo".
+    #[props(optional)]
+    with_action: Option<String>,
+    // Called when the action button is pressed.
+    #[props(optional)]
+    onaction: Option<EventHandler<'a>>,
+    // Called when the toast is dismissed.
+    #[props(optional)]
+    onclose: Option<EventHandler<'a>>,
+}
+
+#[allow(non_snake_case)]
+pub fn Toast<'a>(cx: Scope<'a, Props<'a>>) -> Element<'a> {
+    let error = cx.props.error.unwrap_or_default();
+    let action = cx.props.with_action.clone().unwrap_or_default();
+
+    cx.render(rsx! (
+        div {
+            class: {
+                format_args!("toast {}", if error { "toast-error" } else { "" })
+            },
+            cx.props.icon.map(|icon| rsx!(
+                IconElement {
+                    icon: icon
+                }
+            )),
+            p {
+                class: "toast-text",
+                "{cx.props.text}"
+            },
+            (!action.is_empty()).then(|| rsx!(
+                Button {
+                    small: true,
+                    text: action,
+                    appearance: Appearance::Primary,
+                    onpress: move |_| match &cx.props.onaction {
+                        Some(f) => f.call(()),
+                        None    => {},
+                    }
+                }
+            )),
+            cx.props.onclose.is_some().then(|| rsx!(
+                Button {
+                    small: true,
+                    icon: Icon::XMark,
+                    appearance: Appearance::Secondary,
+                    onpress: move |_| match &cx.props.onclose {
+                        Some(f) => f.call(()),
+                        None    => {},
+                    }
+                }
+            ))
+        }
+    ))
+}
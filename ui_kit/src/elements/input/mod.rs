@@ -10,6 +10,8 @@ pub struct Validation {
     pub min_length: Option<i32>,
     pub alpha_numeric_only: bool,
     pub no_whitespace: bool,
+    // Extra check for formats the options above can't describe, run if the others pass.
+    pub custom: Option<fn(&str) -> Option<ValidationError>>,
 }
 
 #[derive(Default, Clone, Copy)]
@@ -19,6 +21,7 @@ pub struct Options {
     pub disabled: bool,
     pub with_clear_btn: bool,
     pub with_label: Option<&'static str>,
+    pub is_password: bool,
 }
 
 #[derive(Props)]
@@ -28,6 +31,9 @@ pub struct Props<'a> {
     placeholder: String,
     #[props(optional)]
     default_text: Option<String>,
+    // When set, the input shows this value instead of keeping track of its own.
+    #[props(optional)]
+    value: Option<String>,
     #[props(optional)]
     disabled: Option<bool>,
     #[props(optional)]
@@ -150,6 +156,10 @@ pub fn validate(cx: &Scope<Props>, val: &str) -> Option<ValidationError> {
         error = validate_min_max(val, validation.min_length, validation.max_length);
     }
 
+    if let (None, Some(custom)) = (&error, validation.custom) {
+        error = custom(val);
+    }
+
     error
 }
 
@@ -157,11 +167,16 @@ pub fn validate(cx: &Scope<Props>, val: &str) -> Option<ValidationError> {
 pub fn Input<'a>(cx: Scope<'a, Props<'a>>) -> Element<'a> {
     let error = use_state(&cx, || String::from(""));
     let val = use_state(&cx, || get_text(&cx));
+    let text = match &cx.props.value {
+        Some(value) => value.clone(),
+        None => val.to_string(),
+    };
     let default_options = Options::default();
     let options = match &cx.props.options {
         Some(opts) => opts,
         None => &default_options,
     };
+    let show_clear_btn = options.with_clear_btn && !text.is_empty();
     let valid = use_state(&cx, || false);
     let min_len = match options.with_validation {
         Some(opts) => opts.min_length.unwrap_or_default(),
@@ -196,8 +211,9 @@ pub fn Input<'a>(cx: Scope<'a, Props<'a>>) -> Element<'a> {
                     }
                 )),
                 input {
+                    r#type: if options.is_password { "password" } else { "text" },
                     disabled: "{disabled}",
-                    value: "{val}",
+                    value: "{text}",
                     placeholder: "{cx.props.placeholder}",
                     oninput: move |evt| {
                         let current_val = evt.value.clone();
@@ -210,21 +226,22 @@ pub fn Input<'a>(cx: Scope<'a, Props<'a>>) -> Element<'a> {
                         } else if current_val.len() >= min_len as usize {
                             valid.set(true);
                         }
-                        emit(&cx, val.to_string());
+                        emit(&cx, current_val);
                     },
                     onkeyup: move |evt| {
                         if evt.key_code == KeyCode::Enter {
-                            emit_return(&cx, val.to_string());
+                            emit_return(&cx, text.clone());
                         }
                     }
                 }
-                (options.with_clear_btn && !val.is_empty()).then(|| rsx!(
+                show_clear_btn.then(|| rsx!(
                     div {
                         class: "clear-btn",
                         onclick: move |_| {
                             val.set("".into());
                             error.set("".into());
                             valid.set(false);
+                            emit(&cx, String::new());
                         },
                         IconElement { 
                             icon: Icon::Backspace
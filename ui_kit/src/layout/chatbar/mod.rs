@@ -30,6 +30,12 @@ pub struct Props<'a> {
     extensions: Option<Element<'a>>,
     #[props(optional)]
     controls: Option<Element<'a>>,
+    // Text to show in the chatbar, kept up to date through `onchange`.
+    value: String,
+    #[props(optional)]
+    onchange: Option<EventHandler<'a, String>>,
+    #[props(optional)]
+    onreturn: Option<EventHandler<'a, String>>,
 }
 
 #[derive(Props)]
@@ -82,6 +88,17 @@ pub fn Chatbar<'a>(cx: Scope<'a, Props<'a>>) -> Element<'a> {
             Input {
                 // TODO: This should come from props
                 placeholder: "Say something...".into(),
+                value: cx.props.value.clone(),
+                onchange: move |v: String| {
+                    if let Some(f) = &cx.props.onchange {
+                        f.call(v);
+                    }
+                },
+                onreturn: move |v| {
+                    if let Some(f) = &cx.props.onreturn {
+                        f.call(v);
+                    }
+                },
             },
             &cx.props.extensions,
             div {
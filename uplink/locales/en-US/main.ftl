@@ -11,16 +11,45 @@ uplink = Uplink
     .welcome = Welcome
     .clear-unreads = Clear Unreads
     .hide-chat = Hide Chat
+    .messaging-failed = Couldn't connect to the network, messages won't be sent or received.
 
 
 messages = Messages
     .new = New Message
     .replying = Replying to:
+    .today = Today
+    .yesterday = Yesterday
+    .date-format = %B %-d, %Y
+    .you = You
+    .deleted = This message was deleted
+    .edited = (edited)
+    .draft = Draft
+    .typing-one = is typing…
+    .typing-many = are typing…
+    .typing-and = and
+    .typing-others = others
+    .send-failed = Couldn't send that message.
+    .edit-failed = Couldn't edit that message.
+    .delete-failed = Couldn't delete that message.
+    .react-failed = Couldn't update that reaction.
 
 favorites = Favorites
     .add = Add to Favorites
     .remove = Remove from Favorites
 
+groups = Groups
+    .new = New Group
+    .name = Group name (optional)
+    .create = Create Group
+    .cancel = Cancel
+    .pick-friends = Pick at least two friends.
+    .members = Members
+    .add-member = Add to Group
+    .remove-member = Remove from Group
+    .create-failed = Couldn't create that group.
+    .members-failed = Couldn't update the group's members.
+    .unknown-member = Someone you're not friends with
+
 friends = Friends
     .cta-text = No friends yet. Add some!
     .add = Add Someone
@@ -36,6 +65,45 @@ friends = Friends
     .blocked = Blocked
     .accept = Accept
     .deny = Deny Request
+    .invalid-format = Enter a DID key or a Username#0000.
+    .invalid-did = That DID key isn't valid.
+    .invalid-username = Usernames are 4 to 32 characters long.
+    .invalid-short-id = The part after # should be 4 digits.
+    .not-found = No one was found with that DID key or username.
+    .lookup-failed = Couldn't look that user up, try again later.
+    .request-failed = Couldn't send that friend request.
+    .accept-failed = Couldn't accept that friend request.
+    .deny-failed = Couldn't deny that friend request.
+    .add-self = You can't add yourself.
+    .already-friends = You're already friends.
+    .add-blocked = Unblock this user before adding them.
+    .already-requested = You've already sent them a request.
+    .filter = Filter friends...
+    .no-matches = No friends match that name.
+    .note-placeholder = Add a private note, only you will see it (optional)...
+    .note-label = Your note:
+    .set-nickname = Set Nickname
+    .nickname-placeholder = Nickname, leave empty to use their username...
+    .requested-now = Requested just now.
+    .requested-minutes = Requested { $count ->
+        [one] a minute ago.
+       *[other] { $count } minutes ago.
+    }
+    .requested-hours = Requested { $count ->
+        [one] an hour ago.
+       *[other] { $count } hours ago.
+    }
+    .requested-days = Requested { $count ->
+        [one] a day ago.
+       *[other] { $count } days ago.
+    }
+
+profile = Profile
+    .did = DID Key
+    .copy = Copy
+    .copied = Copied!
+    .shared-groups = Shared Groups
+    .no-shared-groups = No groups in common.
 
 files = Files
     .new-folder = New Folder
@@ -47,7 +115,48 @@ settings = Settings
     .general = General 
     .general-app-language = App Language
     .general-change-language = Change Language
+    .general-markdown = Markdown
+    .general-markdown-description = Render bold, italics, code, quotes, lists and links in messages. Turn off to show messages exactly as typed.
+    .general-request-expiry = Friend Request Expiry
+    .general-request-expiry-description = Friend requests you sent that go unanswered are cancelled after this long.
+    .general-request-expiry-days = { $count ->
+        [one] { $count } day
+       *[other] { $count } days
+    }
+    .general-group-gap = Message Grouping
+    .general-group-gap-description = Messages someone sends in a row are grouped together, unless the conversation goes quiet for this long.
+    .general-group-gap-minutes = { $count ->
+        [one] { $count } minute
+       *[other] { $count } minutes
+    }
     .privacy = Privacy
     .audio = Audio
     .extensions = Extensions
-    .developer = Developer
\ No newline at end of file
+    .developer = Developer
+
+journal = Undo
+    .redo = Redo
+    .undo-action = Undo Last Action
+    .redo-action = Redo Last Action
+    .remove-friend = Friend removed.
+    .block = User blocked.
+    .hide-chat = Chat hidden.
+    .unfavorite = Removed from favorites.
+    .remove-member = Member removed.
+
+unlock = Unlock
+    .busy = Unlocking...
+    .description = Enter the passphrase of this profile to continue. On a new profile, the one you enter becomes its passphrase.
+    .placeholder = Passphrase...
+    .error = That passphrase didn't work.
+
+profiles = Choose a profile
+    .create = Create
+    .placeholder = New profile name...
+    .switch = Switch Profile
+    .current = Current profile
+
+search = Search
+    .results = Results
+    .no-results = No messages found.
+    .you = You
\ No newline at end of file
@@ -11,16 +11,45 @@ uplink = Uplink
     .welcome = Bem-vindo
     .clear-unreads = Limpar não lidos
     .hide-chat = Esconder Chat
+    .messaging-failed = Não foi possível conectar à rede, mensagens não serão enviadas nem recebidas.
 
 
 messages = Messages
     .new = Nova Mensagem
     .replying = Respondendo a:
+    .today = Hoje
+    .yesterday = Ontem
+    .date-format = %-d de %B de %Y
+    .you = Você
+    .deleted = Esta mensagem foi apagada
+    .edited = (editada)
+    .draft = Rascunho
+    .typing-one = está digitando…
+    .typing-many = estão digitando…
+    .typing-and = e
+    .typing-others = outros
+    .send-failed = Não foi possível enviar essa mensagem.
+    .edit-failed = Não foi possível editar essa mensagem.
+    .delete-failed = Não foi possível apagar essa mensagem.
+    .react-failed = Não foi possível atualizar essa reação.
 
 favorites = Favorites
     .add = Adicione aos favoritos
     .remove = Remova dos favoritos
 
+groups = Grupos
+    .new = Novo grupo
+    .name = Nome do grupo (opcional)
+    .create = Criar grupo
+    .cancel = Cancelar
+    .pick-friends = Escolha pelo menos dois amigos.
+    .members = Membros
+    .add-member = Adicionar ao grupo
+    .remove-member = Remover do grupo
+    .create-failed = Não foi possível criar esse grupo.
+    .members-failed = Não foi possível atualizar os membros do grupo.
+    .unknown-member = Alguém que não é seu amigo
+
 friends = Friends
     .cta-text = Sem amigos ainda. Adicione alguém!
     .add = Adicione alguém
@@ -36,6 +65,45 @@ friends = Friends
     .blocked = Bloqueado
     .accept = Aceitar
     .deny = Recusar solicitação
+    .invalid-format = Digite uma chave DID ou um nome_de_usuario#0000.
+    .invalid-did = Essa chave DID não é válida.
+    .invalid-username = Nomes de usuário têm de 4 a 32 caracteres.
+    .invalid-short-id = A parte depois do # deve ter 4 dígitos.
+    .not-found = Ninguém foi encontrado com essa chave DID ou nome de usuário.
+    .lookup-failed = Não foi possível buscar esse usuário, tente novamente mais tarde.
+    .request-failed = Não foi possível enviar essa solicitação de amizade.
+    .accept-failed = Não foi possível aceitar essa solicitação de amizade.
+    .deny-failed = Não foi possível recusar essa solicitação de amizade.
+    .add-self = Você não pode adicionar a si mesmo.
+    .already-friends = Vocês já são amigos.
+    .add-blocked = Desbloqueie este usuário antes de adicioná-lo.
+    .already-requested = Você já enviou uma solicitação.
+    .filter = Filtrar amigos...
+    .no-matches = Nenhum amigo com esse nome.
+    .note-placeholder = Adicione uma nota privada, só você vai vê-la (opcional)...
+    .note-label = Sua nota:
+    .set-nickname = Definir Apelido
+    .nickname-placeholder = Apelido, deixe vazio para usar o nome de usuário...
+    .requested-now = Solicitado agora mesmo.
+    .requested-minutes = Solicitado há { $count ->
+        [one] um minuto.
+       *[other] { $count } minutos.
+    }
+    .requested-hours = Solicitado há { $count ->
+        [one] uma hora.
+       *[other] { $count } horas.
+    }
+    .requested-days = Solicitado há { $count ->
+        [one] um dia.
+       *[other] { $count } dias.
+    }
+
+profile = Perfil
+    .did = Chave DID
+    .copy = Copiar
+    .copied = Copiado!
+    .shared-groups = Grupos em comum
+    .no-shared-groups = Nenhum grupo em comum.
 
 files = Files
     .new-folder = Nova Pasta
@@ -47,7 +115,48 @@ settings = Settings
     .general = Geral 
     .general-app-language = Idioma do aplicativo
     .general-change-language = Mude o idioma
+    .general-markdown = Markdown
+    .general-markdown-description = Mostra negrito, itálico, código, citações, listas e links nas mensagens. Desative para ver as mensagens exatamente como foram digitadas.
+    .general-request-expiry = Expiração de solicitações de amizade
+    .general-request-expiry-description = Solicitações de amizade enviadas sem resposta são canceladas depois desse tempo.
+    .general-request-expiry-days = { $count ->
+        [one] { $count } dia
+       *[other] { $count } dias
+    }
+    .general-group-gap = Agrupamento de mensagens
+    .general-group-gap-description = Mensagens enviadas em sequência pela mesma pessoa são agrupadas, a menos que a conversa fique parada por esse tempo.
+    .general-group-gap-minutes = { $count ->
+        [one] { $count } minuto
+       *[other] { $count } minutos
+    }
     .privacy = Privacidade
     .audio = Audio
     .extensions = Extensões
     .developer = Desenvolvedor
+
+journal = Desfazer
+    .redo = Refazer
+    .undo-action = Desfazer última ação
+    .redo-action = Refazer última ação
+    .remove-friend = Amizade desfeita.
+    .block = Usuário bloqueado.
+    .hide-chat = Chat escondido.
+    .unfavorite = Removido dos favoritos.
+    .remove-member = Membro removido.
+
+unlock = Desbloquear
+    .busy = Desbloqueando...
+    .description = Digite a senha deste perfil para continuar. Em um perfil novo, a senha digitada passa a ser a dele.
+    .placeholder = Senha...
+    .error = Essa senha não funcionou.
+
+profiles = Escolha um perfil
+    .create = Criar
+    .placeholder = Nome do novo perfil...
+    .switch = Trocar Perfil
+    .current = Perfil atual
+
+search = Pesquisar
+    .results = Resultados
+    .no-results = Nenhuma mensagem encontrada.
+    .you = Você
\ No newline at end of file
@@ -1,16 +1,24 @@
 
-use chrono::{DateTime, Utc};
+use std::time::Duration;
+
+use chrono::{DateTime, Local, Utc};
 use dioxus::prelude::*;
 use fluent_templates::Loader;
 use timeago::Formatter;
-use ui_kit::{layout::{topbar::Topbar, chatbar::{Chatbar, Reply}}, components::{user_image::UserImage, indicator::{Status, Platform}, context_menu::{ContextMenu, ContextItem}, message_group::MessageGroup, message::{Message, Order}, user_image_group::UserImageGroup}, elements::{button::Button, tooltip::{Tooltip, ArrowPosition}, Appearance}, icons::Icon};
-use warp::multipass::identity::Identity;
+use ui_kit::{layout::{topbar::Topbar, chatbar::{Chatbar, Reply}}, components::{user_image::UserImage, context_menu::{ContextMenu, ContextItem}, message_group::MessageGroup, message_divider::MessageDivider, message::{Message, Order, markdown::find_urls}, message_reply::MessageReply, message_typing::MessageTyping, user_image_group::UserImageGroup, reaction_picker::ReactionPicker, reactions::{Reactions, ReactionSummary}}, elements::{button::Button, input::Input, tooltip::{Tooltip, ArrowPosition}, Appearance}, icons::Icon};
+use uuid::Uuid;
+use warp::{multipass::identity::Identity, raygun::{self, Reaction}};
 
-use crate::{state::{State, Action}, components::{chat::sidebar::build_participants, media::player::MediaPlayer}, LOCALES, APP_LANG};
+use crate::{state::{State, Action, history::Page}, components::{chat::{sidebar::build_participants, group::GroupMembers, link_preview::LinkPreviewCard}, media::player::MediaPlayer}, utils::language::date_locale, warp_runner, LOCALES, APP_LANG};
 
 
 use super::sidebar::build_participants_names;
 
+const HISTORY_SCRIPT: &str = include_str!("./history.js");
+
+// How long typing has to pause before what's in the chatbar is saved as the chat's draft.
+const DRAFT_SAVE_DELAY: Duration = Duration::from_secs(1);
+
 
 fn format_timestamp(datetime: DateTime<Utc>) -> String {
     let formatter = Formatter::new();
@@ -19,9 +27,118 @@ fn format_timestamp(datetime: DateTime<Utc>) -> String {
     formatter.convert(duration)
 }
 
+/// Labels a date separator with the day, relative to today when it's recent.
+fn format_day(datetime: DateTime<Utc>) -> String {
+    let day = datetime.with_timezone(&Local).naive_local().date();
+    let today = Local::now().naive_local().date();
+
+    if day == today {
+        LOCALES.lookup(&*APP_LANG.read(), "messages.today").unwrap_or_default()
+    } else if today.pred_opt() == Some(day) {
+        LOCALES.lookup(&*APP_LANG.read(), "messages.yesterday").unwrap_or_default()
+    } else {
+        let format = LOCALES.lookup(&*APP_LANG.read(), "messages.date-format").unwrap_or_default();
+        datetime.with_timezone(&Local).format_localized(&format, date_locale()).to_string()
+    }
+}
+
+/// Groups a message's reactions by emoji, naming who reacted with each.
+fn summarize_reactions(state: &UseSharedState<State>, message: &raygun::Message, you_text: &str) -> Vec<ReactionSummary> {
+    let state = state.read();
+    let me = state.account.identity.did_key();
+    message.reactions().iter().map(|reaction| {
+        let users = reaction.users();
+        ReactionSummary {
+            emoji: reaction.emoji(),
+            reacted: users.contains(&me),
+            users: users.iter().map(|did| {
+                if *did == me { you_text.to_string() } else { state.display_name(&state.get_friend_identity(did)) }
+            }).collect(),
+        }
+    }).collect()
+}
+
+/// Toggles our own reaction with `emoji` on a message.
+fn react(state: &UseSharedState<State>, message: &raygun::Message, emoji: &str) {
+    let chat = match state.read().get_active_chat() {
+        Some(c) => c,
+        None => return,
+    };
+
+    let mut reaction = Reaction::default();
+    reaction.set_emoji(emoji);
+    reaction.set_users(vec![state.read().account.identity.did_key()]);
+    state.write().mutate(Action::React(chat, message.clone(), reaction));
+}
+
+/// Replaces the text of one of our messages. Leaving the text unchanged or clearing it
+/// entirely cancels the edit.
+fn edit_message(state: &UseSharedState<State>, message: &raygun::Message, text: &str) {
+    let chat = match state.read().get_active_chat() {
+        Some(c) => c,
+        None => return,
+    };
+
+    if text.trim().is_empty() || text == message.value().join("\n") {
+        return;
+    }
+
+    let mut edited = message.clone();
+    edited.set_value(text.lines().map(String::from).collect());
+    state.write().mutate(Action::Edit(chat, edited));
+}
+
+/// Names everyone typing, e.g. "Alice and Bob are typing…". Past three people, the rest
+/// are counted instead of named.
+fn typing_text(names: &[String]) -> String {
+    let lookup = |key: &str| LOCALES.lookup(&*APP_LANG.read(), key).unwrap_or_default();
+    let and = lookup("messages.typing-and");
+
+    let who = match names {
+        [] => return String::new(),
+        [name] => name.clone(),
+        [first @ .., last] if names.len() <= 3 => format!("{} {} {}", first.join(", "), and, last),
+        [first, second, rest @ ..] => format!("{}, {} {} {} {}", first, second, and, rest.len(), lookup("messages.typing-others")),
+    };
+    let verb = if names.len() == 1 { lookup("messages.typing-one") } else { lookup("messages.typing-many") };
+    format!("{} {}", who, verb)
+}
+
+/// Dispatches the chatbar contents to the active chat, as a reply if one is in progress.
+fn send_message(state: &UseSharedState<State>, text: &str) {
+    let lines: Vec<String> = text.lines().map(String::from).collect();
+    if lines.iter().all(|line| line.trim().is_empty()) {
+        return;
+    }
+
+    let chat = match state.read().get_active_chat() {
+        Some(c) => c,
+        None => return,
+    };
+
+    let mut message = raygun::Message::default();
+    message.set_conversation_id(chat.id);
+    message.set_sender(state.read().account.identity.did_key());
+    message.set_value(lines);
+
+    match chat.replying_to.clone() {
+        Some(replying_to) => {
+            message.set_replied(Some(replying_to.id()));
+            state.write().mutate(Action::Reply(chat, message));
+        }
+        None => state.write().mutate(Action::Send(chat, message)),
+    }
+}
+
 #[allow(non_snake_case)]
 pub fn Compose(cx: Scope) -> Element {
     let state: UseSharedState<State> = use_context::<State>(&cx).unwrap();
+    // The message the reaction picker is open for.
+    let reacting_to = use_state(&cx, || None::<Uuid>);
+    // The message being edited in place.
+    let editing = use_state(&cx, || None::<Uuid>);
+    // Whether the members of a group chat are listed below the topbar.
+    let showing_members = use_state(&cx, || false);
     let active_chat = state.read().get_active_chat().unwrap_or_default();
     let message_groups = state.read().get_sort_messages(&active_chat);
 
@@ -35,18 +152,75 @@ pub fn Compose(cx: Scope) -> Element {
 
     let subtext = active_participant.status_message().unwrap_or_default();
 
+    let typists: Vec<Identity> = active_chat.participants.iter()
+        .filter(|identity| active_chat.typing.contains_key(&identity.did_key()))
+        .cloned()
+        .collect();
+    let typists_text = typing_text(&typists.iter().map(|identity| state.read().display_name(identity)).collect::<Vec<_>>());
+
     let is_favorite = state.read().is_favorite(&active_chat);
 
     let reply_message = match state.read().get_active_chat().unwrap_or_default().replying_to {
         Some(m) => m.value().join("\n").to_string(),
         None => "".into(),
     };
+    let reply_presence = active_chat.replying_to.as_ref().map(|m| state.read().get_presence(&m.sender())).unwrap_or_default();
 
     let first_image = active_participant.graphics().profile_picture();
-    let participants_name = build_participants_names(&without_me);
+    let first_presence = state.read().get_presence(&active_participant.did_key());
+    let first_did = active_participant.did_key();
+    let typist_presence = typists.first().map(|typist| state.read().get_presence(&typist.did_key())).unwrap_or_default();
+    let participants_name = active_chat.name.clone().unwrap_or_else(|| build_participants_names(&state.read(), &without_me));
+    let is_group = active_chat.group;
+    let members_chat = active_chat.clone();
 
     let active_media = active_chat.active_media;
     let active_media_chat = active_chat.clone();
+    let messages_chat = active_chat.clone();
+    // The chatbar text lives here while typing, the chat's draft only catches up once typing
+    // pauses. Switching chats picks up the other chat's draft.
+    let typed = use_state(&cx, || None::<(Uuid, String)>);
+    let text = match typed.get() {
+        Some((chat_id, text)) if *chat_id == active_chat.id => text.clone(),
+        _ => active_chat.draft.clone(),
+    };
+    let chat_id = active_chat.id;
+    let send_text_value = text.clone();
+    let pending = typed.clone();
+    let inner = state.inner();
+    use_future(&cx, (typed.get(),), move |(typed,)| async move {
+        let (chat_id, text) = match typed {
+            Some(typed) => typed,
+            None => return,
+        };
+        tokio::time::sleep(DRAFT_SAVE_DELAY).await;
+        // Anything typed in the meantime is saved by its own timer.
+        if *pending.current() != Some((chat_id, text.clone())) {
+            return;
+        }
+        let chat = inner.borrow().read().chats.all.get(&chat_id).cloned();
+        if let Some(chat) = chat.filter(|chat| chat.draft != text) {
+            inner.borrow().write().mutate(Action::SetDraft(chat, text));
+            inner.borrow_mut().notify_consumers();
+        }
+    });
+    let markdown = !state.read().settings.plain_text;
+    let link_previews = state.read().settings.link_previews;
+    let has_older = active_chat.has_more(Page::Older);
+    let has_newer = active_chat.has_more(Page::Newer);
+    let older_chat = active_chat.clone();
+    let newer_chat = active_chat.clone();
+    // Scrolls to the message picked from the search results once it's rendered.
+    let scroll_script = active_chat.scroll_to
+        .filter(|id| active_chat.messages.iter().any(|m| m.id() == *id))
+        .map(|id| format!(r#"document.getElementById("message-{}")?.scrollIntoView({{ block: "center" }})"#, id));
+    // Sentinels are keyed on the edges of the window, so every load brings fresh ones for the
+    // observer to watch. Scripts only run when inserted, so it's re-inserted along with them.
+    let older_anchor = active_chat.page_anchor(Page::Older).map(|m| m.id()).unwrap_or_default();
+    let newer_anchor = active_chat.page_anchor(Page::Newer).map(|m| m.id()).unwrap_or_default();
+    let older_key = format!("{}-older-{}", active_chat.id, older_anchor);
+    let newer_key = format!("{}-newer-{}", active_chat.id, newer_anchor);
+    let history_key = format!("{}-{}-{}", active_chat.id, older_anchor, newer_anchor);
 
 
     let add_text = LOCALES
@@ -61,13 +235,27 @@ pub fn Compose(cx: Scope) -> Element {
     let video_call_text = LOCALES
         .lookup(&*APP_LANG.read(), "uplink.video-call")
         .unwrap_or_default();    
-    // TODO: Pending new message divider implementation
-    let _new_message_text = LOCALES
+    let new_message_text = LOCALES
         .lookup(&*APP_LANG.read(), "messages.new")
         .unwrap_or_default();
     let upload_text = LOCALES
         .lookup(&*APP_LANG.read(), "files.upload")
         .unwrap_or_default();
+    let members_text = LOCALES
+        .lookup(&*APP_LANG.read(), "groups.members")
+        .unwrap_or_default();
+    let unknown_member_text = LOCALES
+        .lookup(&*APP_LANG.read(), "groups.unknown-member")
+        .unwrap_or_default();
+    let you_text = LOCALES
+        .lookup(&*APP_LANG.read(), "messages.you")
+        .unwrap_or_default();
+    let deleted_text = LOCALES
+        .lookup(&*APP_LANG.read(), "messages.deleted")
+        .unwrap_or_default();
+    let edited_text = LOCALES
+        .lookup(&*APP_LANG.read(), "messages.edited")
+        .unwrap_or_default();
 
 
     cx.render(rsx!(
@@ -113,19 +301,33 @@ pub fn Compose(cx: Scope) -> Element {
                                 }
                             )),
                         },
+                        is_group.then(|| rsx!(
+                            Button {
+                                icon: Icon::Users,
+                                appearance: if **showing_members { Appearance::Primary } else { Appearance::Secondary },
+                                tooltip: cx.render(rsx!(
+                                    Tooltip { 
+                                        arrow_position: ArrowPosition::Top, 
+                                        text: members_text
+                                    }
+                                )),
+                                onpress: move |_| showing_members.set(!**showing_members),
+                            },
+                        )),
                     )
                 ),
                 cx.render(
                     rsx! (
                         if without_me.len() < 2 {rsx! (
                             UserImage {
-                                platform: Platform::Mobile,
-                                status: Status::Online,
-                                image: first_image
+                                platform: first_presence.indicator_platform(),
+                                status: first_presence.indicator_status(),
+                                image: first_image,
+                                onpress: move |_| state.write().mutate(Action::ShowProfile(first_did.clone())),
                             }
                         )} else {rsx! (
                             UserImageGroup {
-                                participants: build_participants(&without_me)
+                                participants: build_participants(&state.read(), &without_me)
                             }
                         )}
                         div {
@@ -142,63 +344,242 @@ pub fn Compose(cx: Scope) -> Element {
                     )
                 ),
             },
+            (is_group && **showing_members).then(|| rsx!(
+                GroupMembers {
+                    chat: members_chat,
+                }
+            )),
             active_media.then(|| rsx!(
                 MediaPlayer {},
             )),
             div {
                 id: "messages",
                 div {
+                    has_older.then(|| rsx!(
+                        div {
+                            key: "{older_key}",
+                            class: "history-sentinel",
+                            onclick: move |_| state.write().mutate(Action::LoadMessages(older_chat.clone(), Page::Older)),
+                        }
+                    )),
                     message_groups.iter().map(|group| {
                         let messages = &group.messages;
                         let last_message = messages.last().unwrap().message.clone();
                         let sender = state.read().get_friend_identity(&group.sender);
+                        let sender_name = state.read().display_name(&sender);
+                        let sender_presence = state.read().get_presence(&group.sender);
+                        let sender_did = group.sender.clone();
+                        let remote = group.remote;
                         
                         rsx!(
+                            group.divider.as_ref().and_then(|divider| divider.timestamp).map(|timestamp| rsx!(
+                                MessageDivider {
+                                    text: format_day(timestamp),
+                                }
+                            )),
+                            group.first_unread.then(|| rsx!(
+                                MessageDivider {
+                                    text: new_message_text.clone(),
+                                }
+                            )),
                             MessageGroup {
                                 user_image: cx.render(rsx!(
                                     UserImage {
-                                        platform: Platform::Mobile,
-                                        status: Status::Online
+                                        platform: sender_presence.indicator_platform(),
+                                        status: sender_presence.indicator_status(),
+                                        onpress: move |_| {
+                                            // Our own messages have nobody else's profile to show.
+                                            if remote {
+                                                state.write().mutate(Action::ShowProfile(sender_did.clone()));
+                                            }
+                                        },
                                     }
                                 )),
                                 timestamp: format_timestamp(last_message.date()),
-                                with_sender: if sender.username().is_empty() { "You".into() } else { sender.username()},
+                                // Group members we aren't friends with have no name we know of.
+                                with_sender: if !remote { you_text.clone() } else if sender_name.is_empty() { unknown_member_text.clone() } else { sender_name },
                                 remote: group.remote,
                                 messages.iter().map(|grouped_message| {
                                     let message = grouped_message.message.clone();
                                     let reply_message = grouped_message.message.clone();
+                                    let reactions = summarize_reactions(&state, &message, &you_text);
+                                    let reaction_message = message.clone();
+                                    let picker_message = message.clone();
+                                    let edit_message_value = message.clone();
+                                    let delete_message = message.clone();
+                                    let message_id = message.id();
+                                    let is_reacting = *reacting_to.get() == Some(message_id);
+                                    let is_editing = *editing.get() == Some(message_id);
+                                    let is_deleted = messages_chat.is_deleted(&message_id);
+                                    let is_edited = messages_chat.is_edited(&message_id);
+                                    // Only the first link of a message gets a preview.
+                                    let preview_url = (link_previews && !is_deleted)
+                                        .then(|| find_urls(&message.value().join("\n"), markdown).into_iter().next())
+                                        .flatten();
+                                    // Shows what the message replies to, which may be a tombstone by now.
+                                    let replied_to = message.replied()
+                                        .and_then(|id| messages_chat.messages.iter().find(|m| m.id() == id))
+                                        .map(|m| (
+                                            if messages_chat.is_deleted(&m.id()) { deleted_text.clone() } else { m.value().join("\n") },
+                                            m.sender() != state.read().account.identity.did_key(),
+                                        ));
                                     rsx! (
-                                        ContextMenu {
-                                            id: format!("message-{}", message.id()),
-                                            items: cx.render(rsx!(
-                                                ContextItem {
-                                                    icon: Icon::ArrowLongLeft,
-                                                    text: String::from("Reply"),
-                                                    onpress: move |_| {
-                                                        let chat = state.read().get_active_chat().unwrap_or_default();
-                                                        state.write().mutate(Action::StartReplying(chat, reply_message.clone()));
+                                        replied_to.map(|(text, remote_message)| rsx!(
+                                            MessageReply {
+                                                remote: group.remote,
+                                                remote_message: remote_message,
+                                                with_text: text,
+                                            }
+                                        )),
+                                        if is_editing { rsx!(
+                                            div {
+                                                class: "message-edit",
+                                                Input {
+                                                    placeholder: String::new(),
+                                                    default_text: message.value().join("\n"),
+                                                    onreturn: move |v: String| {
+                                                        edit_message(&state, &edit_message_value, &v);
+                                                        editing.set(None);
                                                     }
                                                 },
-                                                ContextItem {
-                                                    icon: Icon::FaceSmile,
-                                                    text: String::from("React"),
-                                                    //TODO: Wire to state
-                                                },
-                                            )),
-                                            Message {
+                                                Button {
+                                                    icon: Icon::XMark,
+                                                    appearance: Appearance::Secondary,
+                                                    onpress: move |_| editing.set(None),
+                                                }
+                                            }
+                                        )} else { rsx!(
+                                            ContextMenu {
+                                                id: format!("message-{}", message.id()),
+                                                items: cx.render(rsx!(
+                                                    (!is_deleted).then(|| rsx!(
+                                                        ContextItem {
+                                                            icon: Icon::ArrowLongLeft,
+                                                            text: String::from("Reply"),
+                                                            onpress: move |_| {
+                                                                let chat = state.read().get_active_chat().unwrap_or_default();
+                                                                state.write().mutate(Action::StartReplying(chat, reply_message.clone()));
+                                                            }
+                                                        },
+                                                        ContextItem {
+                                                            icon: Icon::FaceSmile,
+                                                            text: String::from("React"),
+                                                            onpress: move |_| reacting_to.set(Some(message_id)),
+                                                        },
+                                                    )),
+                                                    // Only our own messages can be changed.
+                                                    (!group.remote && !is_deleted).then(|| rsx!(
+                                                        ContextItem {
+                                                            icon: Icon::PencilSquare,
+                                                            text: String::from("Edit"),
+                                                            onpress: move |_| editing.set(Some(message_id)),
+                                                        },
+                                                        ContextItem {
+                                                            icon: Icon::Trash,
+                                                            text: String::from("Delete"),
+                                                            danger: true,
+                                                            onpress: move |_| {
+                                                                let chat = state.read().get_active_chat().unwrap_or_default();
+                                                                state.write().mutate(Action::Delete(chat, delete_message.clone()));
+                                                            }
+                                                        },
+                                                    )),
+                                                )),
+                                                Message {
+                                                    remote: group.remote,
+                                                    with_text: if is_deleted { deleted_text.clone() } else { message.value().join("\n") },
+                                                    order: if grouped_message.is_first { Order::First } else if grouped_message.is_last { Order::Last } else { Order::Middle },
+                                                    markdown: markdown && !is_deleted,
+                                                    edited: is_edited,
+                                                    edited_text: edited_text.clone(),
+                                                    tombstone: is_deleted,
+                                                    onlink: move |url: String| {
+                                                        let _ = open::that(url);
+                                                    }
+                                                }
+                                            }
+                                        )},
+                                        preview_url.map(|url| rsx!(
+                                            LinkPreviewCard {
+                                                key: "{url}",
+                                                url: url.clone(),
                                                 remote: group.remote,
-                                                with_text: message.value().join("\n"),
-                                                order: if grouped_message.is_first { Order::First } else if grouped_message.is_last { Order::Last } else { Order::Middle },
                                             }
-                                        }
+                                        )),
+                                        (!reactions.is_empty()).then(|| rsx!(
+                                            Reactions {
+                                                reactions: reactions,
+                                                remote: group.remote,
+                                                onpress: move |emoji: String| react(&state, &reaction_message, &emoji),
+                                            }
+                                        )),
+                                        is_reacting.then(|| rsx!(
+                                            ReactionPicker {
+                                                remote: group.remote,
+                                                onselect: move |emoji: String| {
+                                                    react(&state, &picker_message, &emoji);
+                                                    reacting_to.set(None);
+                                                },
+                                                onclose: move |_| reacting_to.set(None),
+                                            }
+                                        ))
                                     )
                                 })
                             }
                         )
-                    })
+                    }),
+                    has_newer.then(|| rsx!(
+                        div {
+                            key: "{newer_key}",
+                            class: "history-sentinel",
+                            onclick: move |_| state.write().mutate(Action::LoadMessages(newer_chat.clone(), Page::Newer)),
+                        }
+                    )),
+                    std::iter::once(history_key).map(|key| rsx!(
+                        script {
+                            key: "{key}",
+                            "{HISTORY_SCRIPT}"
+                        }
+                    )),
+                    scroll_script.into_iter().map(|script| rsx!(
+                        script {
+                            key: "{script}",
+                            "{script}"
+                        }
+                    ))
                 }
             },
+            (!typists.is_empty()).then(|| rsx!(
+                div {
+                    class: "typing-indicator",
+                    MessageTyping {
+                        text: typists_text,
+                        user_image: cx.render(rsx!(
+                            if typists.len() == 1 {rsx!(
+                                UserImage {
+                                    platform: typist_presence.indicator_platform(),
+                                    status: typist_presence.indicator_status(),
+                                    image: typists[0].graphics().profile_picture(),
+                                }
+                            )} else {rsx!(
+                                UserImageGroup {
+                                    participants: build_participants(&state.read(), &typists)
+                                }
+                            )}
+                        ))
+                    }
+                }
+            )),
             Chatbar {
+                value: text,
+                onchange: move |v: String| {
+                    warp_runner::typing(chat_id, !v.trim().is_empty());
+                    typed.set(Some((chat_id, v)));
+                },
+                onreturn: move |v: String| {
+                    send_message(&state, &v);
+                    typed.set(Some((chat_id, String::new())));
+                },
                 controls: cx.render(rsx!(
                     Button {
                         icon: Icon::ChevronDoubleRight,
@@ -209,6 +590,10 @@ pub fn Compose(cx: Scope) -> Element {
                                 text: send_text
                             }
                         )),
+                        onpress: move |_| {
+                            send_message(&state, &send_text_value);
+                            typed.set(Some((chat_id, String::new())));
+                        }
                     },
                 )),
                 with_replying_to: cx.render(rsx!(
@@ -225,8 +610,8 @@ pub fn Compose(cx: Scope) -> Element {
                             },
                             message: reply_message,
                             UserImage {
-                                platform: Platform::Mobile,
-                                status: Status::Online
+                                platform: reply_presence.indicator_platform(),
+                                status: reply_presence.indicator_status(),
                             },
                         }
                     ))
@@ -0,0 +1,244 @@
+use std::collections::HashSet;
+
+use dioxus::prelude::*;
+use fluent_templates::Loader;
+use ui_kit::{
+    components::{user::User, user_image::UserImage},
+    elements::{button::Button, input::Input, label::Label, Appearance},
+    icons::Icon,
+};
+use warp::{crypto::DID, multipass::identity::Identity};
+
+use crate::{
+    state::{Action, Chat, State},
+    APP_LANG, LOCALES,
+};
+
+// A group needs at least this many friends besides us.
+const MIN_GROUP_FRIENDS: usize = 2;
+
+/// Friends sorted by name, optionally leaving out the members of a chat.
+fn sorted_friends(state: &UseSharedState<State>, except: Option<&Chat>) -> Vec<Identity> {
+    let mut friends: Vec<Identity> = state
+        .read()
+        .friends
+        .all
+        .values()
+        .filter(|friend| {
+            except.map_or(true, |chat| {
+                !chat.participants.iter().any(|p| p.did_key() == friend.did_key())
+            })
+        })
+        .cloned()
+        .collect();
+    friends.sort_by_cached_key(|friend| state.read().display_name(friend).to_lowercase());
+    friends
+}
+
+#[derive(Props)]
+pub struct NewGroupProps<'a> {
+    // Called once the group has been requested, the chat opens when RayGun creates it.
+    oncreate: EventHandler<'a>,
+    oncancel: EventHandler<'a>,
+}
+
+/// Picks the friends to start a group chat with, and optionally a name for it.
+#[allow(non_snake_case)]
+pub fn NewGroup<'a>(cx: Scope<'a, NewGroupProps<'a>>) -> Element<'a> {
+    let state: UseSharedState<State> = use_context::<State>(&cx)?;
+    let name = use_state(&cx, String::new);
+    let selected = use_state(&cx, HashSet::<DID>::new);
+
+    let friends = sorted_friends(&state, None);
+    let enough_selected = selected.len() >= MIN_GROUP_FRIENDS;
+
+    let new_group_text = LOCALES
+        .lookup(&*APP_LANG.read(), "groups.new")
+        .unwrap_or_default();
+    let name_placeholder = LOCALES
+        .lookup(&*APP_LANG.read(), "groups.name")
+        .unwrap_or_default();
+    let pick_friends_text = LOCALES
+        .lookup(&*APP_LANG.read(), "groups.pick-friends")
+        .unwrap_or_default();
+    let create_text = LOCALES
+        .lookup(&*APP_LANG.read(), "groups.create")
+        .unwrap_or_default();
+    let cancel_text = LOCALES
+        .lookup(&*APP_LANG.read(), "groups.cancel")
+        .unwrap_or_default();
+
+    let members = friends.clone();
+
+    cx.render(rsx!(
+        div {
+            id: "new-group",
+            Label {
+                text: new_group_text
+            },
+            Input {
+                placeholder: name_placeholder,
+                onchange: move |value: String| name.set(value),
+            },
+            div {
+                class: "group-friends vertically-scrollable",
+                friends.into_iter().map(|friend| {
+                    let did = friend.did_key();
+                    let presence = state.read().get_presence(&did);
+                    let key = did.to_string();
+                    let active = selected.contains(&did);
+                    rsx!(
+                        User {
+                            key: "{key}",
+                            username: state.read().display_name(&friend),
+                            subtext: String::new(),
+                            active: active,
+                            user_image: cx.render(rsx!(
+                                UserImage {
+                                    platform: presence.indicator_platform(),
+                                    status: presence.indicator_status(),
+                                    image: friend.graphics().profile_picture(),
+                                }
+                            )),
+                            onpress: move |_| {
+                                let mut picked = selected.get().clone();
+                                if !picked.remove(&did) {
+                                    picked.insert(did.clone());
+                                }
+                                selected.set(picked);
+                            }
+                        }
+                    )
+                })
+            },
+            (!enough_selected).then(|| rsx!(
+                p {
+                    class: "group-hint",
+                    "{pick_friends_text}"
+                }
+            )),
+            div {
+                class: "group-controls",
+                Button {
+                    text: cancel_text,
+                    appearance: Appearance::Secondary,
+                    onpress: move |_| cx.props.oncancel.call(()),
+                },
+                enough_selected.then(|| rsx!(
+                    Button {
+                        text: create_text,
+                        icon: Icon::UserGroup,
+                        appearance: Appearance::Primary,
+                        onpress: move |_| {
+                            let picked: Vec<Identity> = members
+                                .iter()
+                                .filter(|friend| selected.contains(&friend.did_key()))
+                                .cloned()
+                                .collect();
+                            let name = Some(name.trim().to_string()).filter(|name| !name.is_empty());
+                            state.write().mutate(Action::CreateGroup(picked, name));
+                            cx.props.oncreate.call(());
+                        }
+                    }
+                )),
+            }
+        }
+    ))
+}
+
+#[derive(PartialEq, Props)]
+pub struct GroupMembersProps {
+    chat: Chat,
+}
+
+/// Lists the members of a group chat, with friends that can still be added below them.
+#[allow(non_snake_case)]
+pub fn GroupMembers(cx: Scope<GroupMembersProps>) -> Element {
+    let state: UseSharedState<State> = use_context::<State>(&cx)?;
+
+    let members = state.read().get_without_me(cx.props.chat.participants.clone());
+    let others = sorted_friends(&state, Some(&cx.props.chat));
+    // Removing the last member would leave us alone in the group.
+    let can_remove = members.len() > 1;
+
+    let members_text = LOCALES
+        .lookup(&*APP_LANG.read(), "groups.members")
+        .unwrap_or_default();
+    let add_text = LOCALES
+        .lookup(&*APP_LANG.read(), "groups.add-member")
+        .unwrap_or_default();
+    let remove_text = LOCALES
+        .lookup(&*APP_LANG.read(), "groups.remove-member")
+        .unwrap_or_default();
+
+    cx.render(rsx!(
+        div {
+            id: "group-members",
+            Label {
+                text: members_text
+            },
+            members.into_iter().map(|member| {
+                let did = member.did_key();
+                let presence = state.read().get_presence(&did);
+                let username = state.read().display_name(&member);
+                let chat = cx.props.chat.clone();
+                let remove_text = remove_text.clone();
+                rsx!(
+                    div {
+                        key: "{did}",
+                        class: "group-member",
+                        UserImage {
+                            platform: presence.indicator_platform(),
+                            status: presence.indicator_status(),
+                            image: member.graphics().profile_picture(),
+                        },
+                        p {
+                            "{username}"
+                        },
+                        can_remove.then(|| rsx!(
+                            Button {
+                                icon: Icon::XMark,
+                                small: true,
+                                appearance: Appearance::Secondary,
+                                text: remove_text,
+                                onpress: move |_| {
+                                    state.write().mutate(Action::RemoveMember(chat.clone(), member.clone()));
+                                }
+                            }
+                        ))
+                    }
+                )
+            }),
+            others.into_iter().map(|friend| {
+                let did = friend.did_key();
+                let presence = state.read().get_presence(&did);
+                let username = state.read().display_name(&friend);
+                let chat = cx.props.chat.clone();
+                let add_text = add_text.clone();
+                rsx!(
+                    div {
+                        key: "{did}",
+                        class: "group-member addable",
+                        UserImage {
+                            platform: presence.indicator_platform(),
+                            status: presence.indicator_status(),
+                            image: friend.graphics().profile_picture(),
+                        },
+                        p {
+                            "{username}"
+                        },
+                        Button {
+                            icon: Icon::UserPlus,
+                            small: true,
+                            appearance: Appearance::Secondary,
+                            text: add_text,
+                            onpress: move |_| {
+                                state.write().mutate(Action::AddMember(chat.clone(), friend.clone()));
+                            }
+                        }
+                    }
+                )
+            })
+        }
+    ))
+}
@@ -0,0 +1,21 @@
+// Loads more history once the top or bottom of the message list scrolls into view. Each
+// sentinel is replaced once its page is loaded, so it only needs to be observed once.
+(() => {
+  const messages = document.getElementById("messages")
+  const sentinels = document.getElementsByClassName("history-sentinel")
+  for (var i = 0; i < sentinels.length; i++) {
+    const sentinel = sentinels.item(i)
+    if (sentinel.dataset.observed) {
+      continue
+    }
+    sentinel.dataset.observed = true
+    new IntersectionObserver(
+      (entries) => {
+        if (entries.some((entry) => entry.isIntersecting)) {
+          sentinel.click()
+        }
+      },
+      { root: messages },
+    ).observe(sentinel)
+  }
+})()
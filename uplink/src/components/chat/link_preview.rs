@@ -0,0 +1,33 @@
+use dioxus::prelude::*;
+use ui_kit::components::link_preview::LinkPreview;
+
+use crate::previews;
+
+#[derive(PartialEq, Props)]
+pub struct Props {
+    url: String,
+    remote: bool,
+}
+
+/// Shows a preview card for a link once it's been fetched. Nothing is rendered for links
+/// that can't be previewed.
+#[allow(non_snake_case)]
+pub fn LinkPreviewCard(cx: Scope<Props>) -> Element {
+    let preview = use_future(&cx, (&cx.props.url,), |(url,)| previews::preview(url));
+
+    let preview = match preview.value() {
+        Some(Some(preview)) => preview.clone(),
+        _ => return None,
+    };
+
+    cx.render(rsx!(LinkPreview {
+        url: cx.props.url.clone(),
+        title: preview.title,
+        description: preview.description.unwrap_or_default(),
+        image: preview.image.unwrap_or_default(),
+        remote: cx.props.remote,
+        onpress: move |url: String| {
+            let _ = open::that(url);
+        }
+    }))
+}
@@ -1,6 +1,9 @@
 use ui_kit::components::nav::Route;
 
 pub mod compose;
+pub mod group;
+pub mod link_preview;
+pub mod search;
 pub mod sidebar;
 pub mod welcome;
 
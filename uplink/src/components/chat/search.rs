@@ -0,0 +1,86 @@
+use dioxus::prelude::*;
+use fluent_templates::Loader;
+use ui_kit::{
+    components::{user::User, user_image::UserImage},
+    elements::label::Label,
+};
+
+use crate::{
+    components::chat::RouteInfo,
+    search,
+    state::{Action, State},
+    APP_LANG, LOCALES,
+};
+
+#[derive(PartialEq, Props)]
+pub struct Props {
+    query: String,
+    route_info: RouteInfo,
+}
+
+/// Lists the messages matching `query` across every conversation. Picking one opens its
+/// chat scrolled to the message.
+#[allow(non_snake_case)]
+pub fn SearchResults(cx: Scope<Props>) -> Element {
+    let state: UseSharedState<State> = use_context::<State>(&cx)?;
+    let hits = search::search(&cx.props.query);
+
+    let results_text = LOCALES
+        .lookup(&*APP_LANG.read(), "search.results")
+        .unwrap_or_default();
+    let no_results_text = LOCALES
+        .lookup(&*APP_LANG.read(), "search.no-results")
+        .unwrap_or_default();
+    let you_text = LOCALES
+        .lookup(&*APP_LANG.read(), "search.you")
+        .unwrap_or_default();
+
+    cx.render(rsx!(
+        div {
+            id: "search-results",
+            Label {
+                text: results_text
+            },
+            hits.is_empty().then(|| rsx!(
+                p {
+                    class: "no-results",
+                    "{no_results_text}"
+                }
+            )),
+            hits.into_iter().filter_map(|hit| {
+                let chat = state.read().chats.all.get(&hit.conversation_id).cloned()?;
+                let sender = state.read().get_friend_identity(&hit.sender);
+                let username = if hit.sender == state.read().account.identity.did_key() {
+                    you_text.clone()
+                } else {
+                    state.read().display_name(&sender)
+                };
+                let timestamp = hit.date.timestamp_millis() as u64;
+                let presence = state.read().get_presence(&hit.sender);
+
+                Some(rsx!(
+                    User {
+                        key: "{hit.message_id}",
+                        username: username,
+                        subtext: hit.preview.clone(),
+                        timestamp: timestamp,
+                        user_image: cx.render(rsx!(
+                            UserImage {
+                                platform: presence.indicator_platform(),
+                                status: presence.indicator_status(),
+                                image: sender.graphics().profile_picture(),
+                            }
+                        )),
+                        onpress: move |_| {
+                            state.write().mutate(Action::ChatWith(chat.clone()));
+                            state.write().mutate(Action::JumpTo(chat.clone(), hit.message_id));
+                            if cx.props.route_info.active.to != "/" {
+                                use_router(&cx).replace_route("/", None, None);
+                            }
+                        }
+                    }
+                ))
+            })
+        }
+    ))
+}
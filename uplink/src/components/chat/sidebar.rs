@@ -1,27 +1,28 @@
 use dioxus::prelude::*;
 use fluent_templates::Loader;
-use ui_kit::{User as UserInfo, elements::{input::{Input, Options}, label::Label}, icons::Icon, components::{nav::Nav, context_menu::{ContextMenu, ContextItem}, user::User, user_image::UserImage, indicator::{Platform, Status}, user_image_group::UserImageGroup}, layout::sidebar::Sidebar as ReusableSidebar};
+use ui_kit::{User as UserInfo, elements::{button::Button, input::{Input, Options}, label::Label, Appearance}, icons::Icon, components::{nav::Nav, context_menu::{ContextMenu, ContextItem}, user::User, user_image::UserImage, user_image_group::UserImageGroup}, layout::sidebar::Sidebar as ReusableSidebar};
 use warp::{multipass::identity::Identity, raygun::Message};
 
-use crate::{components::{chat::RouteInfo, media::remote_control::RemoteControls}, state::{State, Action, Chat}, LOCALES, APP_LANG};
+use crate::{components::{chat::{RouteInfo, group::NewGroup, search::SearchResults}, media::remote_control::RemoteControls}, state::{State, Action, Chat}, LOCALES, APP_LANG};
 
 #[derive(PartialEq, Props)]
 pub struct Props {
     route_info: RouteInfo,
 }
 
-pub fn build_participants(identities: &Vec<Identity>) -> Vec<UserInfo> {
+pub fn build_participants(state: &State, identities: &Vec<Identity>) -> Vec<UserInfo> {
     // Create a vector of UserInfo objects to store the results
     let mut user_info: Vec<UserInfo> = vec![];
 
     // Iterate over the identities vector
     for identity in identities {
         // For each identity, create a new UserInfo object and set its fields
-        // to the corresponding values from the identity object
+        // to the corresponding values from the identity object and its presence
+        let presence = state.get_presence(&identity.did_key());
         user_info.push(UserInfo {
-            platform: Platform::Mobile,
-            status: Status::Online,
-            username: identity.username(),
+            platform: presence.indicator_platform(),
+            status: presence.indicator_status(),
+            username: state.display_name(identity),
             photo: identity.graphics().profile_picture(),
         })
     }
@@ -30,13 +31,13 @@ pub fn build_participants(identities: &Vec<Identity>) -> Vec<UserInfo> {
     user_info
 }
 
-pub fn build_participants_names(identities: &Vec<Identity>) -> String {
+pub fn build_participants_names(state: &State, identities: &Vec<Identity>) -> String {
     let mut participants_name = String::from("");
 
     // Iterate over the identities vector
     for identity in identities {
-        // Create a string with the username of the current identity and a comma
-        let name = format!("{}, ", identity.username());
+        // Create a string with the display name of the current identity and a comma
+        let name = format!("{}, ", state.display_name(identity));
         // Append the name string to the participants_name string
         participants_name.push_str(&name);
     }
@@ -55,10 +56,16 @@ pub fn Sidebar(cx: Scope<Props>) -> Element {
     let state: UseSharedState<State> = use_context::<State>(&cx)?;
 
     let search_placeholder = String::from("Search...");
+    let query = use_state(&cx, String::new);
+    // While searching, the results take the place of the chats.
+    let searching = !query.trim().is_empty();
+    // So does the friend picker while starting a group chat.
+    let creating_group = use_state(&cx, || false);
+    let listing = !searching && !**creating_group;
 
-    let sidebar_chats = state.read().chats.in_sidebar.clone();
+    let sidebar_chats = if listing { state.read().chats.in_sidebar.clone() } else { vec![] };
 
-    let favorites = state.read().chats.favorites.clone();
+    let favorites = if listing { state.read().chats.favorites.clone() } else { vec![] };
 
 
     let favorites_text = LOCALES
@@ -70,6 +77,10 @@ pub fn Sidebar(cx: Scope<Props>) -> Element {
         .lookup(&*APP_LANG.read(), "uplink.chats")
         .unwrap_or_default(); 
 
+    let new_group_text = LOCALES
+        .lookup(&*APP_LANG.read(), "groups.new")
+        .unwrap_or_default();
+
     let binding = state.read();
     let active_media_chat = binding.get_active_media_chat();
 
@@ -84,7 +95,8 @@ pub fn Sidebar(cx: Scope<Props>) -> Element {
                         options: Options {
                             with_clear_btn: true,
                             ..Options::default()
-                        }
+                        },
+                        onchange: move |value: String| query.set(value),
                     }
                 }
             ))
@@ -112,7 +124,7 @@ pub fn Sidebar(cx: Scope<Props>) -> Element {
                             let favorites_chat = chat.clone();
                             let remove_favorite = chat.clone();
                             let without_me = state.read().get_without_me(chat.participants.clone());
-                            let participants_name = build_participants_names(&without_me);
+                            let participants_name = chat.name.clone().unwrap_or_else(|| build_participants_names(&state.read(), &without_me));
 
                             let remove_favorite_text = LOCALES
                                 .lookup(&*APP_LANG.read(), "favorites.remove")
@@ -146,7 +158,7 @@ pub fn Sidebar(cx: Scope<Props>) -> Element {
                                         }
                                     )),
                                     UserImageGroup {
-                                        participants: build_participants(&chat.participants.clone()),
+                                        participants: build_participants(&state.read(), &chat.participants.clone()),
                                         with_username: participants_name,
                                         onpress: move |_| {
                                             state.write().mutate(Action::ChatWith(chat.clone()));
@@ -161,6 +173,34 @@ pub fn Sidebar(cx: Scope<Props>) -> Element {
                     }
                 }
             )),
+            searching.then(|| rsx!(
+                SearchResults {
+                    query: query.to_string(),
+                    route_info: cx.props.route_info.clone(),
+                }
+            )),
+            listing.then(|| rsx!(
+                div {
+                    class: "new-group-button",
+                    Button {
+                        icon: Icon::UserGroup,
+                        text: new_group_text,
+                        appearance: Appearance::Secondary,
+                        onpress: move |_| creating_group.set(true),
+                    }
+                }
+            )),
+            creating_group.then(|| rsx!(
+                NewGroup {
+                    oncancel: move |_| creating_group.set(false),
+                    oncreate: move |_| {
+                        creating_group.set(false);
+                        if cx.props.route_info.active.to != "/" {
+                            use_router(&cx).replace_route("/", None, None);
+                        }
+                    }
+                }
+            )),
             div {
                 id: "chats",
                 (!sidebar_chats.is_empty()).then(|| rsx!(
@@ -178,17 +218,26 @@ pub fn Sidebar(cx: Scope<Props>) -> Element {
                         None => Identity::default(),
                     };
 
-                    let last_message = chat.messages.last();
+                    let last_message = chat.last_message();
                     let unwrapped_message = match last_message {
                         Some(m) => m,
                         None => &default_message,
                     };
 
-                    let val = unwrapped_message.value();
+                    // A chat with an unsent draft shows it in place of the last message.
+                    let subtext = if chat.draft.trim().is_empty() {
+                        unwrapped_message.value().join("\n")
+                    } else {
+                        let draft_text = LOCALES
+                            .lookup(&*APP_LANG.read(), "messages.draft")
+                            .unwrap_or_default();
+                        format!("{}: {}", draft_text, chat.draft)
+                    };
                     let timestamp = unwrapped_message.date().timestamp_millis() as u64;
 
-                    let badge = if chat.unreads > 0 {
-                        chat.unreads.to_string()
+                    let unreads = chat.unreads(&state.read().account.identity.did_key());
+                    let badge = if unreads > 0 {
+                        unreads.to_string()
                     } else { "".into() };
                     
                     let key = chat.id;
@@ -198,7 +247,12 @@ pub fn Sidebar(cx: Scope<Props>) -> Element {
                     let clear_unreads = chat.clone();
 
                     let participants = without_me.clone();
-                    let participants_name = if participants.len() > 2 { build_participants_names(&participants) } else { parsed_user.username() };
+                    let presence = state.read().get_presence(&parsed_user.did_key());
+                    let participants_name = match &chat.name {
+                        Some(name) => name.clone(),
+                        None if participants.len() > 1 => build_participants_names(&state.read(), &participants),
+                        None => state.read().display_name(&parsed_user),
+                    };
 
                     let clear_unreads_text = LOCALES
                         .lookup(&*APP_LANG.read(), "uplink.clear-unreads")
@@ -209,10 +263,12 @@ pub fn Sidebar(cx: Scope<Props>) -> Element {
                     let hide_chat_text = LOCALES
                         .lookup(&*APP_LANG.read(), "uplink.hide-chat")
                         .unwrap_or_default();
-                    // TODO:
-                    let _block_user_text = LOCALES
+                    let block_user_text = LOCALES
                         .lookup(&*APP_LANG.read(), "friends.block")
                         .unwrap_or_default();
+                    // Only direct chats can be blocked from, groups have no single person to block.
+                    let is_direct = !chat.group && participants.len() == 1;
+                    let block_user = parsed_user.clone();
 
                     rsx!(
                         ContextMenu {
@@ -241,22 +297,32 @@ pub fn Sidebar(cx: Scope<Props>) -> Element {
                                         state.write().mutate(Action::RemoveFromSidebar(chat.clone()));
                                     }
                                 },
+                                is_direct.then(|| rsx!(
+                                    ContextItem {
+                                        danger: true,
+                                        icon: Icon::NoSymbol,
+                                        text: block_user_text,
+                                        onpress: move |_| {
+                                            state.write().mutate(Action::Block(block_user.clone()));
+                                        }
+                                    }
+                                )),
                             )),
                             User {
                                 username: participants_name,
-                                subtext: val.join("\n"),
+                                subtext: subtext,
                                 timestamp: timestamp,
                                 active: active,
                                 user_image: cx.render(rsx!(
-                                    if participants.len() <= 2 {rsx! (
+                                    if participants.len() < 2 {rsx! (
                                         UserImage {
-                                            platform: Platform::Mobile,
-                                            status: Status::Online
+                                            platform: presence.indicator_platform(),
+                                            status: presence.indicator_status(),
                                             image: parsed_user.graphics().profile_picture(),
                                         }
                                     )} else {rsx! (
                                         UserImageGroup {
-                                            participants: build_participants(&participants)
+                                            participants: build_participants(&state.read(), &participants)
                                         }
                                     )}
                                 )),
@@ -0,0 +1,30 @@
+use dioxus::prelude::*;
+use fluent_templates::Loader;
+use ui_kit::{components::toast::Toast, icons::Icon};
+
+use crate::{
+    state::{Action, State},
+    APP_LANG, LOCALES,
+};
+
+/// Shows the last error reported to the user until they dismiss it.
+#[allow(non_snake_case)]
+pub fn ErrorToast(cx: Scope) -> Element {
+    let state: UseSharedState<State> = use_context::<State>(&cx).unwrap();
+
+    let text = match &state.read().ui.error {
+        Some(key) => LOCALES.lookup(&*APP_LANG.read(), key).unwrap_or_default(),
+        None => return None,
+    };
+
+    cx.render(rsx!(
+        Toast {
+            text: text,
+            icon: Icon::ExclamationTriangle,
+            error: true,
+            onclose: move |_| {
+                state.write().mutate(Action::DismissError);
+            }
+        }
+    ))
+}
@@ -1,28 +1,53 @@
+use std::str::FromStr;
+
 use dioxus::prelude::*;
 use fluent_templates::Loader;
 use ui_kit::{
     elements::{
         button::Button,
-        input::{Input, Options, Validation},
+        input::{Input, Options, Validation, ValidationError},
         label::Label,
     },
     icons::Icon,
 };
 
-use crate::{LOCALES, APP_LANG};
+use crate::{
+    state::{friend_query::FriendQuery, Action, State},
+    LOCALES, APP_LANG,
+};
+
+// Flags anything that's neither a DID key nor a `Username#0000`.
+fn validate_query(value: &str) -> Option<ValidationError> {
+    if value.trim().is_empty() {
+        return None;
+    }
+    FriendQuery::from_str(value)
+        .err()
+        .map(|e| LOCALES.lookup(&*APP_LANG.read(), e.locale_key()).unwrap_or_default())
+}
+
+// Notes sent along with a request are kept short.
+const NOTE_MAX_LENGTH: i32 = 128;
+
+// Looks the user up, the input already explains what's wrong with an invalid query.
+fn find_friend(state: &UseSharedState<State>, value: &str, note: &str) {
+    if let Ok(query) = FriendQuery::from_str(value) {
+        let note = Some(note.trim().to_string()).filter(|note| !note.is_empty());
+        state.write().mutate(Action::FindFriend(query, note));
+    }
+}
 
 #[allow(non_snake_case)]
 pub fn AddFriend(cx: Scope) -> Element {
+    let state: UseSharedState<State> = use_context::<State>(&cx).unwrap();
+    let query = use_state(&cx, String::new);
+    let note = use_state(&cx, String::new);
+
     // Set up validation options for the input field
     let validation_options = Validation {
-        // The input should have a maximum length of 32
-        max_length: Some(32),
-        // The input should have a minimum length of 4
-        min_length: Some(4),
-        // The input should only contain alphanumeric characters
-        alpha_numeric_only: true,
-        // The input should not contain any whitespace
-        no_whitespace: true,
+        // The input should hold a DID key or a username followed by its short ID
+        custom: Some(validate_query),
+        ..Validation::default()
     };
 
     // Set up options for the input field
@@ -49,6 +74,19 @@ pub fn AddFriend(cx: Scope) -> Element {
         .lookup(&*APP_LANG.read(), "friends.placeholder")
         .unwrap_or_default();
 
+    let note_placeholder = LOCALES
+        .lookup(&*APP_LANG.read(), "friends.note-placeholder")
+        .unwrap_or_default();
+
+    let note_options = Options {
+        with_validation: Some(Validation {
+            max_length: Some(NOTE_MAX_LENGTH),
+            ..Validation::default()
+        }),
+        replace_spaces_underscore: false,
+        ..Options::default()
+    };
+
     cx.render(rsx!(
         div {
             class: "add-friend",
@@ -60,11 +98,24 @@ pub fn AddFriend(cx: Scope) -> Element {
                 Input {
                     placeholder: placeholder,
                     icon: Icon::MagnifyingGlass,
-                    options: input_options
+                    options: input_options,
+                    onchange: move |value: String| query.set(value),
+                    onreturn: move |value: String| find_friend(&state, &value, note.get()),
                 },
                 Button {
                     icon: Icon::Plus,
                     text: add,
+                    onpress: move |_| find_friend(&state, query.get(), note.get()),
+                }
+            },
+            div {
+                class: "note",
+                Input {
+                    placeholder: note_placeholder,
+                    icon: Icon::ChatBubbleBottomCenterText,
+                    options: note_options,
+                    onchange: move |value: String| note.set(value),
+                    onreturn: move |_| find_friend(&state, query.get(), note.get()),
                 }
             }
         }
@@ -1,34 +1,74 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
 use dioxus::prelude::*;
-use fluent_templates::Loader;
+use fluent_templates::{fluent_bundle::FluentValue, Loader};
 use ui_kit::{
     components::{
         context_menu::ContextItem,
         context_menu::ContextMenu,
-        indicator::{Platform, Status},
         user_image::UserImage,
     },
     elements::{
         button::Button,
+        input::{Input, Options},
         label::Label,
         tooltip::{ArrowPosition, Tooltip},
         Appearance,
     },
     icons::Icon,
 };
+use warp::{crypto::DID, multipass::identity::Identity};
 
 use crate::{
-    state::{Action, State},
+    state::{name_key, Action, FriendRequest, State},
     LOCALES, APP_LANG,
 };
 
+/// Keeps the friends whose nickname or username contains `filter`, ignoring case and accents.
+fn filter_friends(state: &State, friends: HashMap<DID, Identity>, filter: &str) -> HashMap<DID, Identity> {
+    let filter = name_key(filter.trim());
+    if filter.is_empty() {
+        return friends;
+    }
+    friends
+        .into_iter()
+        .filter(|(_, friend)| {
+            name_key(&state.display_name(friend)).contains(&filter)
+                || name_key(&friend.username()).contains(&filter)
+        })
+        .collect()
+}
+
+/// Describes how long ago a request was made, e.g. "Requested 4 days ago."
+fn format_requested(date: DateTime<Utc>) -> String {
+    let elapsed = Utc::now().signed_duration_since(date);
+    let (key, count) = if elapsed.num_days() > 0 {
+        ("friends.requested-days", elapsed.num_days())
+    } else if elapsed.num_hours() > 0 {
+        ("friends.requested-hours", elapsed.num_hours())
+    } else if elapsed.num_minutes() > 0 {
+        ("friends.requested-minutes", elapsed.num_minutes())
+    } else {
+        ("friends.requested-now", 0)
+    };
+    let args = HashMap::from([("count", FluentValue::from(count))]);
+    LOCALES
+        .lookup_with_args(&*APP_LANG.read(), key, &args)
+        .unwrap_or_default()
+}
+
 #[derive(Props)]
 pub struct Props<'a> {
-    // The username of the friend request sender
+    // The name shown for the friend, their nickname if they have one
     username: String,
     // A suffix to the username, typically a unique identifier
     suffix: String,
     // The user image element to display
     user_image: Element<'a>,
+    // The pending request, shown with when it was made and our note, if it's one we sent
+    #[props(optional)]
+    request: Option<FriendRequest>,
     // An optional event handler for the "onchat" event
     #[props(optional)]
     onchat: Option<EventHandler<'a>>,
@@ -59,6 +99,12 @@ pub fn Friend<'a>(cx: Scope<'a, Props<'a>>) -> Element<'a> {
     let accept_text = LOCALES
         .lookup(&*APP_LANG.read(), "friends.accept")
         .unwrap_or_default();
+    let note_label = LOCALES
+        .lookup(&*APP_LANG.read(), "friends.note-label")
+        .unwrap_or_default();
+
+    let requested_text = cx.props.request.as_ref().map(|request| format_requested(request.date));
+    let note = cx.props.request.as_ref().and_then(|request| request.note.clone());
 
     cx.render(rsx!(
         div {
@@ -72,10 +118,17 @@ pub fn Friend<'a>(cx: Scope<'a, Props<'a>>) -> Element<'a> {
                         "#{cx.props.suffix}"
                     }
                 },
-                Label {
-                    // TODO: this is stubbed for now, wire up to the actual request time
-                    text: "Requested 4 days ago.".into()
-                }
+                requested_text.map(|text| rsx!(
+                    Label {
+                        text: text
+                    }
+                )),
+                note.map(|note| rsx!(
+                    p {
+                        class: "request-note",
+                        "{note_label} {note}"
+                    }
+                ))
             },
             div {
                 class: "request-controls",
@@ -137,10 +190,20 @@ pub fn Friend<'a>(cx: Scope<'a, Props<'a>>) -> Element<'a> {
 #[allow(non_snake_case)]
 pub fn Friends(cx: Scope) -> Element {
     let state: UseSharedState<State> = use_context::<State>(&cx).unwrap();
-    let friends_list = state.read().friends.all.clone();
-    let friends = State::get_friends_by_first_letter(friends_list);
+    let filter = use_state(&cx, String::new);
+    // The friend whose nickname is being edited, if any.
+    let editing = use_state(&cx, || None::<DID>);
+    let friends_list = filter_friends(&state.read(), state.read().friends.all.clone(), filter.get());
+    let friends = state.read().get_friends_by_first_letter(friends_list);
 
     let friends_text = LOCALES.lookup(&*APP_LANG.read(), "friends").unwrap_or_default();
+    let filter_placeholder = LOCALES
+        .lookup(&*APP_LANG.read(), "friends.filter")
+        .unwrap_or_default();
+    let no_matches_text = LOCALES
+        .lookup(&*APP_LANG.read(), "friends.no-matches")
+        .unwrap_or_default();
+    let no_matches = friends.is_empty() && !filter.trim().is_empty();
 
     cx.render(rsx! (
         div {
@@ -148,16 +211,31 @@ pub fn Friends(cx: Scope) -> Element {
             Label {
                 text: friends_text,
             },
+            Input {
+                placeholder: filter_placeholder,
+                icon: Icon::MagnifyingGlass,
+                options: Options {
+                    with_clear_btn: true,
+                    ..Options::default()
+                },
+                onchange: move |value: String| filter.set(value),
+            },
+            no_matches.then(|| rsx!(
+                p {
+                    class: "no-matches",
+                    "{no_matches_text}"
+                }
+            )),
             friends.into_iter().map(|(letter, sorted_friends)| {
-                let group_letter = letter.to_string();
                 rsx!(
                     div {
-                        key: "friend-group-{group_letter}",
+                        key: "friend-group-{letter}",
                         Label {
-                            text: letter.into(),
+                            text: letter.clone(),
                         },
                         sorted_friends.into_iter().map(|friend| {
                             let did = friend.did_key().clone();
+                            let presence = state.read().get_presence(&did);
                             let did_suffix: String = did.to_string().chars().rev().take(6).collect();
                             let chat_with_friend = state.read().get_chat_with_friend(&friend.clone());
                             let chat_with_friend_context = state.read().get_chat_with_friend(&friend.clone());
@@ -166,6 +244,12 @@ pub fn Friends(cx: Scope) -> Element {
                             let remove_friend_2 = remove_friend.clone();
                             let block_friend = friend.clone();
                             let block_friend_clone = friend.clone();
+                            let profile_did = did.clone();
+                            let nickname_did = did.clone();
+                            let edit_did = did.clone();
+                            let is_editing = editing.get().as_ref() == Some(&did);
+                            let display_name = state.read().display_name(&friend);
+                            let nickname = state.read().friends.nicknames.get(&did).cloned().unwrap_or_default();
 
                             let call_text = LOCALES
                                 .lookup(&*APP_LANG.read(), "uplink.call")
@@ -182,8 +266,38 @@ pub fn Friends(cx: Scope) -> Element {
                             let block_test = LOCALES
                                 .lookup(&*APP_LANG.read(), "friends.block")
                                 .unwrap_or_default();
+                            let nickname_text = LOCALES
+                                .lookup(&*APP_LANG.read(), "friends.set-nickname")
+                                .unwrap_or_default();
+                            let nickname_placeholder = LOCALES
+                                .lookup(&*APP_LANG.read(), "friends.nickname-placeholder")
+                                .unwrap_or_default();
 
-                            rsx!(
+                            if is_editing { rsx!(
+                                div {
+                                    key: "{did}-friend-nickname",
+                                    class: "nickname-edit",
+                                    UserImage {
+                                        platform: presence.indicator_platform(),
+                                        status: presence.indicator_status(),
+                                        image: friend.graphics().profile_picture(),
+                                    },
+                                    Input {
+                                        placeholder: nickname_placeholder,
+                                        default_text: nickname,
+                                        onreturn: move |value: String| {
+                                            // An empty nickname goes back to the username.
+                                            state.write().mutate(Action::SetNickname(nickname_did.clone(), Some(value)));
+                                            editing.set(None);
+                                        }
+                                    },
+                                    Button {
+                                        icon: Icon::XMark,
+                                        appearance: Appearance::Secondary,
+                                        onpress: move |_| editing.set(None),
+                                    }
+                                }
+                            )} else { rsx!(
                                 ContextMenu {
                                     id: format!("{}-friend-listing", did),
                                     key: "{did}-friend-listing",
@@ -208,6 +322,11 @@ pub fn Friends(cx: Scope) -> Element {
                                                 let _ = &state.write().mutate(Action::Favorite(chat_with_friend_context_clone.clone()));
                                             }
                                         },
+                                        ContextItem {
+                                            icon: Icon::PencilSquare,
+                                            text: nickname_text,
+                                            onpress: move |_| editing.set(Some(edit_did.clone())),
+                                        },
                                         hr{}
                                         ContextItem {
                                             danger: true,
@@ -227,13 +346,16 @@ pub fn Friends(cx: Scope) -> Element {
                                         },
                                     )),
                                     Friend {
-                                        username: friend.username(),
+                                        username: display_name,
                                         suffix: did_suffix,
                                         user_image: cx.render(rsx! (
                                             UserImage {
-                                                platform: Platform::Desktop,
-                                                status: Status::Online,
-                                                image: friend.graphics().profile_picture()
+                                                platform: presence.indicator_platform(),
+                                                status: presence.indicator_status(),
+                                                image: friend.graphics().profile_picture(),
+                                                onpress: move |_| {
+                                                    state.write().mutate(Action::ShowProfile(profile_did.clone()));
+                                                }
                                             }
                                         )),
                                         onchat: move |_| {
@@ -248,7 +370,7 @@ pub fn Friends(cx: Scope) -> Element {
                                         }
                                     }
                                 }
-                            )
+                            )}
                         })
                     }
                 )
@@ -272,8 +394,10 @@ pub fn PendingFriends(cx: Scope) -> Element {
             Label {
                 text: requests_text,
             },
-            friends_list.into_iter().map(|friend| {
+            friends_list.into_iter().map(|request| {
+                let friend = request.identity.clone();
                 let did = friend.did_key().clone();
+                let presence = state.read().get_presence(&did);
                 let did_suffix: String = did.to_string().chars().rev().take(6).collect();
 
                 let deny_text = LOCALES
@@ -299,15 +423,16 @@ pub fn PendingFriends(cx: Scope) -> Element {
                             },
                         )),
                         Friend {
-                            username: friend.username(),
+                            username: state.read().display_name(&friend),
                             suffix: did_suffix,
                             user_image: cx.render(rsx! (
                                 UserImage {
-                                    platform: Platform::Desktop,
-                                    status: Status::Online,
+                                    platform: presence.indicator_platform(),
+                                    status: presence.indicator_status(),
                                     image: friend.graphics().profile_picture()
                                 }
                             )),
+                            request: request,
                             onaccept: move |_| {
                                 let _ = state.write().mutate(Action::AcceptRequest(friend_clone.clone()));
                             },
@@ -337,8 +462,10 @@ pub fn OutgoingRequests(cx: Scope) -> Element {
             Label {
                 text: requests_text,
             },
-            friends_list.into_iter().map(|friend| {
+            friends_list.into_iter().map(|request| {
+                let friend = request.identity.clone();
                 let did = friend.did_key().clone();
+                let presence = state.read().get_presence(&did);
                 let did_suffix: String = did.to_string().chars().rev().take(6).collect();
 
                 let cancel_text = LOCALES
@@ -363,15 +490,16 @@ pub fn OutgoingRequests(cx: Scope) -> Element {
                             },
                         )),
                         Friend {
-                            username: friend.username(),
+                            username: state.read().display_name(&friend),
                             suffix: did_suffix,
                             user_image: cx.render(rsx! (
                                 UserImage {
-                                    platform: Platform::Desktop,
-                                    status: Status::Online,
+                                    platform: presence.indicator_platform(),
+                                    status: presence.indicator_status(),
                                     image: friend.graphics().profile_picture()
                                 }
                             )),
+                            request: request,
                             onremove: move |_| {
                                 let _ = &state.write().mutate(Action::CancelRequest(friend_clone.clone()));
                             }
@@ -400,6 +528,7 @@ pub fn BlockedUsers(cx: Scope) -> Element {
             },
             block_list.into_iter().map(|blocked_user| {
                 let did = blocked_user.did_key().clone();
+                let presence = state.read().get_presence(&did);
                 let did_suffix: String = did.to_string().chars().rev().take(6).collect();
                 let unblock_user = blocked_user.clone();
                 let unblock_user_clone = unblock_user.clone();
@@ -421,12 +550,12 @@ pub fn BlockedUsers(cx: Scope) -> Element {
                             },
                         )),
                         Friend {
-                            username: blocked_user.username(),
+                            username: state.read().display_name(&blocked_user),
                             suffix: did_suffix,
                             user_image: cx.render(rsx! (
                                 UserImage {
-                                    platform: Platform::Desktop,
-                                    status: Status::Online,
+                                    platform: presence.indicator_platform(),
+                                    status: presence.indicator_status(),
                                     image: blocked_user.graphics().profile_picture()
                                 }
                             )),
@@ -0,0 +1,311 @@
+use std::time::Duration;
+
+use arboard::Clipboard;
+use dioxus::prelude::*;
+use fluent_templates::Loader;
+use ui_kit::{
+    components::user_image::UserImage,
+    elements::{button::Button, label::Label, Appearance},
+    icons::Icon,
+};
+use warp::{crypto::DID, multipass::identity::Identity};
+
+use crate::{
+    components::chat::sidebar::build_participants_names,
+    state::{Action, Chat, State},
+    APP_LANG, LOCALES,
+};
+
+// How long the copy button says the DID was copied.
+const COPIED_TIMEOUT: Duration = Duration::from_secs(2);
+
+// The banner comes from the other peer and ends up in a style attribute, so only base64
+// encoded images are let through. Anything else could break out of the `url()`.
+fn banner_style(banner: &str) -> String {
+    let is_data_image = banner
+        .strip_prefix("data:image/")
+        .and_then(|rest| rest.split_once(";base64,"))
+        .map_or(false, |(subtype, data)| {
+            !subtype.is_empty()
+                && subtype
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c))
+                && data
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "+/=".contains(c))
+        });
+    if is_data_image {
+        format!("background-image: url('{}');", banner)
+    } else {
+        String::new()
+    }
+}
+
+// Anyone we know of, friends first, then the people we share a chat with.
+fn find_identity(state: &State, did: &DID) -> Option<Identity> {
+    state.friends.all.get(did).cloned().or_else(|| {
+        state
+            .chats
+            .all
+            .values()
+            .flat_map(|chat| chat.participants.iter())
+            .find(|identity| identity.did_key() == *did)
+            .cloned()
+    })
+}
+
+// Group chats both of us are in, sorted by name.
+fn shared_groups(state: &State, did: &DID) -> Vec<(Chat, String)> {
+    let mut groups: Vec<(Chat, String)> = state
+        .chats
+        .all
+        .values()
+        .filter(|chat| chat.group && chat.participants.iter().any(|p| p.did_key() == *did))
+        .map(|chat| {
+            let name = chat.name.clone().unwrap_or_else(|| {
+                build_participants_names(state, &state.get_without_me(chat.participants.clone()))
+            });
+            (chat.clone(), name)
+        })
+        .collect();
+    groups.sort_by_key(|(_, name)| name.to_lowercase());
+    groups
+}
+
+/// Shows the profile of the user picked in the friends list, a chat topbar or next to their
+/// messages, along with what can be done with them.
+#[allow(non_snake_case)]
+pub fn FriendProfile(cx: Scope) -> Element {
+    let state: UseSharedState<State> = use_context::<State>(&cx)?;
+    // Bumped on every copy, so the button only goes back once the last copy timed out.
+    let copies = use_state(&cx, || 0u32);
+    let copy_count = **copies;
+    let copied = copies.clone();
+    use_future(&cx, (&copy_count,), |(count,)| async move {
+        if count == 0 {
+            return;
+        }
+        tokio::time::sleep(COPIED_TIMEOUT).await;
+        if *copied.current() == count {
+            copied.set(0);
+        }
+    });
+
+    let did = state.read().ui.profile.clone()?;
+    let identity = find_identity(&state.read(), &did)?;
+    let is_friend = state.read().has_friend_with_did(&did);
+    let presence = state.read().get_presence(&did);
+    let groups = shared_groups(&state.read(), &did);
+    // There's no way to start a chat from here yet, so chatting, calling and favoriting are
+    // only offered once one exists.
+    let direct_chat = state.read().direct_chat_with(&identity);
+    let is_favorite = direct_chat
+        .as_ref()
+        .map_or(false, |chat| state.read().is_favorite(chat));
+
+    let username = identity.username();
+    let display_name = state.read().display_name(&identity);
+    let has_nickname = display_name != username;
+    let short_id = format!("{:04}", identity.short_id());
+    let status_message = identity.status_message().unwrap_or_default();
+    let banner_style = banner_style(&identity.graphics().profile_banner());
+    let did_text = did.to_string();
+    let copy_did = did_text.clone();
+
+    let lookup = |key: &str| LOCALES.lookup(&*APP_LANG.read(), key).unwrap_or_default();
+    let did_label = lookup("profile.did");
+    let copy_text = if copy_count > 0 { lookup("profile.copied") } else { lookup("profile.copy") };
+    let groups_text = lookup("profile.shared-groups");
+    let no_groups_text = lookup("profile.no-shared-groups");
+    let chat_text = lookup("uplink.chat");
+    let call_text = lookup("uplink.call");
+    let favorite_text = if is_favorite { lookup("favorites.remove") } else { lookup("favorites.add") };
+    let block_text = lookup("friends.block");
+    let remove_text = lookup("friends.remove");
+
+    let block_user = identity.clone();
+    let remove_friend = identity.clone();
+
+    cx.render(rsx!(
+        div {
+            id: "friend-profile",
+            onclick: move |_| state.write().mutate(Action::HideProfile),
+            div {
+                class: "profile-card",
+                onclick: move |evt| evt.cancel_bubble(),
+                div {
+                    class: "profile-banner",
+                    style: "{banner_style}",
+                    Button {
+                        icon: Icon::XMark,
+                        small: true,
+                        appearance: Appearance::Secondary,
+                        onpress: move |_| state.write().mutate(Action::HideProfile),
+                    }
+                },
+                div {
+                    class: "profile-header",
+                    UserImage {
+                        platform: presence.indicator_platform(),
+                        status: presence.indicator_status(),
+                        image: identity.graphics().profile_picture(),
+                    },
+                    p {
+                        class: "username",
+                        "{display_name}",
+                        span {
+                            "#{short_id}"
+                        }
+                    },
+                    // Nicknames are ours alone, so the name they picked is shown as well.
+                    has_nickname.then(|| rsx!(
+                        p {
+                            class: "real-name",
+                            "{username}"
+                        }
+                    )),
+                    (!status_message.is_empty()).then(|| rsx!(
+                        p {
+                            class: "status-message",
+                            "{status_message}"
+                        }
+                    ))
+                },
+                div {
+                    class: "profile-section",
+                    Label {
+                        text: did_label
+                    },
+                    div {
+                        class: "profile-did",
+                        code {
+                            "{did_text}"
+                        },
+                        Button {
+                            icon: Icon::ClipboardDocument,
+                            small: true,
+                            appearance: Appearance::Secondary,
+                            text: copy_text,
+                            onpress: move |_| {
+                                let copied = Clipboard::new().and_then(|mut clipboard| clipboard.set_text(copy_did.clone()));
+                                if copied.is_ok() {
+                                    copies.set(copy_count + 1);
+                                }
+                            },
+                        }
+                    },
+                },
+                div {
+                    class: "profile-section",
+                    Label {
+                        text: groups_text
+                    },
+                    groups.is_empty().then(|| rsx!(
+                        p {
+                            class: "profile-empty",
+                            "{no_groups_text}"
+                        }
+                    )),
+                    groups.into_iter().map(|(chat, name)| {
+                        let key = chat.id;
+                        rsx!(
+                            div {
+                                key: "{key}",
+                                class: "shared-group",
+                                onclick: move |_| {
+                                    state.write().mutate(Action::HideProfile);
+                                    state.write().mutate(Action::ChatWith(chat.clone()));
+                                    use_router(&cx).replace_route("/", None, None);
+                                },
+                                "{name}"
+                            }
+                        )
+                    })
+                },
+                is_friend.then(|| rsx!(
+                    div {
+                        class: "profile-actions",
+                        direct_chat.map(|chat| {
+                            let chat_with = chat.clone();
+                            let call_with = chat.clone();
+                            let favorite = chat;
+                            rsx!(
+                                Button {
+                                    icon: Icon::ChatBubbleBottomCenterText,
+                                    text: chat_text,
+                                    onpress: move |_| {
+                                        state.write().mutate(Action::HideProfile);
+                                        state.write().mutate(Action::ChatWith(chat_with.clone()));
+                                        use_router(&cx).replace_route("/", None, None);
+                                    }
+                                },
+                                Button {
+                                    icon: Icon::PhoneArrowUpRight,
+                                    text: call_text,
+                                    appearance: Appearance::Secondary,
+                                    onpress: move |_| {
+                                        state.write().mutate(Action::HideProfile);
+                                        state.write().mutate(Action::ChatWith(call_with.clone()));
+                                        state.write().mutate(Action::ToggleMedia(call_with.clone()));
+                                        use_router(&cx).replace_route("/", None, None);
+                                    }
+                                },
+                                Button {
+                                    icon: Icon::Heart,
+                                    text: favorite_text,
+                                    appearance: Appearance::Secondary,
+                                    onpress: move |_| {
+                                        state.write().mutate(Action::ToggleFavorite(favorite.clone()));
+                                    }
+                                },
+                            )
+                        }),
+                        Button {
+                            icon: Icon::UserMinus,
+                            text: remove_text,
+                            appearance: Appearance::Secondary,
+                            onpress: move |_| {
+                                state.write().mutate(Action::HideProfile);
+                                state.write().mutate(Action::RemoveFriend(remove_friend.clone()));
+                            }
+                        },
+                    }
+                )),
+                div {
+                    class: "profile-actions",
+                    Button {
+                        icon: Icon::NoSymbol,
+                        text: block_text,
+                        appearance: Appearance::Danger,
+                        onpress: move |_| {
+                            state.write().mutate(Action::HideProfile);
+                            state.write().mutate(Action::Block(block_user.clone()));
+                        }
+                    }
+                }
+            }
+        }
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_base64_images_make_it_into_the_banner_style() {
+        assert_eq!(
+            banner_style("data:image/png;base64,iVBORw0KGgo="),
+            "background-image: url('data:image/png;base64,iVBORw0KGgo=');"
+        );
+        for banner in [
+            "",
+            "https://example.com/banner.png",
+            "data:text/html;base64,PGgxPg==",
+            "data:image/png;base64,');background:red;('",
+            "data:image/svg+xml,<svg></svg>",
+        ] {
+            assert_eq!(banner_style(banner), "");
+        }
+    }
+}
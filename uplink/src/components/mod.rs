@@ -1,4 +1,6 @@
 pub mod chat;
+pub mod error;
 pub mod friends;
 pub mod media;
-pub mod settings;
\ No newline at end of file
+pub mod settings;
+pub mod undo;
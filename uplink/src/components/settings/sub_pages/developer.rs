@@ -1,12 +1,14 @@
 use dioxus::prelude::*;
 use ui_kit::{elements::{switch::Switch, Appearance, button::Button}, icons::Icon};
 
-use crate::{components::settings::SettingSection, state::State};
+use crate::{components::settings::SettingSection, profiles, state::State};
 
 
 #[allow(non_snake_case)]
 pub fn DeveloperSettings(cx: Scope) -> Element {
     let state = use_context::<State>(&cx).unwrap();
+    let data_dir = profiles::data_dir();
+    let open_cache_description = format!("Open the cache in your default file browser. It lives in {}.", data_dir.display());
 
     cx.render(rsx!(
         div {
@@ -32,19 +34,13 @@ pub fn DeveloperSettings(cx: Scope) -> Element {
             },
             SettingSection {
                 section_label: "Open Cache".into(),
-                section_description: "Open the cache in your default file browser.".into(),
+                section_description: open_cache_description,
                 Button {
                     text: "Open Folder".into(),
                     appearance: Appearance::Secondary,
                     icon: Icon::FolderOpen,
-                    onpress: |_| {
-                        let cache_path = dirs::home_dir()
-                            .unwrap_or_default()
-                            .join(".uplink/")
-                            .into_os_string()
-                            .into_string()
-                            .unwrap_or_default();
-                        let _ = opener::open(&cache_path);
+                    onpress: move |_| {
+                        let _ = opener::open(&data_dir);
                     }
                 }
             },
@@ -1,8 +1,26 @@
+use std::collections::HashMap;
+
 use dioxus::prelude::*;
-use fluent_templates::Loader;
-use ui_kit::{elements::{switch::Switch, select::Select }};
+use fluent_templates::{fluent_bundle::FluentValue, Loader};
+use ui_kit::{elements::{switch::Switch, select::Select, button::Button, Appearance}, icons::Icon};
+
+use crate::{components::settings::SettingSection, utils::language::{change_language, get_available_languages, APP_LANG}, state::{State, Action}, profiles, LOCALES};
+
+// Periods, in days, outgoing friend requests can be left pending for.
+const REQUEST_EXPIRY_CHOICES: [i64; 4] = [7, 14, 30, 90];
+
+// Quiet periods, in minutes, after which a new message group is started.
+const GROUP_GAP_CHOICES: [i64; 4] = [5, 10, 30, 60];
 
-use crate::{components::settings::SettingSection, utils::language::{change_language, get_available_languages, APP_LANG}, state::{State, Action}, LOCALES};
+fn format_days(days: i64) -> String {
+    let args = HashMap::from([("count", FluentValue::from(days))]);
+    LOCALES.lookup_with_args(&*APP_LANG.read(), "settings.general-request-expiry-days", &args).unwrap_or_default()
+}
+
+fn format_minutes(minutes: i64) -> String {
+    let args = HashMap::from([("count", FluentValue::from(minutes))]);
+    LOCALES.lookup_with_args(&*APP_LANG.read(), "settings.general-group-gap-minutes", &args).unwrap_or_default()
+}
 
 #[allow(non_snake_case)]
 pub fn GeneralSettings(cx: Scope) -> Element {    
@@ -17,6 +35,34 @@ pub fn GeneralSettings(cx: Scope) -> Element {
     .lookup(app_lang, "settings.general-change-language")
     .unwrap_or_default().clone();
 
+    let active_profile = profiles::active().unwrap_or_default();
+    let switch_profile_text = LOCALES
+    .lookup(app_lang, "profiles.switch")
+    .unwrap_or_default();
+
+    let markdown_text = LOCALES
+    .lookup(app_lang, "settings.general-markdown")
+    .unwrap_or_default();
+    let markdown_description_text = LOCALES
+    .lookup(app_lang, "settings.general-markdown-description")
+    .unwrap_or_default();
+
+    let request_expiry_text = LOCALES
+    .lookup(app_lang, "settings.general-request-expiry")
+    .unwrap_or_default();
+    let request_expiry_description_text = LOCALES
+    .lookup(app_lang, "settings.general-request-expiry-description")
+    .unwrap_or_default();
+    let request_expiry_value = format_days(state.read().settings.request_expiry().num_days());
+
+    let group_gap_text = LOCALES
+    .lookup(app_lang, "settings.general-group-gap")
+    .unwrap_or_default();
+    let group_gap_description_text = LOCALES
+    .lookup(app_lang, "settings.general-group-gap-description")
+    .unwrap_or_default();
+    let group_gap_value = format_minutes(state.read().settings.group_gap().num_minutes());
+
     cx.render(rsx!(
         div {
             id: "settings-general",
@@ -39,6 +85,55 @@ pub fn GeneralSettings(cx: Scope) -> Element {
                     }
                 }
             },
+            SettingSection {
+                section_label: markdown_text,
+                section_description: markdown_description_text,
+                Switch {
+                    active: !state.read().settings.plain_text,
+                    onflipped: move |enabled: bool| {
+                        state.write().mutate(Action::SetPlainText(!enabled));
+                    }
+                }
+            },
+            SettingSection {
+                section_label: request_expiry_text,
+                section_description: request_expiry_description_text,
+                Select {
+                    initial_value: request_expiry_value,
+                    options: REQUEST_EXPIRY_CHOICES.iter().map(|days| format_days(*days)).collect(),
+                    onselect: move |value: String| {
+                        if let Some(days) = REQUEST_EXPIRY_CHOICES.iter().find(|days| format_days(**days) == value) {
+                            state.write().mutate(Action::SetRequestExpiry(*days));
+                        }
+                    }
+                }
+            },
+            SettingSection {
+                section_label: group_gap_text,
+                section_description: group_gap_description_text,
+                Select {
+                    initial_value: group_gap_value,
+                    options: GROUP_GAP_CHOICES.iter().map(|minutes| format_minutes(*minutes)).collect(),
+                    onselect: move |value: String| {
+                        if let Some(minutes) = GROUP_GAP_CHOICES.iter().find(|minutes| format_minutes(**minutes) == value) {
+                            state.write().mutate(Action::SetGroupGap(*minutes));
+                        }
+                    }
+                }
+            },
+            SettingSection {
+                section_label: switch_profile_text.clone(),
+                section_description: format!("{}: {}", LOCALES.lookup(app_lang, "profiles.current").unwrap_or_default(), active_profile),
+                Button {
+                    text: switch_profile_text,
+                    appearance: Appearance::Secondary,
+                    icon: Icon::ArrowsRightLeft,
+                    onpress: move |_| {
+                        // Back to the profile picker, which opens the next one.
+                        profiles::close(&mut state.write());
+                    }
+                }
+            },
         }
     ))
 }
@@ -1,13 +1,40 @@
 use dioxus::prelude::*;
 use ui_kit::{
-    elements::{button::Button, Appearance},
+    elements::{button::Button, switch::Switch, Appearance},
     icons::Icon,
 };
 
-use crate::components::settings::SettingSection;
+use crate::{
+    components::settings::SettingSection,
+    previews, search,
+    state::{encryption::StateKey, Action, State},
+};
 
 #[allow(non_snake_case)]
 pub fn PrivacySettings(cx: Scope) -> Element {
+    let state: UseSharedState<State> = use_context::<State>(&cx).unwrap();
+    let is_encrypted = state.read().is_encrypted();
+    let link_previews = state.read().settings.link_previews;
+    // Deriving the key takes a while, so it's done off the UI thread while the button shows
+    // it's busy.
+    let encrypting = use_state(&cx, || false);
+
+    let inner = state.inner();
+    let done = encrypting.clone();
+    use_future(&cx, (encrypting.get(),), move |(encrypting,)| async move {
+        if !encrypting {
+            return;
+        }
+        let passphrase = inner.borrow().read().account.keystore_passphrase.clone();
+        if let Ok(key) = tokio::task::spawn_blocking(move || StateKey::new(&passphrase)).await {
+            inner.borrow().write().set_key(Some(key));
+            search::invalidate();
+            previews::invalidate();
+        }
+        done.set(false);
+        inner.borrow_mut().notify_consumers();
+    });
+
     cx.render(rsx!(
         div {
             id: "settings-privacy",
@@ -20,6 +47,59 @@ pub fn PrivacySettings(cx: Scope) -> Element {
                     icon: Icon::DocumentText,
                 }
             },
+            if is_encrypted {
+                rsx!(
+                    SettingSection {
+                        section_label: "Encrypt Local Data".into(),
+                        section_description: "Your local data is encrypted with the passphrase of this profile.".into(),
+                        Button {
+                            text: "Decrypt".into(),
+                            appearance: Appearance::Secondary,
+                            icon: Icon::LockOpen,
+                            onpress: move |_| {
+                                state.write().set_key(None);
+                                search::invalidate();
+                                previews::invalidate();
+                            },
+                        }
+                    }
+                )
+            } else {
+                rsx!(
+                    SettingSection {
+                        section_label: "Encrypt Local Data".into(),
+                        section_description: "Protect your chats and friends on this device with the passphrase of this profile. If you forget it, your local data can't be recovered.".into(),
+                        if *encrypting.get() {
+                            rsx!(
+                                Button {
+                                    text: "Encrypting...".into(),
+                                    icon: Icon::LockClosed,
+                                    disabled: true,
+                                }
+                            )
+                        } else {
+                            rsx!(
+                                Button {
+                                    text: "Encrypt".into(),
+                                    appearance: Appearance::Secondary,
+                                    icon: Icon::LockClosed,
+                                    onpress: move |_| encrypting.set(true),
+                                }
+                            )
+                        }
+                    }
+                )
+            },
+            SettingSection {
+                section_label: "Link Previews".into(),
+                section_description: "Show a preview card for links in messages. Previews are fetched from the linked sites, which can see your IP address.".into(),
+                Switch {
+                    active: link_previews,
+                    onflipped: move |enabled: bool| {
+                        state.write().mutate(Action::SetLinkPreviews(enabled));
+                    }
+                }
+            }
         }
     ))
 }
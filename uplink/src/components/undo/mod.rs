@@ -0,0 +1,79 @@
+use std::time::Duration;
+
+use dioxus::prelude::*;
+use fluent_templates::Loader;
+use ui_kit::{components::toast::Toast, icons::Icon};
+
+use crate::{
+    state::{Action, State},
+    APP_LANG, LOCALES,
+};
+
+// How long the offer to undo stays up.
+const PROMPT_TIMEOUT: Duration = Duration::from_secs(8);
+
+/// Gets the locale key describing a destructive action.
+fn describe(action: &Action) -> &'static str {
+    match action {
+        Action::RemoveFriend(_) => "journal.remove-friend",
+        Action::Block(_) => "journal.block",
+        Action::RemoveFromSidebar(_) => "journal.hide-chat",
+        Action::UnFavorite(_) => "journal.unfavorite",
+        Action::RemoveMember(_, _) => "journal.remove-member",
+        _ => "journal",
+    }
+}
+
+/// Offers to undo the most recent destructive action.
+#[allow(non_snake_case)]
+pub fn UndoToast(cx: Scope) -> Element {
+    let state: UseSharedState<State> = use_context::<State>(&cx).unwrap();
+
+    // Withdraws the offer after a while, unless another action was prompted in the meantime.
+    let prompted = state
+        .read()
+        .journal
+        .prompt()
+        .map(|entry| entry.action.clone());
+    let inner = state.inner();
+    use_future(&cx, (&prompted,), |(prompted,)| async move {
+        if prompted.is_none() {
+            return;
+        }
+        tokio::time::sleep(PROMPT_TIMEOUT).await;
+        let current = inner
+            .borrow()
+            .read()
+            .journal
+            .prompt()
+            .map(|entry| entry.action.clone());
+        if current == prompted {
+            inner.borrow().write().journal.dismiss_prompt();
+            inner.borrow_mut().notify_consumers();
+        }
+    });
+
+    let key = match state.read().journal.prompt() {
+        Some(entry) => describe(&entry.action),
+        None => return None,
+    };
+
+    let text = LOCALES.lookup(&*APP_LANG.read(), key).unwrap_or_default();
+    let undo_text = LOCALES
+        .lookup(&*APP_LANG.read(), "journal")
+        .unwrap_or_default();
+
+    cx.render(rsx!(
+        Toast {
+            text: text,
+            icon: Icon::ArrowUturnLeft,
+            with_action: undo_text,
+            onaction: move |_| {
+                state.write().undo();
+            },
+            onclose: move |_| {
+                state.write().journal.dismiss_prompt();
+            }
+        }
+    ))
+}
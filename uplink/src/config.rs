@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
 
-use std::fs;
+use std::{fs, path::Path};
+
+use crate::profiles;
 
 /// A struct that represents the configuration of the application.
 #[derive(Debug, Default, Deserialize, Serialize)]
@@ -72,33 +74,16 @@ impl Config {
     }
 
     pub fn load() -> Self {
-        let config_path = dirs::home_dir()
-            .unwrap_or_default()
-            .join(".uplink/Config.json")
-            .into_os_string()
-            .into_string()
-            .unwrap_or_default();
-        // Load the config from the specified path
-        match fs::read_to_string(config_path) {
-            Ok(contents) => {
-                // Parse the config from the file contents using serde
-                match serde_json::from_str(&contents) {
-                    Ok(config) => config,
-                    Err(_) => Self::new(),
-                }
-            }
-            Err(_) => Self::new(),
-        }
+        Self::load_from(&profiles::config_path())
     }
 
     pub fn load_or_default() -> Self {
-        let config_path = dirs::home_dir()
-            .unwrap_or_default()
-            .join(".uplink/Config.json")
-            .into_os_string()
-            .into_string()
-            .unwrap_or_default();
-        // Try to load the config from the specified path
+        Self::load()
+    }
+
+    /// Loads the config stored at `config_path`, falling back to the defaults.
+    pub fn load_from(config_path: &Path) -> Self {
+        // Load the config from the specified path
         match fs::read_to_string(config_path) {
             Ok(contents) => {
                 // Parse the config from the file contents using serde
@@ -113,13 +98,7 @@ impl Config {
 
     fn save(&self) -> Result<(), std::io::Error> {
         let config_json = serde_json::to_string(self)?;
-        let config_path = dirs::home_dir()
-            .unwrap_or_default()
-            .join(".uplink/Config.json")
-            .into_os_string()
-            .into_string()
-            .unwrap_or_default();
-        fs::write(config_path, config_json)?;
+        fs::write(profiles::config_path(), config_json)?;
         Ok(())
     }
 }
@@ -1,4 +1,6 @@
 pub mod chat;
 pub mod files;
 pub mod friends;
+pub mod profiles;
 pub mod settings;
+pub mod unlock;
@@ -0,0 +1,93 @@
+use dioxus::prelude::*;
+use fluent_templates::Loader;
+use ui_kit::{
+    elements::{
+        button::Button,
+        input::{Input, Options},
+        label::Label,
+        Appearance,
+    },
+    icons::Icon,
+};
+
+use crate::{profiles, state::State, APP_LANG, LOCALES};
+
+fn create_profile(state: &UseSharedState<State>, error: &UseState<String>, name: &str) {
+    match profiles::create(name) {
+        Ok(_) => profiles::switch(&mut state.write(), name.trim()),
+        Err(e) => error.set(e),
+    }
+}
+
+#[allow(non_snake_case)]
+pub fn ProfilesLayout(cx: Scope) -> Element {
+    let state: UseSharedState<State> = use_context::<State>(&cx).unwrap();
+    let name = use_state(&cx, String::new);
+    let error = use_state(&cx, String::new);
+    let profile_names = profiles::list();
+    // The profile opened last time is the one most likely picked again.
+    let last_used = profiles::last_used();
+
+    let choose_text = LOCALES
+        .lookup(&*APP_LANG.read(), "profiles")
+        .unwrap_or_default();
+    let create_text = LOCALES
+        .lookup(&*APP_LANG.read(), "profiles.create")
+        .unwrap_or_default();
+    let placeholder = LOCALES
+        .lookup(&*APP_LANG.read(), "profiles.placeholder")
+        .unwrap_or_default();
+
+    cx.render(rsx!(
+        div {
+            id: "profiles-layout",
+            div {
+                class: "profiles-body",
+                Label {
+                    text: choose_text,
+                },
+                profile_names.into_iter().map(|profile| {
+                    let key = profile.clone();
+                    let appearance = if last_used.as_ref() == Some(&profile) {
+                        Appearance::Primary
+                    } else {
+                        Appearance::Secondary
+                    };
+                    rsx!(
+                        Button {
+                            key: "{key}",
+                            text: profile.clone(),
+                            icon: Icon::UserCircle,
+                            appearance: appearance,
+                            onpress: move |_| profiles::switch(&mut state.write(), &profile),
+                        }
+                    )
+                }),
+                div {
+                    class: "profiles-create",
+                    Input {
+                        placeholder: placeholder,
+                        icon: Icon::UserPlus,
+                        options: Options {
+                            with_clear_btn: true,
+                            ..Options::default()
+                        },
+                        onchange: move |value: String| name.set(value),
+                        onreturn: move |value: String| create_profile(&state, error, &value),
+                    },
+                    Button {
+                        text: create_text,
+                        icon: Icon::Plus,
+                        onpress: move |_| create_profile(&state, error, name.get()),
+                    }
+                },
+                (!error.is_empty()).then(|| rsx!(
+                    p {
+                        class: "error",
+                        "{error}"
+                    }
+                ))
+            }
+        }
+    ))
+}
@@ -0,0 +1,123 @@
+use dioxus::prelude::*;
+use fluent_templates::Loader;
+use ui_kit::{
+    elements::{
+        button::Button,
+        input::{Input, Options},
+        label::Label,
+    },
+    icons::Icon,
+};
+use zeroize::Zeroizing;
+
+use crate::{previews, profiles, search, state::State, APP_LANG, LOCALES};
+
+#[allow(non_snake_case)]
+pub fn UnlockLayout(cx: Scope) -> Element {
+    let state: UseSharedState<State> = use_context::<State>(&cx).unwrap();
+    let passphrase = use_state(&cx, || Zeroizing::new(String::new()));
+    let error = use_state(&cx, || false);
+    // Deriving the key takes a while, so it's done off the UI thread while the button shows
+    // it's busy.
+    let unlocking = use_state(&cx, || false);
+
+    let inner = state.inner();
+    let entered = passphrase.get().clone();
+    let (done, failed) = (unlocking.clone(), error.clone());
+    use_future(&cx, (unlocking.get(),), move |(unlocking,)| async move {
+        if !unlocking {
+            return;
+        }
+        let to_decrypt = entered.clone();
+        let decrypted =
+            tokio::task::spawn_blocking(move || State::decrypt_saved(&to_decrypt)).await;
+        // A wrong passphrase leaves the state locked.
+        let unlocked = match decrypted {
+            Ok(Ok(decrypted)) => inner.borrow().write().unlock(entered, decrypted).is_ok(),
+            _ => false,
+        };
+        if unlocked {
+            let inner = inner.borrow();
+            search::open(&inner.read());
+            previews::open(&inner.read());
+            profiles::start_messaging(&inner.read());
+        }
+        failed.set(!unlocked);
+        done.set(false);
+        inner.borrow_mut().notify_consumers();
+    });
+
+    let unlock_text = LOCALES
+        .lookup(&*APP_LANG.read(), "unlock")
+        .unwrap_or_default();
+    let busy_text = LOCALES
+        .lookup(&*APP_LANG.read(), "unlock.busy")
+        .unwrap_or_default();
+    let description = LOCALES
+        .lookup(&*APP_LANG.read(), "unlock.description")
+        .unwrap_or_default();
+    let placeholder = LOCALES
+        .lookup(&*APP_LANG.read(), "unlock.placeholder")
+        .unwrap_or_default();
+    let error_text = LOCALES
+        .lookup(&*APP_LANG.read(), "unlock.error")
+        .unwrap_or_default();
+
+    // The keystore can't be protected by an empty passphrase.
+    let start = move |value: &str| {
+        if *unlocking.get() {
+            return;
+        }
+        if value.is_empty() {
+            error.set(true);
+            return;
+        }
+        passphrase.set(Zeroizing::new(value.into()));
+        unlocking.set(true);
+    };
+
+    cx.render(rsx!(
+        div {
+            id: "unlock-layout",
+            div {
+                class: "unlock-body",
+                Label {
+                    text: description,
+                },
+                Input {
+                    placeholder: placeholder,
+                    icon: Icon::LockClosed,
+                    options: Options {
+                        is_password: true,
+                        ..Options::default()
+                    },
+                    onchange: move |value: String| passphrase.set(Zeroizing::new(value)),
+                    onreturn: move |value: String| start(&value),
+                },
+                error.then(|| rsx!(
+                    p {
+                        class: "error",
+                        "{error_text}"
+                    }
+                )),
+                if *unlocking.get() {
+                    rsx!(
+                        Button {
+                            text: busy_text,
+                            icon: Icon::LockClosed,
+                            disabled: true,
+                        }
+                    )
+                } else {
+                    rsx!(
+                        Button {
+                            text: unlock_text,
+                            icon: Icon::LockOpen,
+                            onpress: move |_| start(passphrase.get()),
+                        }
+                    )
+                }
+            }
+        }
+    ))
+}
@@ -1,12 +1,18 @@
 use std::fs;
+use std::time::Duration;
 
 use dioxus::desktop::tao;
+use dioxus::desktop::tao::accelerator::{Accelerator, SysMods};
 use dioxus::desktop::tao::dpi::LogicalSize;
+use dioxus::desktop::tao::event::Event;
+use dioxus::desktop::tao::keyboard::KeyCode;
 use dioxus::desktop::tao::platform::macos::WindowBuilderExtMacOS;
 use dioxus::prelude::*;
 
-use state::{State};
-use tao::menu::{MenuBar as Menu, MenuItem};
+use state::journal::{JournalCmd, JOURNAL_CH};
+use state::persistence::{self, PersistCmd, PERSIST_CH};
+use state::{Action, State};
+use tao::menu::{MenuBar as Menu, MenuItem, MenuItemAttributes};
 use tao::window::WindowBuilder;
 use ui_kit::icons::IconElement;
 use ui_kit::{components::nav::Route as UIRoute, icons::Icon};
@@ -15,9 +21,15 @@ use ui_kit::STYLE as UIKIT_STYLES;
 use utils::language::APP_LANG;
 
 use crate::components::media::popout_player::PopoutPlayer;
+use crate::components::error::ErrorToast;
+use crate::components::friends::profile::FriendProfile;
+use crate::components::undo::UndoToast;
+use crate::warp_runner::WARP_EVENT_CH;
 use crate::layouts::files::FilesLayout;
 use crate::layouts::friends::FriendsLayout;
+use crate::layouts::profiles::ProfilesLayout;
 use crate::layouts::settings::settings::SettingsLayout;
+use crate::layouts::unlock::UnlockLayout;
 use crate::{components::chat::RouteInfo, layouts::chat::ChatLayout};
 
 pub const APP_STYLE: &str = include_str!("./compiled_styles.css");
@@ -25,9 +37,13 @@ pub const APP_STYLE: &str = include_str!("./compiled_styles.css");
 pub mod components;
 pub mod config;
 pub mod layouts;
+pub mod previews;
+pub mod profiles;
+pub mod search;
 pub mod state;
 pub mod testing;
 pub mod utils;
+pub mod warp_runner;
 
 use fluent_templates::{static_loader, Loader};
 
@@ -43,9 +59,7 @@ static_loader! {
 
 fn main() {
     // Initalized the cache dir if needed
-    let cache_path = dirs::home_dir()
-        .unwrap_or_default()
-        .join(".uplink/")
+    let cache_path = profiles::root_dir()
         .into_os_string()
         .into_string()
         .unwrap_or_default();
@@ -69,6 +83,19 @@ fn main() {
     edit_menu.add_native_item(MenuItem::Copy);
     edit_menu.add_native_item(MenuItem::Paste);
     edit_menu.add_native_item(MenuItem::SelectAll);
+    // Undoing actions goes through the state journal. Cmd+Z is left to text fields.
+    edit_menu.add_native_item(MenuItem::Separator);
+    let undo_text = LOCALES.lookup(&APP_LANG.read(), "journal.undo-action").unwrap_or_default();
+    let redo_text = LOCALES.lookup(&APP_LANG.read(), "journal.redo-action").unwrap_or_default();
+    let undo_item = edit_menu.add_item(
+        MenuItemAttributes::new(&undo_text)
+            .with_accelerators(&Accelerator::new(SysMods::AltCmd, KeyCode::KeyZ)),
+    );
+    let redo_item = edit_menu.add_item(
+        MenuItemAttributes::new(&redo_text)
+            .with_accelerators(&Accelerator::new(SysMods::AltCmdShift, KeyCode::KeyZ)),
+    );
+    let (undo_id, redo_id) = (undo_item.id(), redo_item.id());
 
     window_menu.add_native_item(MenuItem::Minimize);
     window_menu.add_native_item(MenuItem::Zoom);
@@ -102,18 +129,133 @@ fn main() {
     }
     
 
-    dioxus::desktop::launch_cfg(app, |c| c.with_window(|_| window.with_menu(main_menu)))
+    dioxus::desktop::launch_cfg(app, |c| {
+        c.with_window(|_| window.with_menu(main_menu))
+            .with_event_handler(move |event, _| match event {
+                Event::MenuEvent { menu_id, .. } => {
+                    if *menu_id == undo_id {
+                        let _ = JOURNAL_CH.tx.send(JournalCmd::Undo);
+                    } else if *menu_id == redo_id {
+                        let _ = JOURNAL_CH.tx.send(JournalCmd::Redo);
+                    }
+                }
+                // Make sure the last changes hit the disk before we go.
+                Event::LoopDestroyed => persistence::flush_blocking(),
+                _ => {}
+            })
+    })
+}
+
+// Hands pending changes to the state, the search index and the link previews over to the
+// background writer.
+fn save(state: &mut State) {
+    // TODO: surface failures to the user once we have a place for them.
+    let _ = state.persist();
+    let _ = search::persist(state.key.as_ref());
+    let _ = previews::persist(state.key.as_ref());
 }
 
 fn app(cx: Scope) -> Element {
-    let state = match State::load() {
-        Ok(s) => s,
-        Err(_) => State::default(),
-    };
-    let _ = use_context_provider(&cx, || state);
+    let _ = use_context_provider(&cx, || {
+        // With several profiles, nothing is loaded until the user picks one.
+        let mut state = match profiles::startup() {
+            Some(name) => profiles::open(&name),
+            None => State::default(),
+        };
+        state.add_hook(warp_runner::action_hook());
+        state.add_hook(search::action_hook());
+        state
+    });
 
     let state: UseSharedState<State> = use_context::<State>(&cx).unwrap();
 
+    // Feed everything the warp runner reports back into state.
+    let inner = state.inner();
+    use_future(&cx, (), |_| async move {
+        let mut rx = WARP_EVENT_CH.rx.lock().await;
+        while let Some(event) = rx.recv().await {
+            let action = warp_runner::to_action(&inner.borrow().read(), event);
+            if let Some(action) = action {
+                inner.borrow().write().mutate(action);
+                inner.borrow_mut().notify_consumers();
+            }
+        }
+    });
+
+    // Typing indicators go away on their own once peers stop refreshing them.
+    let inner = state.inner();
+    use_future(&cx, (), |_| async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(1));
+        loop {
+            interval.tick().await;
+            if inner.borrow().read().has_expired_typists() {
+                inner.borrow().write().expire(Action::ExpireTyping);
+                inner.borrow_mut().notify_consumers();
+            }
+        }
+    });
+
+    // Outgoing friend requests nobody answered are dropped once they're too old.
+    let inner = state.inner();
+    use_future(&cx, (), |_| async move {
+        let mut interval = tokio::time::interval(Duration::from_secs(60));
+        loop {
+            interval.tick().await;
+            if inner.borrow().read().has_expired_requests() {
+                inner.borrow().write().expire(Action::ExpireRequests);
+                inner.borrow_mut().notify_consumers();
+            }
+        }
+    });
+
+    // Periodically persist pending changes, and flush them when asked to.
+    let inner = state.inner();
+    use_future(&cx, (), |_| async move {
+        let mut rx = PERSIST_CH.rx.lock().await;
+        let mut interval = tokio::time::interval(persistence::SAVE_INTERVAL);
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {
+                    save(&mut inner.borrow().write());
+                }
+                cmd = rx.recv() => match cmd {
+                    Some(PersistCmd::Flush(ack)) => {
+                        save(&mut inner.borrow().write());
+                        persistence::flush(ack);
+                    }
+                    None => break,
+                },
+            }
+        }
+    });
+
+    // Undo and Redo from the Edit menu.
+    let inner = state.inner();
+    use_future(&cx, (), |_| async move {
+        let mut rx = JOURNAL_CH.rx.lock().await;
+        while let Some(cmd) = rx.recv().await {
+            match cmd {
+                JournalCmd::Undo => inner.borrow().write().undo(),
+                JournalCmd::Redo => inner.borrow().write().redo(),
+            }
+            inner.borrow_mut().notify_consumers();
+        }
+    });
+
+    if profiles::active().is_none() {
+        return cx.render(rsx!(
+            style { "{UIKIT_STYLES} {APP_STYLE}" },
+            ProfilesLayout {}
+        ));
+    }
+
+    if state.read().is_locked() {
+        return cx.render(rsx!(
+            style { "{UIKIT_STYLES} {APP_STYLE}" },
+            UnlockLayout {}
+        ));
+    }
+
     let user_lang_saved = state.read().settings.language.clone();
     utils::language::change_language(user_lang_saved);
 
@@ -171,7 +313,11 @@ fn app(cx: Scope) -> Element {
             state.read().ui.popout_player.then(|| rsx!(
                 PopoutPlayer {}
             )),
+            UndoToast {},
+            ErrorToast {},
             Router {
+                // Inside the router, since it can take us to a chat.
+                FriendProfile {},
                 Route {
                     to: "/",
                     ChatLayout {
@@ -0,0 +1,349 @@
+use std::{
+    io::{self, Read},
+    net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr, ToSocketAddrs},
+    time::Duration,
+};
+
+use super::{Preview, PreviewFetcher};
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+// Redirects followed before giving up on a page.
+const MAX_REDIRECTS: u32 = 5;
+
+// Pages and images past these sizes are cut off, previews only need the start of a page.
+const MAX_PAGE_BYTES: u64 = 512 * 1024;
+const MAX_IMAGE_BYTES: u64 = 256 * 1024;
+
+// Long descriptions are shortened to this many characters.
+const MAX_DESCRIPTION_LEN: usize = 200;
+
+/// Fetches pages over HTTP and builds previews out of their OpenGraph tags, falling back
+/// to the page title. The preview image is downloaded right away and kept inline.
+#[derive(Default)]
+pub struct HttpFetcher;
+
+impl PreviewFetcher for HttpFetcher {
+    fn fetch(&self, url: &str) -> Option<Preview> {
+        let agent = ureq::AgentBuilder::new()
+            .timeout(TIMEOUT)
+            .redirects(MAX_REDIRECTS)
+            .resolver(PublicResolver)
+            .build();
+        let response = agent.get(url).call().ok()?;
+        if !response.content_type().starts_with("text/html") {
+            return None;
+        }
+
+        let mut page = vec![];
+        response
+            .into_reader()
+            .take(MAX_PAGE_BYTES)
+            .read_to_end(&mut page)
+            .ok()?;
+        let html = String::from_utf8_lossy(&page);
+
+        let meta = Meta::parse(&html);
+        let title = meta.get("og:title").or_else(|| page_title(&html))?;
+        let description = meta
+            .get("og:description")
+            .or_else(|| meta.get("description"))
+            .map(|description| description.chars().take(MAX_DESCRIPTION_LEN).collect());
+        let image = meta
+            .get("og:image")
+            .and_then(|image| resolve(url, &image))
+            .and_then(|image| fetch_image(&agent, &image));
+
+        Some(Preview {
+            title,
+            description,
+            image,
+        })
+    }
+}
+
+/// Resolves hosts like the system does, but only hands out addresses on the public internet.
+/// Links come from other people, so every connection made for a preview, redirects
+/// included, goes through it to keep them from reaching into our machine or network.
+struct PublicResolver;
+
+impl ureq::Resolver for PublicResolver {
+    fn resolve(&self, netloc: &str) -> io::Result<Vec<SocketAddr>> {
+        let host = netloc.rsplit_once(':').map_or(netloc, |(host, _)| host);
+        let host = host.trim_end_matches('.').to_ascii_lowercase();
+        if host == "localhost" || host.ends_with(".localhost") || host.ends_with(".local") {
+            return Err(refused(netloc));
+        }
+
+        let addrs: Vec<SocketAddr> = netloc
+            .to_socket_addrs()?
+            .filter(|addr| is_public(addr.ip()))
+            .collect();
+        if addrs.is_empty() {
+            return Err(refused(netloc));
+        }
+        Ok(addrs)
+    }
+}
+
+fn refused(netloc: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::PermissionDenied,
+        format!("{} isn't a public address", netloc),
+    )
+}
+
+// Whether the address can be reached by anyone on the internet, as opposed to loopback,
+// private, link-local and other special purpose ranges.
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => is_public_v4(ip),
+        IpAddr::V6(ip) => is_public_v6(ip),
+    }
+}
+
+fn is_public_v4(ip: Ipv4Addr) -> bool {
+    let [a, b, c, _] = ip.octets();
+    !(ip.is_private()
+        || ip.is_loopback()
+        || ip.is_link_local()
+        || ip.is_broadcast()
+        || ip.is_documentation()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        // "This network", shared address space (CGNAT), IETF protocol assignments,
+        // benchmarking and reserved.
+        || a == 0
+        || (a == 100 && (64..128).contains(&b))
+        || (a == 192 && b == 0 && c == 0)
+        || (a == 198 && (18..20).contains(&b))
+        || a >= 240)
+}
+
+fn is_public_v6(ip: Ipv6Addr) -> bool {
+    // Addresses wrapping an IPv4 one (mapped or NAT64) are as public as what they wrap.
+    if let Some(ip) = ip.to_ipv4_mapped() {
+        return is_public_v4(ip);
+    }
+    let segments = ip.segments();
+    if segments[..6] == [0x64, 0xff9b, 0, 0, 0, 0] {
+        let [.., high, low] = segments;
+        return is_public_v4(Ipv4Addr::from(((high as u32) << 16) | low as u32));
+    }
+
+    !(ip.is_loopback()
+        || ip.is_unspecified()
+        || ip.is_multicast()
+        // Unique local, link-local, site-local and documentation.
+        || (segments[0] & 0xfe00) == 0xfc00
+        || (segments[0] & 0xffc0) == 0xfe80
+        || (segments[0] & 0xffc0) == 0xfec0
+        || (segments[0] == 0x2001 && segments[1] == 0xdb8)
+        // IPv4-compatible addresses, deprecated and never routed.
+        || segments[..6] == [0; 6])
+}
+
+// Downloads an image into a data URI.
+fn fetch_image(agent: &ureq::Agent, url: &str) -> Option<String> {
+    let response = agent.get(url).call().ok()?;
+    let content_type = response.content_type().to_string();
+    if !content_type.starts_with("image/") {
+        return None;
+    }
+
+    let mut image = vec![];
+    response
+        .into_reader()
+        .take(MAX_IMAGE_BYTES + 1)
+        .read_to_end(&mut image)
+        .ok()?;
+    // A truncated image is of no use.
+    if image.len() as u64 > MAX_IMAGE_BYTES {
+        return None;
+    }
+
+    Some(format!(
+        "data:{};base64,{}",
+        content_type,
+        base64::encode(image)
+    ))
+}
+
+// Turns the url of an image found on the page at `page_url` into an absolute one.
+fn resolve(page_url: &str, url: &str) -> Option<String> {
+    if url.starts_with("https://") || url.starts_with("http://") {
+        return Some(url.into());
+    }
+
+    let scheme_end = page_url.find("://")?;
+    if let Some(rest) = url.strip_prefix("//") {
+        return Some(format!("{}://{}", &page_url[..scheme_end], rest));
+    }
+
+    let origin_end = page_url[scheme_end + 3..]
+        .find('/')
+        .map(|i| scheme_end + 3 + i)
+        .unwrap_or(page_url.len());
+    url.starts_with('/')
+        .then(|| format!("{}{}", &page_url[..origin_end], url))
+}
+
+/// The `<meta>` tags of a page, by their `property` or `name`.
+struct Meta(Vec<(String, String)>);
+
+impl Meta {
+    fn parse(html: &str) -> Self {
+        let lowercase = html.to_ascii_lowercase();
+        let mut tags = vec![];
+        let mut position = 0;
+
+        while let Some(start) = lowercase[position..].find("<meta") {
+            let start = position + start;
+            let end = match lowercase[start..].find('>') {
+                Some(end) => start + end,
+                None => break,
+            };
+            let tag = &html[start..end];
+
+            let key = attribute(tag, "property").or_else(|| attribute(tag, "name"));
+            if let (Some(key), Some(content)) = (key, attribute(tag, "content")) {
+                tags.push((key.to_lowercase(), content));
+            }
+            position = end;
+        }
+
+        Meta(tags)
+    }
+
+    fn get(&self, key: &str) -> Option<String> {
+        self.0
+            .iter()
+            .find(|(k, content)| k == key && !content.trim().is_empty())
+            .map(|(_, content)| content.trim().to_string())
+    }
+}
+
+// Reads a quoted attribute out of a tag.
+fn attribute(tag: &str, name: &str) -> Option<String> {
+    let lowercase = tag.to_ascii_lowercase();
+    let mut position = 0;
+
+    while let Some(i) = lowercase[position..].find(name) {
+        let i = position + i;
+        position = i + name.len();
+        // Skip matches that are only the end of another attribute's name.
+        if i > 0 && !lowercase[..i].ends_with(char::is_whitespace) {
+            continue;
+        }
+
+        let rest = tag[position..].trim_start();
+        let rest = match rest.strip_prefix('=') {
+            Some(rest) => rest.trim_start(),
+            None => continue,
+        };
+        let quote = rest.chars().next().filter(|c| *c == '"' || *c == '\'')?;
+        let value = &rest[1..];
+        let end = value.find(quote)?;
+        return Some(decode_entities(&value[..end]));
+    }
+    None
+}
+
+fn page_title(html: &str) -> Option<String> {
+    let lowercase = html.to_ascii_lowercase();
+    let start = lowercase.find("<title")?;
+    let start = start + lowercase[start..].find('>')? + 1;
+    let end = start + lowercase[start..].find("</title")?;
+    let title = decode_entities(html[start..end].trim());
+    (!title.is_empty()).then_some(title)
+}
+
+fn decode_entities(text: &str) -> String {
+    text.replace("&quot;", "\"")
+        .replace("&#39;", "'")
+        .replace("&#x27;", "'")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use ureq::Resolver;
+
+    use super::*;
+
+    #[test]
+    fn public_addresses() {
+        for ip in [
+            "1.1.1.1",
+            "93.184.216.34",
+            "2606:4700::1111",
+            "::ffff:8.8.8.8",
+        ] {
+            assert!(is_public(ip.parse().unwrap()), "{ip}");
+        }
+    }
+
+    #[test]
+    fn local_addresses_are_refused() {
+        for ip in [
+            "127.0.0.1",
+            "10.1.2.3",
+            "172.16.0.1",
+            "192.168.1.1",
+            "169.254.169.254",
+            "100.64.0.1",
+            "0.0.0.0",
+            "255.255.255.255",
+            "::1",
+            "::",
+            "fd00::1",
+            "fe80::1",
+            "::ffff:127.0.0.1",
+            "::ffff:192.168.0.1",
+            "64:ff9b::a00:1",
+            "::127.0.0.1",
+        ] {
+            assert!(!is_public(ip.parse().unwrap()), "{ip}");
+        }
+    }
+
+    #[test]
+    fn resolver_refuses_local_hosts() {
+        for netloc in [
+            "localhost:80",
+            "LOCALHOST.:443",
+            "printer.local:80",
+            "api.localhost:8080",
+            "127.0.0.1:80",
+            "192.168.0.10:443",
+            "[::1]:80",
+            "[fe80::1]:443",
+        ] {
+            assert!(PublicResolver.resolve(netloc).is_err(), "{netloc}");
+        }
+    }
+
+    #[test]
+    fn resolver_keeps_public_literals() {
+        assert_eq!(
+            PublicResolver.resolve("1.1.1.1:443").unwrap(),
+            vec!["1.1.1.1:443".parse().unwrap()]
+        );
+    }
+
+    #[test]
+    fn relative_image_urls() {
+        let page = "https://example.com/a/b";
+        assert_eq!(
+            resolve(page, "/i.png").as_deref(),
+            Some("https://example.com/i.png")
+        );
+        assert_eq!(
+            resolve(page, "//cdn.example.com/i.png").as_deref(),
+            Some("https://cdn.example.com/i.png")
+        );
+        assert_eq!(resolve(page, "i.png"), None);
+    }
+}
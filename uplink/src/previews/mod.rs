@@ -0,0 +1,256 @@
+use std::{
+    collections::HashMap,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use chrono::{DateTime, Utc};
+use fluent_templates::once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use warp::sync::RwLock;
+
+use crate::{
+    profiles,
+    state::{
+        encryption::{self, StateKey},
+        persistence, State,
+    },
+};
+
+pub mod http;
+
+const CACHE_FILE: &str = "link_previews.json";
+
+// How many previews are kept, the ones fetched longest ago are dropped first.
+const MAX_CACHED_PREVIEWS: usize = 500;
+
+// Links that couldn't be previewed are tried again after this long.
+const FAILED_RETRY: Duration = Duration::from_secs(10 * 60);
+
+/// What's shown about a link under the message containing it.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Preview {
+    pub title: String,
+    #[serde(default)]
+    pub description: Option<String>,
+    // A data URI, so showing the preview doesn't reach out to the network again.
+    #[serde(default)]
+    pub image: Option<String>,
+}
+
+/// Produces previews for links. Fetching is blocking, it never runs on the UI thread.
+pub trait PreviewFetcher: Send + Sync {
+    fn fetch(&self, url: &str) -> Option<Preview>;
+}
+
+static FETCHER: Lazy<RwLock<Arc<dyn PreviewFetcher>>> =
+    Lazy::new(|| RwLock::new(Arc::new(http::HttpFetcher::default())));
+
+// The previews of the active profile.
+static CACHE: Lazy<RwLock<PreviewCache>> = Lazy::new(|| RwLock::new(PreviewCache::default()));
+
+#[derive(Default, Deserialize, Serialize)]
+struct PreviewCache {
+    #[serde(default)]
+    previews: HashMap<String, CachedPreview>,
+    // Links that couldn't be previewed and when that was, they aren't worth keeping around.
+    #[serde(skip_serializing, skip_deserializing)]
+    failed: HashMap<String, Instant>,
+    #[serde(skip_serializing, skip_deserializing)]
+    dirty: bool,
+}
+
+#[derive(Clone, Deserialize, Serialize)]
+struct CachedPreview {
+    #[serde(flatten)]
+    preview: Preview,
+    #[serde(default = "Utc::now")]
+    fetched: DateTime<Utc>,
+}
+
+impl PreviewCache {
+    // `Some(None)` for links that failed recently, `None` if the link should be fetched.
+    fn get(&self, url: &str) -> Option<Option<Preview>> {
+        if let Some(cached) = self.previews.get(url) {
+            return Some(Some(cached.preview.clone()));
+        }
+        self.failed
+            .get(url)
+            .filter(|failed| failed.elapsed() < FAILED_RETRY)
+            .map(|_| None)
+    }
+
+    fn insert(&mut self, url: String, preview: Option<Preview>) {
+        match preview {
+            Some(preview) => {
+                self.failed.remove(&url);
+                self.previews.insert(
+                    url,
+                    CachedPreview {
+                        preview,
+                        fetched: Utc::now(),
+                    },
+                );
+                self.trim();
+                self.dirty = true;
+            }
+            None => {
+                self.failed
+                    .retain(|_, failed| failed.elapsed() < FAILED_RETRY);
+                self.failed.insert(url, Instant::now());
+            }
+        }
+    }
+
+    fn trim(&mut self) {
+        while self.previews.len() > MAX_CACHED_PREVIEWS {
+            let oldest = self
+                .previews
+                .iter()
+                .min_by_key(|(_, cached)| cached.fetched)
+                .map(|(url, _)| url.clone());
+            match oldest {
+                Some(url) => self.previews.remove(&url),
+                None => break,
+            };
+        }
+    }
+}
+
+/// Replaces the fetcher used for links that aren't cached yet.
+#[cfg(test)]
+pub fn set_fetcher(fetcher: Arc<dyn PreviewFetcher>) {
+    *FETCHER.write() = fetcher;
+}
+
+/// Returns the preview of `url`, fetching it in the background if it isn't cached.
+pub async fn preview(url: String) -> Option<Preview> {
+    if let Some(preview) = CACHE.read().get(&url) {
+        return preview;
+    }
+
+    let fetcher = FETCHER.read().clone();
+    let fetch_url = url.clone();
+    let preview = tokio::task::spawn_blocking(move || fetcher.fetch(&fetch_url))
+        .await
+        .ok()
+        .flatten();
+
+    CACHE.write().insert(url, preview.clone());
+    preview
+}
+
+/// Loads the preview cache of the active profile.
+pub fn open(state: &State) {
+    let contents = std::fs::read_to_string(profiles::data_dir().join(CACHE_FILE)).ok();
+    let contents = match (contents, &state.key) {
+        (Some(contents), Some(key)) if encryption::is_encrypted(&contents) => {
+            key.decrypt_with_key(&contents).ok()
+        }
+        (contents, _) => contents,
+    };
+
+    let mut cache: PreviewCache = contents
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default();
+    cache.trim();
+    *CACHE.write() = cache;
+}
+
+/// Makes sure the cache is written on the next save, e.g. after the passphrase changed.
+pub fn invalidate() {
+    CACHE.write().dirty = true;
+}
+
+/// Drops the cache of a profile that's being closed.
+pub fn close() {
+    *CACHE.write() = PreviewCache::default();
+}
+
+/// Hands the cache to the background writer if it changed. Links say a lot about a
+/// conversation, so like the state it's encrypted when a passphrase is set.
+pub fn persist(key: Option<&StateKey>) -> Result<(), Box<dyn std::error::Error>> {
+    let mut cache = CACHE.write();
+    if !cache.dirty || profiles::active().is_none() {
+        return Ok(());
+    }
+
+    let serialized = serde_json::to_string(&*cache)?;
+    let contents = match key {
+        Some(key) => key.encrypt(&serialized)?,
+        None => serialized,
+    };
+
+    persistence::write(profiles::data_dir().join(CACHE_FILE), contents);
+    cache.dirty = false;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::previews::StubFetcher;
+
+    fn stub_preview(title: &str) -> Preview {
+        Preview {
+            title: title.into(),
+            description: None,
+            image: None,
+        }
+    }
+
+    #[tokio::test]
+    async fn previews_come_from_the_fetcher_and_are_cached() {
+        set_fetcher(Arc::new(StubFetcher::default()));
+
+        let url = "https://example.com/cached".to_string();
+        let preview = preview(url.clone()).await.unwrap();
+        assert_eq!(preview.title, "example.com");
+        assert_eq!(
+            CACHE.read().get(&url).map(|cached| cached.map(|p| p.title)),
+            Some(Some("example.com".to_string()))
+        );
+    }
+
+    #[test]
+    fn failures_are_retried_after_a_while() {
+        let mut cache = PreviewCache::default();
+        let url = "https://example.com/a".to_string();
+
+        cache.insert(url.clone(), None);
+        assert_eq!(cache.get(&url), Some(None));
+        assert!(!cache.dirty);
+
+        cache
+            .failed
+            .insert(url.clone(), Instant::now() - FAILED_RETRY);
+        assert_eq!(cache.get(&url), None);
+
+        cache.insert(url.clone(), Some(stub_preview("a")));
+        assert_eq!(cache.get(&url), Some(Some(stub_preview("a"))));
+        assert!(cache.failed.is_empty());
+    }
+
+    #[test]
+    fn the_oldest_previews_are_dropped() {
+        let mut cache = PreviewCache::default();
+        cache.insert(
+            "https://example.com/first".into(),
+            Some(stub_preview("first")),
+        );
+        cache
+            .previews
+            .get_mut("https://example.com/first")
+            .unwrap()
+            .fetched = Utc::now() - chrono::Duration::days(1);
+
+        for i in 0..MAX_CACHED_PREVIEWS {
+            cache.insert(
+                format!("https://example.com/{}", i),
+                Some(stub_preview("x")),
+            );
+        }
+        assert_eq!(cache.previews.len(), MAX_CACHED_PREVIEWS);
+        assert_eq!(cache.get("https://example.com/first"), None);
+    }
+}
@@ -0,0 +1,296 @@
+use std::{env, fs, path::PathBuf};
+
+use fluent_templates::once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+#[cfg(feature = "mock")]
+use warp::multipass::identity::Identity;
+use warp::sync::RwLock;
+
+#[cfg(feature = "mock")]
+use crate::testing::{multipass::MockMultiPass, raygun::MockRayGun};
+use crate::{
+    config::Config,
+    previews, search,
+    state::{persistence, State},
+    warp_runner::{WarpCmd, WarpRunner, WARP_CMD_CH},
+};
+
+// Name given to the profile holding data from before profiles existed.
+const DEFAULT_PROFILE: &str = "Default";
+
+const INDEX_FILE: &str = "profiles.json";
+
+const CONFIG_FILE: &str = "Config.json";
+
+/// Command line flag overriding the root directory, as `--data-dir <path>` or `--data-dir=<path>`.
+pub const DATA_DIR_FLAG: &str = "--data-dir";
+
+/// Environment variable overriding the root directory.
+pub const DATA_DIR_ENV: &str = "UPLINK_HOME";
+
+struct ActiveProfile {
+    name: String,
+    data_dir: PathBuf,
+}
+
+// The profile whose data is currently loaded, if any.
+static ACTIVE_PROFILE: Lazy<RwLock<Option<ActiveProfile>>> = Lazy::new(|| RwLock::new(None));
+
+// The root directory and whether it was set explicitly, resolved once at startup.
+static ROOT_DIR: Lazy<(PathBuf, bool)> = Lazy::new(resolve_root_dir);
+
+fn resolve_root_dir() -> (PathBuf, bool) {
+    let explicit_dir = data_dir_flag().or_else(|| env::var_os(DATA_DIR_ENV).map(PathBuf::from));
+    match explicit_dir {
+        Some(dir) => (dir, true),
+        None => (dirs::home_dir().unwrap_or_default().join(".uplink"), false),
+    }
+}
+
+fn data_dir_flag() -> Option<PathBuf> {
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        if arg == DATA_DIR_FLAG {
+            return args.next().map(PathBuf::from);
+        }
+        if let Some(dir) = arg.strip_prefix(&format!("{}=", DATA_DIR_FLAG)) {
+            return Some(PathBuf::from(dir));
+        }
+    }
+    None
+}
+
+/// Every local profile, stored at the root of the Uplink directory.
+#[derive(Default, Deserialize, Serialize)]
+struct ProfileIndex {
+    #[serde(default)]
+    profiles: Vec<String>,
+    #[serde(default)]
+    last_used: Option<String>,
+}
+
+impl ProfileIndex {
+    fn load() -> Option<Self> {
+        let contents = fs::read_to_string(root_dir().join(INDEX_FILE)).ok()?;
+        serde_json::from_str(&contents).ok()
+    }
+
+    fn save(&self) -> std::io::Result<()> {
+        let contents = serde_json::to_string(self)?;
+        persistence::write_atomic(&root_dir().join(INDEX_FILE), contents.as_bytes())
+    }
+}
+
+/// The root directory holding every profile. Taken from the `--data-dir` flag, then the
+/// `UPLINK_HOME` environment variable, and defaults to `~/.uplink`.
+pub fn root_dir() -> PathBuf {
+    ROOT_DIR.0.clone()
+}
+
+/// The data directory of the active profile, where `State` and everything cached for it is
+/// kept. Every read and write of profile data goes through here.
+pub fn data_dir() -> PathBuf {
+    match &*ACTIVE_PROFILE.read() {
+        Some(profile) => profile.data_dir.clone(),
+        None => profile_dir(DEFAULT_PROFILE),
+    }
+}
+
+/// Where the active profile's `Config` is stored. Unlike the rest of its data, the config
+/// never moves, since it's what points to a custom data directory.
+pub fn config_path() -> PathBuf {
+    let name = active().unwrap_or_else(|| DEFAULT_PROFILE.into());
+    profile_dir(&name).join(CONFIG_FILE)
+}
+
+// A profile's data lives in its own directory, unless its config points somewhere else.
+// Directories given on launch take precedence over the config.
+fn resolve_data_dir(name: &str) -> PathBuf {
+    let default_dir = profile_dir(name);
+    if ROOT_DIR.1 {
+        return default_dir;
+    }
+
+    let cache_dir = Config::load_from(&default_dir.join(CONFIG_FILE))
+        .developer
+        .cache_dir;
+    if cache_dir.trim().is_empty() {
+        default_dir
+    } else {
+        PathBuf::from(cache_dir.trim())
+    }
+}
+
+fn profile_dir(name: &str) -> PathBuf {
+    root_dir().join("profiles").join(dir_name(name))
+}
+
+// Profile names are free text, keep their directory names portable.
+fn dir_name(name: &str) -> String {
+    name.trim()
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c.to_ascii_lowercase()
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+/// The name of the profile that's currently loaded.
+pub fn active() -> Option<String> {
+    ACTIVE_PROFILE
+        .read()
+        .as_ref()
+        .map(|profile| profile.name.clone())
+}
+
+/// Lists every local profile, in the order they were created.
+pub fn list() -> Vec<String> {
+    ProfileIndex::load().unwrap_or_default().profiles
+}
+
+/// The profile that was opened most recently, if it still exists.
+pub fn last_used() -> Option<String> {
+    let index = ProfileIndex::load()?;
+    index.last_used.filter(|name| index.profiles.contains(name))
+}
+
+/// Creates a new, empty profile.
+pub fn create(name: &str) -> Result<(), String> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err("profile name can't be empty".into());
+    }
+
+    let mut index = ProfileIndex::load().unwrap_or_default();
+    if index
+        .profiles
+        .iter()
+        .any(|profile| dir_name(profile) == dir_name(name))
+    {
+        return Err(format!("a profile named {} already exists", name));
+    }
+
+    fs::create_dir_all(profile_dir(name)).map_err(|e| e.to_string())?;
+    index.profiles.push(name.into());
+    index.save().map_err(|e| e.to_string())
+}
+
+/// Decides which profile to open at startup. With a single profile it's opened right away,
+/// with several the user picks one, which is signaled by returning `None`.
+///
+/// The first run after profiles were introduced moves the existing data into a default profile.
+pub fn startup() -> Option<String> {
+    let index = match ProfileIndex::load() {
+        Some(index) if !index.profiles.is_empty() => index,
+        _ => {
+            let _ = adopt_legacy_data();
+            ProfileIndex::load()?
+        }
+    };
+
+    match index.profiles.as_slice() {
+        [profile] => Some(profile.clone()),
+        _ => None,
+    }
+}
+
+// Creates the default profile out of the files that used to live at the root directory.
+fn adopt_legacy_data() -> Result<(), String> {
+    create(DEFAULT_PROFILE)?;
+    for file in ["state.json", CONFIG_FILE] {
+        let legacy_path = root_dir().join(file);
+        if legacy_path.exists() {
+            fs::rename(&legacy_path, profile_dir(DEFAULT_PROFILE).join(file))
+                .map_err(|e| e.to_string())?;
+        }
+    }
+    Ok(())
+}
+
+/// Makes `name` the active profile and loads its state. The state stays locked until the
+/// profile's passphrase is entered, unlocking it opens messaging, search and the previews.
+pub fn open(name: &str) -> State {
+    *ACTIVE_PROFILE.write() = Some(ActiveProfile {
+        name: name.into(),
+        data_dir: resolve_data_dir(name),
+    });
+    let _ = fs::create_dir_all(data_dir());
+
+    let mut index = ProfileIndex::load().unwrap_or_default();
+    index.last_used = Some(name.into());
+    let _ = index.save();
+
+    // An encrypted state file is kept on disk until `State::unlock` decrypts it, and an
+    // unreadable one has been backed up by `State::load`. Both start from an empty state.
+    let mut state = State::load().unwrap_or_default();
+    // warp's keystore needs the passphrase as well, so every profile waits for it.
+    state.lock();
+    state
+}
+
+/// Starts RayGun and MultiPass for the active profile, on IPFS with its data kept in the
+/// profile's data directory.
+#[cfg(not(feature = "mock"))]
+pub fn start_messaging(state: &State) {
+    WarpRunner::start(data_dir(), state.account.keystore_passphrase.clone());
+}
+
+/// Starts messaging against a local, in-process RayGun, and lookups against a MultiPass that
+/// only knows the identities we already do.
+#[cfg(feature = "mock")]
+pub fn start_messaging(state: &State) {
+    let raygun = MockRayGun::new(state.account.identity.did_key());
+    let mut multipass = MockMultiPass::new(state.account.identity.clone(), known_identities(state));
+    for did in state.friends.all.keys() {
+        multipass.add_friend(did.clone());
+    }
+    WarpRunner::new(Box::new(raygun), Box::new(multipass)).run();
+}
+
+// Everyone the state mentions: friends, blocked users and pending requests.
+#[cfg(feature = "mock")]
+fn known_identities(state: &State) -> Vec<Identity> {
+    let friends = &state.friends;
+    friends
+        .all
+        .values()
+        .chain(friends.blocked.iter())
+        .chain(friends.incoming_requests.iter().map(|r| &r.identity))
+        .chain(friends.outgoing_requests.iter().map(|r| &r.identity))
+        .cloned()
+        .collect()
+}
+
+/// Replaces `state` with the state of another profile. Hooks are carried over.
+pub fn switch(state: &mut State, name: &str) {
+    close(state);
+
+    let mut opened = open(name);
+    opened.hooks = std::mem::take(&mut state.hooks);
+    *state = opened;
+}
+
+/// Saves and tears down the active profile, leaving `state` empty until another one is opened.
+pub fn close(state: &mut State) {
+    if active().is_none() {
+        return;
+    }
+
+    let _ = state.persist();
+    let _ = search::persist(state.key.as_ref());
+    let _ = previews::persist(state.key.as_ref());
+    persistence::wait_for_writer();
+    search::close();
+    previews::close();
+    let _ = WARP_CMD_CH.tx.send(WarpCmd::Shutdown);
+
+    *ACTIVE_PROFILE.write() = None;
+    *state = State {
+        hooks: std::mem::take(&mut state.hooks),
+        ..State::default()
+    };
+}
@@ -0,0 +1,412 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+
+use chrono::{DateTime, Utc};
+use fluent_templates::once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+use warp::{crypto::DID, multipass::identity::Identity, raygun::Message, sync::RwLock};
+
+use crate::{
+    profiles,
+    state::{
+        encryption::{self, StateKey},
+        history::Page,
+        persistence, Action, ActionHook, Chat, State,
+    },
+};
+
+const INDEX_FILE: &str = "search_index.json";
+
+// How many characters of a message are kept to preview hits.
+const PREVIEW_LEN: usize = 120;
+
+/// How many hits a search returns at most.
+pub const MAX_HITS: usize = 50;
+
+// The index of the active profile.
+static SEARCH_INDEX: Lazy<RwLock<SearchIndex>> = Lazy::new(|| RwLock::new(SearchIndex::default()));
+
+#[derive(Clone, Deserialize, Serialize)]
+struct Document {
+    conversation_id: Uuid,
+    sender: DID,
+    date: DateTime<Utc>,
+    preview: String,
+}
+
+/// An inverted index over message bodies.
+#[derive(Default, Deserialize, Serialize)]
+struct SearchIndex {
+    #[serde(default)]
+    documents: HashMap<Uuid, Document>,
+    // Term to the messages containing it, with the number of occurrences. Terms are sorted
+    // so the ones starting with a prefix can be looked up as a range.
+    #[serde(default)]
+    postings: BTreeMap<String, HashMap<Uuid, u32>>,
+    #[serde(skip_serializing, skip_deserializing)]
+    dirty: bool,
+}
+
+/// A message matching a search, best matches first.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SearchHit {
+    pub conversation_id: Uuid,
+    pub message_id: Uuid,
+    pub sender: DID,
+    pub date: DateTime<Utc>,
+    pub preview: String,
+    pub score: f32,
+}
+
+// Splits text into lowercase words. Anything that isn't a letter or a digit separates words.
+fn tokenize(text: &str) -> Vec<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| !word.is_empty())
+        .map(|word| word.to_lowercase())
+        .collect()
+}
+
+impl SearchIndex {
+    fn add(&mut self, message: &Message) {
+        if self.documents.contains_key(&message.id()) {
+            return;
+        }
+
+        let text = message.value().join("\n");
+        for term in tokenize(&text) {
+            *self
+                .postings
+                .entry(term)
+                .or_default()
+                .entry(message.id())
+                .or_default() += 1;
+        }
+
+        self.documents.insert(
+            message.id(),
+            Document {
+                conversation_id: message.conversation_id(),
+                sender: message.sender(),
+                date: message.date(),
+                preview: text.chars().take(PREVIEW_LEN).collect(),
+            },
+        );
+        self.dirty = true;
+    }
+
+    fn remove(&mut self, message_id: Uuid) {
+        if self.documents.remove(&message_id).is_none() {
+            return;
+        }
+
+        self.postings.retain(|_, messages| {
+            messages.remove(&message_id);
+            !messages.is_empty()
+        });
+        self.dirty = true;
+    }
+
+    // Drops every message of the chats `hidden` picks out.
+    fn remove_chats(&mut self, hidden: impl Fn(&Uuid) -> bool) {
+        let removed: HashSet<Uuid> = self
+            .documents
+            .iter()
+            .filter(|(_, document)| hidden(&document.conversation_id))
+            .map(|(id, _)| *id)
+            .collect();
+        if removed.is_empty() {
+            return;
+        }
+
+        self.documents.retain(|id, _| !removed.contains(id));
+        self.postings.retain(|_, messages| {
+            messages.retain(|id, _| !removed.contains(id));
+            !messages.is_empty()
+        });
+        self.dirty = true;
+    }
+
+    // Every query word has to match, the last one as a prefix so results show up while typing.
+    // Hits are ranked by term frequency weighted by how rare each term is.
+    fn search(&self, query: &str) -> Vec<SearchHit> {
+        let words = tokenize(query);
+        let total = self.documents.len() as f32;
+        let mut scores: Option<HashMap<Uuid, f32>> = None;
+
+        for (i, word) in words.iter().enumerate() {
+            let is_last = i == words.len() - 1;
+            let mut word_scores: HashMap<Uuid, f32> = HashMap::new();
+
+            let matching_terms: Vec<&HashMap<Uuid, u32>> = if is_last {
+                self.postings
+                    .range(word.clone()..)
+                    .take_while(|(term, _)| term.starts_with(word.as_str()))
+                    .map(|(_, messages)| messages)
+                    .collect()
+            } else {
+                self.postings.get(word).into_iter().collect()
+            };
+            for messages in matching_terms {
+                let idf = (1.0 + total / messages.len() as f32).ln();
+                for (id, count) in messages {
+                    *word_scores.entry(*id).or_default() += *count as f32 * idf;
+                }
+            }
+
+            scores = Some(match scores {
+                None => word_scores,
+                Some(scores) => scores
+                    .into_iter()
+                    .filter_map(|(id, score)| word_scores.get(&id).map(|s| (id, score + s)))
+                    .collect(),
+            });
+        }
+
+        let mut hits: Vec<SearchHit> = scores
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|(id, score)| {
+                self.documents.get(&id).map(|document| SearchHit {
+                    conversation_id: document.conversation_id,
+                    message_id: id,
+                    sender: document.sender.clone(),
+                    date: document.date,
+                    preview: document.preview.clone(),
+                    score,
+                })
+            })
+            .collect();
+
+        hits.sort_by(|a, b| {
+            b.score
+                .partial_cmp(&a.score)
+                .unwrap_or(std::cmp::Ordering::Equal)
+                .then(b.date.cmp(&a.date))
+        });
+        hits.truncate(MAX_HITS);
+        hits
+    }
+}
+
+/// Searches every indexed message of the active profile.
+pub fn search(query: &str) -> Vec<SearchHit> {
+    SEARCH_INDEX.read().search(query)
+}
+
+/// Loads the index of the active profile, then adds whatever `state` holds that isn't
+/// indexed yet. A missing or unreadable index is rebuilt from scratch the same way. Only
+/// chats shown in the sidebar are searched.
+pub fn open(state: &State) {
+    let contents = std::fs::read_to_string(profiles::data_dir().join(INDEX_FILE)).ok();
+    let contents = match (contents, &state.key) {
+        // The index may still be in plain text if the passphrase was set in the meantime.
+        (Some(contents), Some(key)) if encryption::is_encrypted(&contents) => {
+            key.decrypt_with_key(&contents).ok()
+        }
+        (contents, _) => contents,
+    };
+
+    let mut index: SearchIndex = contents
+        .and_then(|contents| serde_json::from_str(&contents).ok())
+        .unwrap_or_default();
+    index.remove_chats(|id| !state.chats.in_sidebar.contains(id));
+    for chat in state.chats.all.values() {
+        if state.chats.in_sidebar.contains(&chat.id) {
+            index_chat(&mut index, chat);
+        }
+    }
+
+    *SEARCH_INDEX.write() = index;
+}
+
+/// Makes sure the index is written on the next save, e.g. after the passphrase changed.
+pub fn invalidate() {
+    SEARCH_INDEX.write().dirty = true;
+}
+
+/// Drops the index of a profile that's being closed.
+pub fn close() {
+    *SEARCH_INDEX.write() = SearchIndex::default();
+}
+
+fn index_chat(index: &mut SearchIndex, chat: &Chat) {
+    for message in &chat.messages {
+        index.add(message);
+    }
+}
+
+/// Hands the index to the background writer if it changed. Like the state, it's encrypted
+/// when a passphrase is set.
+pub fn persist(key: Option<&StateKey>) -> Result<(), Box<dyn std::error::Error>> {
+    let mut index = SEARCH_INDEX.write();
+    if !index.dirty || profiles::active().is_none() {
+        return Ok(());
+    }
+
+    let serialized = serde_json::to_string(&*index)?;
+    let contents = match key {
+        Some(key) => key.encrypt(&serialized)?,
+        None => serialized,
+    };
+
+    persistence::write(profiles::data_dir().join(INDEX_FILE), contents);
+    index.dirty = false;
+    Ok(())
+}
+
+/// Builds the `ActionHook` that keeps the index up to date as messages come in, change or
+/// go away, and as chats are hidden or shown again.
+pub fn action_hook() -> ActionHook {
+    ActionHook::new(
+        vec![
+            Action::NewMessage(Chat::default(), Message::default()),
+            Action::MessagesLoaded(Chat::default(), Page::Older, vec![]),
+            Action::Edit(Chat::default(), Message::default()),
+            Action::MessageEdited(Chat::default(), Message::default()),
+            Action::Delete(Chat::default(), Message::default()),
+            Action::MessageDeleted(Chat::default(), Uuid::nil()),
+            Action::ChatWith(Chat::default()),
+            Action::AddToSidebar(Chat::default()),
+            Action::RemoveFromSidebar(Chat::default()),
+            Action::RemoveFriend(Identity::default()),
+            Action::Block(Identity::default()),
+        ],
+        |state, action| {
+            let mut index = SEARCH_INDEX.write();
+            match action {
+                // Hooks run before the action is applied, so the state still has the chat as it
+                // was. A message in a hidden chat shows the chat again.
+                Action::NewMessage(chat, message) => {
+                    if !state.chats.in_sidebar.contains(&chat.id) {
+                        if let Some(chat) = state.chats.all.get(&chat.id) {
+                            index_chat(&mut index, chat);
+                        }
+                    }
+                    index.add(message);
+                }
+                Action::ChatWith(chat) | Action::AddToSidebar(chat) => {
+                    if let Some(chat) = state.chats.all.get(&chat.id) {
+                        index_chat(&mut index, chat);
+                    }
+                }
+                Action::RemoveFromSidebar(chat) => index.remove_chats(|id| *id == chat.id),
+                // Their direct chat is hidden along with them.
+                Action::RemoveFriend(identity) | Action::Block(identity) => {
+                    let chat = state.get_chat_with_friend(identity);
+                    if chat.id != Uuid::nil() {
+                        index.remove_chats(|id| *id == chat.id);
+                    }
+                }
+                Action::MessagesLoaded(_, _, messages) => {
+                    for message in messages {
+                        index.add(message);
+                    }
+                }
+                // Edited messages are indexed again under their new text.
+                Action::Edit(_, message) | Action::MessageEdited(_, message) => {
+                    index.remove(message.id());
+                    index.add(message);
+                }
+                Action::Delete(_, message) => index.remove(message.id()),
+                Action::MessageDeleted(_, message_id) => index.remove(*message_id),
+                _ => {}
+            }
+        },
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message(conversation_id: Uuid, text: &str) -> Message {
+        let mut message = Message::default();
+        message.set_conversation_id(conversation_id);
+        message.set_value(vec![text.into()]);
+        message
+    }
+
+    fn index(messages: &[&Message]) -> SearchIndex {
+        let mut index = SearchIndex::default();
+        for message in messages {
+            index.add(message);
+        }
+        index
+    }
+
+    fn ids(hits: Vec<SearchHit>) -> Vec<Uuid> {
+        hits.into_iter().map(|hit| hit.message_id).collect()
+    }
+
+    #[test]
+    fn words_are_lowercased_and_split_on_anything_else() {
+        assert_eq!(
+            tokenize("Hello, World! it's 2pm"),
+            vec!["hello", "world", "it", "s", "2pm"]
+        );
+        assert!(tokenize(" ...  ").is_empty());
+    }
+
+    #[test]
+    fn every_word_has_to_match() {
+        let chat = Uuid::new_v4();
+        let both = message(chat, "lunch tomorrow?");
+        let one = message(chat, "lunch was great");
+        let index = index(&[&both, &one]);
+
+        assert_eq!(ids(index.search("tomorrow lunch")), vec![both.id()]);
+        assert!(index.search("dinner lunch").is_empty());
+    }
+
+    #[test]
+    fn only_the_last_word_matches_as_a_prefix() {
+        let chat = Uuid::new_v4();
+        let meeting = message(chat, "meeting at noon");
+        let meet = message(chat, "meet me there");
+        let index = index(&[&meeting, &meet]);
+
+        let mut hits = ids(index.search("mee"));
+        hits.sort();
+        let mut expected = vec![meeting.id(), meet.id()];
+        expected.sort();
+        assert_eq!(hits, expected);
+
+        assert_eq!(ids(index.search("meet me")), vec![meet.id()]);
+        assert!(index.search("mee noon").is_empty());
+    }
+
+    #[test]
+    fn rare_and_repeated_terms_rank_first() {
+        let chat = Uuid::new_v4();
+        let repeated = message(chat, "deploy deploy deploy");
+        let once = message(chat, "deploy today");
+        let other = message(chat, "rollback the deploy");
+        let filler = message(chat, "today is the day");
+        let index = index(&[&repeated, &once, &other, &filler]);
+
+        let hits = index.search("deploy");
+        let score = |id| hits.iter().find(|hit| hit.message_id == id).unwrap().score;
+        assert!(score(repeated.id()) > score(once.id()));
+        assert_eq!(score(once.id()), score(other.id()));
+
+        // "day" is only in one message, "deploy" is in three.
+        let hits = index.search("d");
+        let score = |id| hits.iter().find(|hit| hit.message_id == id).unwrap().score;
+        assert!(score(filler.id()) > score(once.id()));
+    }
+
+    #[test]
+    fn hidden_chats_are_dropped() {
+        let hidden = Uuid::new_v4();
+        let shown = Uuid::new_v4();
+        let gone = message(hidden, "hello there");
+        let kept = message(shown, "hello again");
+        let mut index = index(&[&gone, &kept]);
+
+        index.remove_chats(|id| *id == hidden);
+
+        assert_eq!(ids(index.search("hello")), vec![kept.id()]);
+        assert!(index.search("there").is_empty());
+        assert!(!index.postings.contains_key("there"));
+    }
+}
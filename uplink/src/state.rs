@@ -1,8 +1,9 @@
 use chrono::{DateTime, Utc};
 use either::Either;
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, HashSet};
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fs;
+use unicode_normalization::{char::is_combining_mark, UnicodeNormalization};
 use uuid::Uuid;
 use warp::{
     constellation::item::Item,
@@ -10,6 +11,27 @@ use warp::{
     multipass::identity::Identity,
     raygun::{Message, Reaction},
 };
+use zeroize::Zeroizing;
+
+use self::encryption::{EncryptionError, StateKey};
+use self::friend_query::FriendQuery;
+use self::history::Page;
+use self::journal::{Entry, Journal};
+use self::presence::{Availability, Device, Presence};
+
+pub mod encryption;
+pub mod friend_query;
+pub mod history;
+pub mod journal;
+pub mod migrations;
+pub mod persistence;
+pub mod presence;
+
+// How many seconds someone is shown as typing after their last typing event.
+pub const TYPING_TIMEOUT_SECS: i64 = 6;
+
+/// Heading of the friends whose usernames don't start with a letter.
+pub const NON_LETTER_BUCKET: &str = "#";
 
 #[derive(Eq, PartialEq)]
 pub struct MessageDivider {
@@ -37,6 +59,10 @@ pub enum Direction {
 pub struct MessageGroup {
     pub sender: DID,
     pub remote: bool,
+    // Set on the first group of each day, rendered as a date separator above the group.
+    pub divider: Option<MessageDivider>,
+    // The group starts with the first unread message, so the "New messages" divider goes above it.
+    pub first_unread: bool,
     pub messages: Vec<GroupedMessage>,
 }
 
@@ -53,17 +79,40 @@ pub struct ActionHook {
     callback: Box<dyn Fn(&State, &Action)>,
 }
 
+impl ActionHook {
+    /// Creates a hook that runs `callback` whenever an action with the same discriminant as
+    /// one of `action_types` is dispatched. The payloads of `action_types` are ignored.
+    pub fn new(action_types: Vec<Action>, callback: impl Fn(&State, &Action) + 'static) -> Self {
+        ActionHook {
+            action_type: Either::Right(action_types),
+            callback: Box::new(callback),
+        }
+    }
+}
+
 /// Alias for the type representing a route.
 pub type To = String;
 
-#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+#[derive(Clone, Default, Deserialize, Serialize)]
 pub struct Account {
     #[serde(default)]
     pub identity: Identity,
+    // Unlocks warp's keystore. It's entered when the profile is unlocked, is never saved
+    // and is wiped from memory once dropped.
+    #[serde(skip)]
+    pub keystore_passphrase: Zeroizing<String>,
     // pub settings: Option<CustomSettings>,
     // pub profile: Option<Profile>,
 }
 
+impl fmt::Debug for Account {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Account")
+            .field("identity", &self.identity)
+            .finish_non_exhaustive()
+    }
+}
+
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
 pub struct Route {
     // String representation of the current active route.
@@ -82,15 +131,110 @@ pub struct Chat {
     // Includes the list of participants within a given chat.
     #[serde(default)]
     pub participants: Vec<Identity>,
-    // Messages should only contain messages we want to render. Do not include the entire message history.
+    // Set for group chats. Members can be added to and removed from those, even when only
+    // two participants are left.
     #[serde(default)]
+    pub group: bool,
+    // Name given to a group chat when it was created, if any.
+    #[serde(default)]
+    pub name: Option<String>,
+    // Messages should only contain messages we want to render. Do not include the entire message history.
+    // Older and newer pages are loaded from RayGun as the user scrolls, see `history`.
+    #[serde(default, serialize_with = "history::serialize_recent")]
     pub messages: Vec<Message>,
-    // Unread count for this chat, should be cleared when we view the chat.
+    // Set once the first message of the conversation has been loaded into `messages`.
+    #[serde(skip)]
+    pub reached_start: bool,
+    // Set when the newest messages were evicted while scrolling back through the history.
+    // Neither flag is saved, a loaded chat holds the newest page, see `history`.
+    #[serde(skip)]
+    pub detached: bool,
+    // Live messages that came in while the window was scrolled back, until it catches up.
+    #[serde(skip)]
+    pub missed: Vec<Message>,
+    // Message to bring into view once it's loaded, e.g. after picking a search result.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub scroll_to: Option<Uuid>,
+    // The last message the user has seen. Unread counts are derived from it.
     #[serde(default)]
-    pub unreads: u32,
+    pub last_read: Option<ReadMarker>,
+    // The first message that was unread when the chat was opened. The "New messages"
+    // divider is rendered right above it.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub first_unread: Option<Uuid>,
+    // Messages that were changed after being sent, marked as edited when rendered.
+    #[serde(default)]
+    pub edited: HashSet<Uuid>,
+    // Messages that were deleted. They're kept in `messages` as empty tombstones so that
+    // replies to them still make sense.
+    #[serde(default)]
+    pub deleted: HashSet<Uuid>,
     // If a value exists, we will render the message we're replying to above the chatbar
     #[serde(default)]
     pub replying_to: Option<Message>,
+    // Text typed into the chatbar that hasn't been sent yet. It's put back into the chatbar
+    // whenever the chat is opened again.
+    #[serde(default)]
+    pub draft: String,
+    // Participants currently typing in the chat, along with when we last heard they were.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub typing: HashMap<DID, DateTime<Utc>>,
+}
+
+/// Points at a message in a chat. The date is kept so the marker can still be compared
+/// against messages once its own message is no longer loaded.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct ReadMarker {
+    pub message_id: Uuid,
+    pub date: DateTime<Utc>,
+}
+
+impl From<&Message> for ReadMarker {
+    fn from(message: &Message) -> Self {
+        ReadMarker {
+            message_id: message.id(),
+            date: message.date(),
+        }
+    }
+}
+
+impl Chat {
+    /// Counts the messages from others that came after the last read one.
+    pub fn unreads(&self, me: &DID) -> u32 {
+        self.unread_messages(me).len() as u32
+    }
+
+    // Messages from others that came after the last read one, or all of them when nothing
+    // was read yet. Messages missed while scrolled back count too.
+    fn unread_messages(&self, me: &DID) -> Vec<&Message> {
+        let known: Vec<&Message> = self.messages.iter().chain(self.missed.iter()).collect();
+        let unread: Vec<&Message> = match &self.last_read {
+            None => known,
+            Some(marker) => match known.iter().position(|m| m.id() == marker.message_id) {
+                Some(i) => known[i + 1..].to_vec(),
+                // The marker isn't loaded, fall back to comparing dates.
+                None => known
+                    .into_iter()
+                    .filter(|m| m.date() > marker.date)
+                    .collect(),
+            },
+        };
+        unread.into_iter().filter(|m| &m.sender() != me).collect()
+    }
+
+    fn mark_read(&mut self, message: &Message) {
+        self.last_read = Some(message.into());
+    }
+
+    /// Whether the message is a tombstone left by a deletion.
+    pub fn is_deleted(&self, message_id: &Uuid) -> bool {
+        self.deleted.contains(message_id)
+    }
+
+    /// Whether the message was changed after being sent.
+    pub fn is_edited(&self, message_id: &Uuid) -> bool {
+        self.edited.contains(message_id)
+    }
 }
 
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
@@ -119,9 +263,37 @@ pub struct Friends {
     pub blocked: Vec<Identity>,
     // Friend requests, incoming and outgoing.
     #[serde(default)]
-    pub incoming_requests: Vec<Identity>,
+    pub incoming_requests: Vec<FriendRequest>,
+    #[serde(default)]
+    pub outgoing_requests: Vec<FriendRequest>,
+    // Last known presence of the people we know, by DID.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub presence: HashMap<DID, Presence>,
+    // Local aliases given to people we know, never shared with them.
     #[serde(default)]
-    pub outgoing_requests: Vec<Identity>,
+    pub nicknames: HashMap<DID, String>,
+}
+
+/// A pending friend request, sent or received.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub struct FriendRequest {
+    pub identity: Identity,
+    // When the request was sent or received.
+    pub date: DateTime<Utc>,
+    // A private memo kept with requests we send. MultiPass has no way to carry it, so it never
+    // leaves this device.
+    #[serde(default)]
+    pub note: Option<String>,
+}
+
+impl FriendRequest {
+    pub fn new(identity: Identity, note: Option<String>) -> Self {
+        FriendRequest {
+            identity,
+            date: Utc::now(),
+            note,
+        }
+    }
 }
 
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
@@ -139,6 +311,41 @@ pub struct Settings {
     // Selected Language
     #[serde(default)]
     pub language: String,
+    // Minutes without messages after which a new message group is started.
+    // Falls back to `DEFAULT_GROUP_GAP_MINUTES` when not set.
+    #[serde(default)]
+    pub group_gap_minutes: Option<i64>,
+    // Shows messages exactly as they were typed instead of rendering them as Markdown.
+    #[serde(default)]
+    pub plain_text: bool,
+    // Fetches a preview card for links in messages. Off unless asked for, since fetching
+    // tells the site someone is looking at the link.
+    #[serde(default)]
+    pub link_previews: bool,
+    // Days after which outgoing friend requests nobody answered are dropped.
+    // Falls back to `DEFAULT_REQUEST_EXPIRY_DAYS` when not set.
+    #[serde(default)]
+    pub request_expiry_days: Option<i64>,
+}
+
+/// How long a conversation can go quiet before messages from the same sender are split
+/// into a new group.
+pub const DEFAULT_GROUP_GAP_MINUTES: i64 = 10;
+
+/// How long an outgoing friend request stays pending before it's dropped.
+pub const DEFAULT_REQUEST_EXPIRY_DAYS: i64 = 30;
+
+impl Settings {
+    pub fn group_gap(&self) -> chrono::Duration {
+        chrono::Duration::minutes(self.group_gap_minutes.unwrap_or(DEFAULT_GROUP_GAP_MINUTES))
+    }
+
+    pub fn request_expiry(&self) -> chrono::Duration {
+        chrono::Duration::days(
+            self.request_expiry_days
+                .unwrap_or(DEFAULT_REQUEST_EXPIRY_DAYS),
+        )
+    }
 }
 
 #[derive(Clone, Debug, Default, Deserialize, Serialize)]
@@ -150,10 +357,17 @@ pub struct UI {
     pub muted: bool,
     #[serde(default)]
     pub silenced: bool,
+    // Locale key of an error shown in a toast until it's dismissed.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub error: Option<String>,
+    // Whose profile is open, if anyone's.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub profile: Option<DID>,
 }
 
 use std::fmt;
 
+use crate::profiles;
 use crate::testing::mock::generate_mock;
 
 #[derive(Default, Deserialize, Serialize)]
@@ -172,6 +386,17 @@ pub struct State {
     pub ui: UI,
     #[serde(skip_serializing, skip_deserializing)]
     pub(crate) hooks: Vec<ActionHook>,
+    #[serde(skip_serializing, skip_deserializing)]
+    pub journal: Journal,
+    // Set when there are changes that haven't been persisted yet.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub(crate) dirty: bool,
+    // Encrypts the state file at rest. Only ever held in memory.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub(crate) key: Option<StateKey>,
+    // Set until the profile's passphrase has been entered.
+    #[serde(skip_serializing, skip_deserializing)]
+    pub(crate) locked: bool,
 }
 
 impl fmt::Debug for State {
@@ -222,6 +447,10 @@ impl State {
         if !self.chats.in_sidebar.contains(&chat.id) {
             self.chats.in_sidebar.push(chat.id.clone());
         }
+        // Opening a chat starts at the bottom, unless a `JumpTo` follows.
+        if let Some(c) = self.chats.all.get_mut(&chat.id) {
+            c.scroll_to = None;
+        }
     }
 
     /// Clears the active chat in the `State` struct.
@@ -327,6 +556,50 @@ impl State {
         *self.chats.all.get_mut(&chat.id).unwrap() = c.clone();
     }
 
+    /// Keeps what's been typed into the chatbar for a given chat on `State` struct.
+    ///
+    /// # Arguments
+    ///
+    /// * `chat` - The chat the text was typed in.
+    /// * `draft` - The text in the chatbar, empty once the message is sent.
+    fn set_draft(&mut self, chat: &Chat, draft: &str) {
+        if let Some(c) = self.chats.all.get_mut(&chat.id) {
+            c.draft = draft.to_string();
+        }
+    }
+
+    /// Shows a participant as typing in a given chat on `State` struct, until they stop or
+    /// `TYPING_TIMEOUT_SECS` passes without hearing from them again.
+    fn start_typing(&mut self, chat: &Chat, did: &DID) {
+        if let Some(c) = self.chats.all.get_mut(&chat.id) {
+            c.typing.insert(did.clone(), Utc::now());
+        }
+    }
+
+    /// Stops showing a participant as typing in a given chat on `State` struct.
+    fn stop_typing(&mut self, chat: &Chat, did: &DID) {
+        if let Some(c) = self.chats.all.get_mut(&chat.id) {
+            c.typing.remove(did);
+        }
+    }
+
+    /// Whether anyone has been shown as typing for longer than `TYPING_TIMEOUT_SECS`.
+    pub fn has_expired_typists(&self) -> bool {
+        let cutoff = Utc::now() - chrono::Duration::seconds(TYPING_TIMEOUT_SECS);
+        self.chats
+            .all
+            .values()
+            .any(|c| c.typing.values().any(|since| *since < cutoff))
+    }
+
+    /// Drops typing indicators nobody refreshed within `TYPING_TIMEOUT_SECS`.
+    fn expire_typing(&mut self) {
+        let cutoff = Utc::now() - chrono::Duration::seconds(TYPING_TIMEOUT_SECS);
+        for c in self.chats.all.values_mut() {
+            c.typing.retain(|_, since| *since >= cutoff);
+        }
+    }
+
     /// Clear unreads  within a given chat on `State` struct.
     ///
     /// # Arguments
@@ -334,10 +607,57 @@ impl State {
     /// * `chat` - The chat to clear unreads on.
     ///
     fn clear_unreads(&mut self, chat: &Chat) {
-        match self.chats.all.get_mut(&chat.id) {
-            Some(chat) => chat.unreads = 0,
+        let chat = match self.chats.all.get_mut(&chat.id) {
+            Some(chat) => chat,
             None => return,
         };
+        if let Some(last) = chat.last_message().cloned() {
+            chat.mark_read(&last);
+        }
+    }
+
+    /// Remembers where the user left off in a chat that's being opened, then marks it read.
+    fn open_chat(&mut self, chat: &Chat) {
+        let me = self.account.identity.did_key();
+        if let Some(c) = self.chats.all.get_mut(&chat.id) {
+            c.first_unread = c.unread_messages(&me).first().map(|m| m.id());
+        }
+        self.clear_unreads(chat);
+    }
+
+    /// Adds a group chat RayGun created for us to the `State` struct and opens it.
+    fn add_group(&mut self, chat: Chat) {
+        self.chats
+            .all
+            .entry(chat.id)
+            .or_insert_with(|| chat.clone());
+        self.set_active_chat(&chat);
+    }
+
+    /// Adds a member to a group chat on `State` struct.
+    fn add_member(&mut self, chat: &Chat, identity: &Identity) {
+        let c = match self.chats.all.get_mut(&chat.id) {
+            Some(c) if c.group => c,
+            _ => return,
+        };
+        if !c
+            .participants
+            .iter()
+            .any(|participant| participant.did_key() == identity.did_key())
+        {
+            c.participants.push(identity.clone());
+        }
+    }
+
+    /// Removes a member from a group chat on `State` struct.
+    fn remove_member(&mut self, chat: &Chat, did: &DID) {
+        let c = match self.chats.all.get_mut(&chat.id) {
+            Some(c) if c.group => c,
+            _ => return,
+        };
+        c.participants
+            .retain(|participant| participant.did_key() != *did);
+        c.typing.remove(did);
     }
 
     /// Remove a chat from the sidebar on `State` struct.
@@ -373,17 +693,33 @@ impl State {
         self.settings.language = string.clone();
     }
 
+    fn set_plain_text(&mut self, plain_text: bool) {
+        self.settings.plain_text = plain_text;
+    }
+
+    fn set_link_previews(&mut self, enabled: bool) {
+        self.settings.link_previews = enabled;
+    }
+
+    fn set_request_expiry(&mut self, days: i64) {
+        self.settings.request_expiry_days = Some(days);
+    }
+
+    fn set_group_gap(&mut self, minutes: i64) {
+        self.settings.group_gap_minutes = Some(minutes);
+    }
+
     fn cancel_request(&mut self, direction: Direction, identity: &Identity) {
         match direction {
             Direction::Outgoing => {
                 self.friends
                     .outgoing_requests
-                    .retain(|friend| friend.did_key() != identity.did_key());
+                    .retain(|request| request.identity.did_key() != identity.did_key());
             }
             Direction::Incoming => {
                 self.friends
                     .incoming_requests
-                    .retain(|friend| friend.did_key() != identity.did_key());
+                    .retain(|request| request.identity.did_key() != identity.did_key());
             }
         }
     }
@@ -393,7 +729,7 @@ impl State {
             Direction::Outgoing => {
                 self.friends
                     .outgoing_requests
-                    .retain(|friend| friend.did_key() != identity.did_key());
+                    .retain(|request| request.identity.did_key() != identity.did_key());
                 self.friends
                     .all
                     .insert(identity.did_key(), identity.clone());
@@ -401,7 +737,7 @@ impl State {
             Direction::Incoming => {
                 self.friends
                     .incoming_requests
-                    .retain(|friend| friend.did_key() != identity.did_key());
+                    .retain(|request| request.identity.did_key() != identity.did_key());
                 self.friends
                     .all
                     .insert(identity.did_key(), identity.clone());
@@ -409,16 +745,197 @@ impl State {
         }
     }
 
-    fn new_incoming_request(&mut self, identity: &Identity) {
-        self.friends.incoming_requests.push(identity.clone());
+    /// Records a message in its chat, skipping messages we already have. The chat is
+    /// surfaced in the sidebar and, unless it's the one being viewed, left unread.
+    fn add_message(&mut self, chat: &Chat, message: Message) {
+        let is_active = self.chats.active == Some(chat.id);
+
+        let c = match self.chats.all.get_mut(&chat.id) {
+            Some(c) => c,
+            None => return,
+        };
+
+        if c.messages.iter().any(|m| m.id() == message.id()) {
+            return;
+        }
+
+        // Messages in the chat being viewed, and our own, are read as they come in.
+        let is_mine = message.sender() == self.account.identity.did_key();
+        if is_active || is_mine {
+            c.mark_read(&message);
+        }
+        // Whoever sent the message is done typing it.
+        c.typing.remove(&message.sender());
+        c.push_message(message);
+
+        if !self.chats.in_sidebar.contains(&chat.id) {
+            self.chats.in_sidebar.push(chat.id);
+        }
+    }
+
+    /// Adds the reaction's users to the message's reactions with that emoji, or takes them
+    /// back if they already reacted with it. Emojis nobody reacts with anymore are dropped.
+    fn toggle_reaction(&mut self, chat: &Chat, message_id: Uuid, reaction: &Reaction) {
+        let message = match self
+            .chats
+            .all
+            .get_mut(&chat.id)
+            .and_then(|c| c.messages.iter_mut().find(|m| m.id() == message_id))
+        {
+            Some(m) => m,
+            None => return,
+        };
+
+        let mut reactions = message.reactions();
+        match reactions.iter_mut().find(|r| r.emoji() == reaction.emoji()) {
+            Some(existing) => {
+                let mut users = existing.users();
+                for user in reaction.users() {
+                    match users.iter().position(|u| *u == user) {
+                        Some(i) => {
+                            users.remove(i);
+                        }
+                        None => users.push(user),
+                    }
+                }
+                existing.set_users(users);
+            }
+            None => reactions.push(reaction.clone()),
+        }
+        reactions.retain(|r| !r.users().is_empty());
+        message.set_reactions(reactions);
+    }
+
+    /// Replaces the text of a message, unless it has been deleted in the meantime.
+    fn edit_message(&mut self, chat: &Chat, message: &Message) {
+        let c = match self.chats.all.get_mut(&chat.id) {
+            Some(c) => c,
+            None => return,
+        };
+        if c.is_deleted(&message.id()) {
+            return;
+        }
+
+        if let Some(m) = c.messages.iter_mut().find(|m| m.id() == message.id()) {
+            m.set_value(message.value());
+            c.edited.insert(message.id());
+        }
+        if let Some(replying_to) = c.replying_to.as_mut().filter(|m| m.id() == message.id()) {
+            replying_to.set_value(message.value());
+        }
+    }
+
+    /// Turns a message into a tombstone. Its text and reactions are dropped, but it keeps
+    /// its place in the chat.
+    fn delete_message(&mut self, chat: &Chat, message_id: Uuid) {
+        let c = match self.chats.all.get_mut(&chat.id) {
+            Some(c) => c,
+            None => return,
+        };
+
+        if let Some(m) = c.messages.iter_mut().find(|m| m.id() == message_id) {
+            m.set_value(vec![]);
+            m.set_reactions(vec![]);
+        }
+        c.deleted.insert(message_id);
+        c.edited.remove(&message_id);
+        // There's nothing left to reply to.
+        if c.replying_to.as_ref().map(|m| m.id()) == Some(message_id) {
+            c.replying_to = None;
+        }
+    }
+
+    fn set_reactions(&mut self, chat: &Chat, message_id: Uuid, reactions: Vec<Reaction>) {
+        if let Some(message) = self
+            .chats
+            .all
+            .get_mut(&chat.id)
+            .and_then(|c| c.messages.iter_mut().find(|m| m.id() == message_id))
+        {
+            message.set_reactions(reactions);
+        }
+    }
+
+    fn load_messages(&mut self, chat: &Chat, page: Page, messages: Vec<Message>) {
+        if let Some(c) = self.chats.all.get_mut(&chat.id) {
+            c.load_page(page, messages);
+        }
+    }
+
+    fn jump_to(&mut self, chat: &Chat, message_id: Uuid) {
+        if let Some(c) = self.chats.all.get_mut(&chat.id) {
+            c.scroll_to = Some(message_id);
+        }
+    }
+
+    fn new_incoming_request(&mut self, request: &FriendRequest) {
+        let did = request.identity.did_key();
+        if !self
+            .friends
+            .incoming_requests
+            .iter()
+            .any(|r| r.identity.did_key() == did)
+        {
+            self.friends.incoming_requests.push(request.clone());
+        }
     }
 
     fn toggle_popout(&mut self) {
         self.ui.popout_player = !self.ui.popout_player;
     }
 
-    fn new_outgoing_request(&mut self, identity: &Identity) {
-        self.friends.outgoing_requests.push(identity.clone());
+    fn new_outgoing_request(&mut self, request: &FriendRequest) {
+        let did = request.identity.did_key();
+        if !self
+            .friends
+            .outgoing_requests
+            .iter()
+            .any(|r| r.identity.did_key() == did)
+        {
+            self.friends.outgoing_requests.push(request.clone());
+        }
+    }
+
+    fn request_failed(&mut self, did: &DID) {
+        self.friends
+            .outgoing_requests
+            .retain(|request| request.identity.did_key() != *did);
+        self.show_error("friends.request-failed");
+    }
+
+    /// Whether any outgoing request has gone unanswered for longer than the expiry period.
+    pub fn has_expired_requests(&self) -> bool {
+        self.expired_requests().next().is_some()
+    }
+
+    /// Outgoing requests that have gone unanswered for longer than the expiry period.
+    pub fn expired_requests(&self) -> impl Iterator<Item = &FriendRequest> {
+        let cutoff = Utc::now() - self.settings.request_expiry();
+        self.friends
+            .outgoing_requests
+            .iter()
+            .filter(move |request| request.date < cutoff)
+    }
+
+    fn expire_requests(&mut self) {
+        let cutoff = Utc::now() - self.settings.request_expiry();
+        self.friends
+            .outgoing_requests
+            .retain(|request| request.date >= cutoff);
+    }
+
+    fn set_presence(&mut self, did: DID, presence: Presence) {
+        self.friends.presence.insert(did, presence);
+    }
+
+    fn set_nickname(&mut self, did: DID, nickname: Option<String>) {
+        match nickname
+            .map(|n| n.trim().to_string())
+            .filter(|n| !n.is_empty())
+        {
+            Some(nickname) => self.friends.nicknames.insert(did, nickname),
+            None => self.friends.nicknames.remove(&did),
+        };
     }
 
     fn block(&mut self, identity: &Identity) {
@@ -427,10 +944,18 @@ impl State {
             self.friends.blocked.push(identity.clone());
         }
 
-        // Remove the identity from the outgoing requests list if they are present
+        // Remove the identity from the requests lists if they are present
         self.friends
             .outgoing_requests
-            .retain(|friend| friend.did_key() != identity.did_key());
+            .retain(|request| request.identity.did_key() != identity.did_key());
+        self.friends
+            .incoming_requests
+            .retain(|request| request.identity.did_key() != identity.did_key());
+
+        // They're no longer shown as typing anywhere
+        for chat in self.chats.all.values_mut() {
+            chat.typing.remove(&identity.did_key());
+        }
 
         // Remove the identity from the friends list if they are present
         self.remove_friend(&identity.did_key());
@@ -445,6 +970,12 @@ impl State {
         }
     }
 
+    fn add_friend(&mut self, identity: &Identity) {
+        self.friends
+            .all
+            .insert(identity.did_key(), identity.clone());
+    }
+
     fn remove_friend(&mut self, did: &DID) {
         // Remove the friend from the all field of the friends struct
         self.friends.all.remove(did);
@@ -467,8 +998,8 @@ impl State {
         }
 
         // If the friend's direct chat is currently the active chat, clear the active chat
-        if self.chats.active.is_some() {
-            if self.get_active_chat().unwrap_or_default().id == direct_chat.unwrap().id {
+        if let Some(chat) = direct_chat {
+            if self.chats.active == Some(chat.id) {
                 self.clear_active_chat();
             }
         }
@@ -489,6 +1020,22 @@ impl State {
         self.ui.silenced = !self.ui.silenced;
     }
 
+    fn show_error(&mut self, key: &str) {
+        self.ui.error = Some(key.to_string());
+    }
+
+    fn dismiss_error(&mut self) {
+        self.ui.error = None;
+    }
+
+    fn show_profile(&mut self, did: DID) {
+        self.ui.profile = Some(did);
+    }
+
+    fn hide_profile(&mut self) {
+        self.ui.profile = None;
+    }
+
     /// Getters
     /// Getters are the only public facing methods besides dispatch.
     /// Getters help retrieve data from state in common ways preventing reused code.
@@ -524,13 +1071,18 @@ impl State {
     }
 
     pub fn get_chat_with_friend(&self, friend: &Identity) -> Chat {
-        let chat = self
-            .chats
+        self.direct_chat_with(friend).unwrap_or_default()
+    }
+
+    /// The one-on-one chat with a friend, if there is one.
+    pub fn direct_chat_with(&self, friend: &Identity) -> Option<Chat> {
+        self.chats
             .all
             .values()
-            .find(|chat| chat.participants.len() == 2 && chat.participants.contains(friend));
-
-        chat.unwrap_or(&Chat::default()).clone()
+            .find(|chat| {
+                !chat.group && chat.participants.len() == 2 && chat.participants.contains(friend)
+            })
+            .cloned()
     }
 
     pub fn get_without_me(&self, identities: Vec<Identity>) -> Vec<Identity> {
@@ -543,6 +1095,30 @@ impl State {
             .collect()
     }
 
+    /// Someone's last known presence. We're always online, on this desktop.
+    pub fn get_presence(&self, did: &DID) -> Presence {
+        if *did == self.account.identity.did_key() {
+            return Presence::new(Availability::Online, Device::Desktop);
+        }
+        self.friends.presence.get(did).copied().unwrap_or_default()
+    }
+
+    /// The name to show for someone, their nickname if we gave them one.
+    pub fn display_name(&self, identity: &Identity) -> String {
+        self.friends
+            .nicknames
+            .get(&identity.did_key())
+            .cloned()
+            .unwrap_or_else(|| identity.username())
+    }
+
+    pub fn is_blocked(&self, did: &DID) -> bool {
+        self.friends
+            .blocked
+            .iter()
+            .any(|identity| identity.did_key() == *did)
+    }
+
     pub fn has_friend_with_did(&self, did: &DID) -> bool {
         self.friends
             .all
@@ -550,36 +1126,55 @@ impl State {
             .any(|identity| identity.did_key() == *did)
     }
 
-    // Define a method for sorting a vector of messages.
+    /// Groups consecutive messages from the same sender. A new group is also started on
+    /// each new day, which gets a date divider, after the conversation was idle for longer
+    /// than the configured gap, and where the unread messages begin.
     pub fn get_sort_messages(&self, chat: &Chat) -> Vec<MessageGroup> {
-        let mut message_groups = Vec::new();
-        let current_sender = chat.messages[0].sender(); // TODO: This could error in runtime
-        let mut current_group = MessageGroup {
-            remote: self.has_friend_with_did(&current_sender),
-            sender: current_sender,
-            messages: Vec::new(),
-        };
-
-        for message in chat.messages.clone() {
-            if message.sender() != current_group.sender {
-                message_groups.push(current_group);
-                current_group = MessageGroup {
-                    remote: self.has_friend_with_did(&message.sender()),
+        let gap = self.settings.group_gap();
+        let me = self.account.identity.did_key();
+        let mut message_groups: Vec<MessageGroup> = Vec::new();
+        let mut previous: Option<&Message> = None;
+
+        for message in chat.messages.iter() {
+            let new_day = previous.map_or(true, |p| local_day(p) != local_day(message));
+            let idle = previous.map_or(false, |p| message.date() - p.date() > gap);
+            let first_unread = chat.first_unread == Some(message.id());
+            let new_sender = previous.map_or(true, |p| p.sender() != message.sender());
+
+            if new_sender || new_day || idle || first_unread {
+                if let Some(last) = message_groups
+                    .last_mut()
+                    .and_then(|group| group.messages.last_mut())
+                {
+                    last.is_last = true;
+                }
+                message_groups.push(MessageGroup {
+                    // Group members we aren't friends with are just as remote as friends.
+                    remote: message.sender() != me,
                     sender: message.sender(),
+                    divider: new_day.then(|| MessageDivider {
+                        timestamp: Some(message.date()),
+                    }),
+                    first_unread,
                     messages: Vec::new(),
-                };
+                });
             }
 
-            current_group.messages.push(GroupedMessage {
-                message,
-                is_first: current_group.messages.is_empty(),
-                is_last: false,
-            });
+            if let Some(group) = message_groups.last_mut() {
+                group.messages.push(GroupedMessage {
+                    message: message.clone(),
+                    is_first: group.messages.is_empty(),
+                    is_last: false,
+                });
+            }
+            previous = Some(message);
         }
 
-        if !current_group.messages.is_empty() {
-            current_group.messages.last_mut().unwrap().is_last = true;
-            message_groups.push(current_group);
+        if let Some(last) = message_groups
+            .last_mut()
+            .and_then(|group| group.messages.last_mut())
+        {
+            last.is_last = true;
         }
 
         message_groups
@@ -589,40 +1184,37 @@ impl State {
         self.friends.all.get(did).cloned().unwrap_or_default()
     }
 
+    /// Groups friends by the first letter of their display name, alphabetically and sorted
+    /// by name within each group. Accents are left out of both, so "Álvaro" is listed under
+    /// "a". Names that don't start with a letter are listed last, under `NON_LETTER_BUCKET`.
     pub fn get_friends_by_first_letter(
+        &self,
         friends: HashMap<DID, Identity>,
-    ) -> HashMap<char, Vec<Identity>> {
-        let mut friends_by_first_letter: HashMap<char, Vec<Identity>> = HashMap::new();
-
-        // Iterate over the friends and add each one to the appropriate Vec in the
-        // friends_by_first_letter HashMap
-        for (_, friend) in friends {
-            let first_letter = friend
-                .username()
-                .chars()
-                .next()
-                .unwrap()
-                .to_ascii_lowercase();
-
-            friends_by_first_letter
-                .entry(first_letter)
-                .or_insert_with(Vec::new)
-                .push(friend.clone());
+    ) -> Vec<(String, Vec<Identity>)> {
+        let mut by_letter: BTreeMap<String, Vec<Identity>> = BTreeMap::new();
+        let mut others = vec![];
+
+        for friend in friends.into_values() {
+            let first = name_key(&self.display_name(&friend)).chars().next();
+            match first.filter(|c| c.is_alphabetic()) {
+                Some(letter) => by_letter.entry(letter.into()).or_default().push(friend),
+                None => others.push(friend),
+            }
         }
 
-        // Sort the keys of the HashMap alphabetically
-        let mut sorted_keys: Vec<char> = friends_by_first_letter.keys().cloned().collect();
-        sorted_keys.sort_unstable();
-
-        // Create a new HashMap with the same values as friends_by_first_letter, but with
-        // the keys in alphabetical order
-        let mut sorted_friends_by_first_letter: HashMap<char, Vec<Identity>> = HashMap::new();
-        for key in sorted_keys {
-            sorted_friends_by_first_letter
-                .insert(key, friends_by_first_letter.get(&key).unwrap().clone());
+        let mut index: Vec<(String, Vec<Identity>)> = by_letter.into_iter().collect();
+        if !others.is_empty() {
+            index.push((NON_LETTER_BUCKET.into(), others));
         }
-
-        sorted_friends_by_first_letter
+        for (_, friends) in index.iter_mut() {
+            friends.sort_by_cached_key(|friend| {
+                (
+                    name_key(&self.display_name(friend)),
+                    friend.did_key().to_string(),
+                )
+            });
+        }
+        index
     }
 
     pub fn clear(&mut self) {
@@ -630,6 +1222,7 @@ impl State {
         self.friends = Friends::default();
         self.account = Account::default();
         self.settings = Settings::default();
+        self.dirty = true;
     }
 }
 
@@ -637,6 +1230,54 @@ impl State {
     pub fn mutate(&mut self, action: Action) {
         self.call_hooks(&action);
 
+        if let Some(inverse) = journal::inverse(self, &action) {
+            self.journal.record(Entry {
+                action: action.clone(),
+                inverse,
+            });
+        }
+
+        self.apply(action);
+
+        self.dirty = true;
+    }
+
+    /// Reverts the most recent undoable action.
+    pub fn undo(&mut self) {
+        let entry = match self.journal.take_undo() {
+            Some(entry) => entry,
+            None => return,
+        };
+
+        for action in entry.inverse {
+            self.call_hooks(&action);
+            self.apply(action);
+        }
+
+        self.dirty = true;
+    }
+
+    /// Re-applies the most recently undone action.
+    pub fn redo(&mut self) {
+        let action = match self.journal.take_redo() {
+            Some(action) => action,
+            None => return,
+        };
+
+        if let Some(inverse) = journal::inverse(self, &action) {
+            self.journal.record_redo(Entry {
+                action: action.clone(),
+                inverse,
+            });
+        }
+
+        self.call_hooks(&action);
+        self.apply(action);
+
+        self.dirty = true;
+    }
+
+    fn apply(&mut self, action: Action) {
         match action {
             // Action::Call(_) => todo!(),
             // Action::Hangup(_) => todo!(),
@@ -646,20 +1287,29 @@ impl State {
             Action::ToggleMedia(chat) => self.toggle_media(&chat),
             Action::EndAll => self.disable_all_active_media(),
             Action::SetLanguage(language) => self.set_language(&language),
-            Action::SendRequest(identity) => self.new_outgoing_request(&identity),
+            Action::SetPlainText(plain_text) => self.set_plain_text(plain_text),
+            Action::SetLinkPreviews(enabled) => self.set_link_previews(enabled),
+            Action::SetRequestExpiry(days) => self.set_request_expiry(days),
+            Action::SetGroupGap(minutes) => self.set_group_gap(minutes),
+            // The lookup is handled by the warp runner, which answers with `SendRequest` or an error.
+            Action::FindFriend(_, _) => {}
+            Action::SendRequest(request) => self.new_outgoing_request(&request),
             Action::RequestAccepted(identity) => {
                 self.complete_request(Direction::Outgoing, &identity);
             }
             Action::CancelRequest(identity) => {
                 self.cancel_request(Direction::Outgoing, &identity);
             }
-            Action::IncomingRequest(identity) => self.new_incoming_request(&identity),
+            Action::RequestFailed(did) => self.request_failed(&did),
+            Action::IncomingRequest(request) => self.new_incoming_request(&request),
+            Action::ExpireRequests => self.expire_requests(),
             Action::AcceptRequest(identity) => {
                 self.complete_request(Direction::Incoming, &identity);
             }
             Action::DenyRequest(identity) => {
                 self.cancel_request(Direction::Incoming, &identity);
             }
+            Action::AddFriend(friend) => self.add_friend(&friend),
             Action::RemoveFriend(friend) => self.remove_friend(&friend.did_key()),
             Action::Block(identity) => self.block(&identity),
             Action::UnBlock(identity) => self.unblock(&identity),
@@ -668,7 +1318,7 @@ impl State {
             Action::ChatWith(chat) => {
                 // TODO: this should create a conversation in warp if one doesn't exist
                 self.set_active_chat(&chat);
-                self.clear_unreads(&chat);
+                self.open_chat(&chat);
             }
             Action::AddToSidebar(chat) => {
                 self.add_chat_to_sidebar(chat);
@@ -676,7 +1326,9 @@ impl State {
             Action::RemoveFromSidebar(chat) => {
                 self.remove_sidebar_chat(&chat);
             }
-            Action::NewMessage(_, _) => todo!(),
+            Action::NewMessage(chat, message) => {
+                self.add_message(&chat, message);
+            }
             Action::ToggleFavorite(chat) => {
                 self.toggle_favorite(&chat);
             }
@@ -686,12 +1338,69 @@ impl State {
             Action::CancelReply(chat) => {
                 self.cancel_reply(&chat);
             }
+            Action::SetDraft(chat, draft) => {
+                self.set_draft(&chat, &draft);
+            }
+            Action::StartedTyping(chat, did) => {
+                self.start_typing(&chat, &did);
+            }
+            Action::PresenceChanged(did, presence) => self.set_presence(did, presence),
+            Action::SetNickname(did, nickname) => self.set_nickname(did, nickname),
+            Action::StoppedTyping(chat, did) => {
+                self.stop_typing(&chat, &did);
+            }
+            Action::ExpireTyping => self.expire_typing(),
+            // Creating the group is handled by the warp runner, which answers with `GroupCreated`.
+            Action::CreateGroup(_, _) => {}
+            Action::GroupCreated(chat) => self.add_group(chat),
+            Action::AddMember(chat, identity) => self.add_member(&chat, &identity),
+            Action::RemoveMember(chat, identity) => self.remove_member(&chat, &identity.did_key()),
+            Action::AddMemberFailed(chat, did) => {
+                self.remove_member(&chat, &did);
+                self.show_error("groups.members-failed");
+            }
+            Action::RemoveMemberFailed(chat, identity) => {
+                self.add_member(&chat, &identity);
+                self.show_error("groups.members-failed");
+            }
             Action::ClearUnreads(chat) => {
                 self.clear_unreads(&chat);
             }
-            Action::React(_, _, _) => todo!(),
-            Action::Reply(_, _) => todo!(),
-            Action::Send(_, _) => todo!(),
+            // The reaction is shown right away, RayGun's own view of the message replaces it
+            // once the warp runner reports it back.
+            Action::React(chat, message, reaction) => {
+                self.toggle_reaction(&chat, message.id(), &reaction);
+            }
+            Action::ReactionsUpdated(chat, message_id, reactions) => {
+                self.set_reactions(&chat, message_id, reactions);
+            }
+            // Edits and deletions are shown right away and carried to RayGun by the warp runner.
+            Action::Edit(chat, message) | Action::MessageEdited(chat, message) => {
+                self.edit_message(&chat, &message);
+            }
+            Action::Delete(chat, message) => {
+                self.delete_message(&chat, message.id());
+            }
+            Action::MessageDeleted(chat, message_id) => {
+                self.delete_message(&chat, message_id);
+            }
+            // Sending is handled by the warp runner through an `ActionHook`, the message itself
+            // is recorded once RayGun reports it back as a `NewMessage`.
+            Action::Reply(chat, _) => {
+                self.cancel_reply(&chat);
+                self.set_draft(&chat, "");
+            }
+            Action::Send(chat, _) => {
+                self.set_draft(&chat, "");
+            }
+            // Loading is handled by the warp runner, which answers with `MessagesLoaded`.
+            Action::LoadMessages(_, _) => {}
+            Action::MessagesLoaded(chat, page, messages) => {
+                self.load_messages(&chat, page, messages);
+            }
+            Action::JumpTo(chat, message_id) => {
+                self.jump_to(&chat, message_id);
+            }
             Action::Navigate(to) => {
                 self.set_active_route(to);
             }
@@ -699,9 +1408,18 @@ impl State {
             Action::TogglePopout => {
                 self.toggle_popout();
             }
+            Action::ShowError(key) => self.show_error(&key),
+            Action::DismissError => self.dismiss_error(),
+            Action::ShowProfile(did) => self.show_profile(did),
+            Action::HideProfile => self.hide_profile(),
         }
+    }
 
-        let _ = self.save();
+    /// Applies the clean up done on a timer, e.g. `ExpireRequests`. Hooks see it like any
+    /// other action, but it isn't journaled and doesn't mark the state as changed.
+    pub fn expire(&mut self, action: Action) {
+        self.call_hooks(&action);
+        self.apply(action);
     }
 
     fn call_hooks(&mut self, action: &Action) {
@@ -728,35 +1446,113 @@ impl State {
         self.hooks.push(hook);
     }
 
-    /// Saves the current state to disk.
-    fn save(&self) -> Result<(), Box<dyn std::error::Error>> {
-        let serialized = serde_json::to_string(self)?;
-        let cache_path = dirs::home_dir()
-            .unwrap_or_default()
-            .join(".uplink/state.json")
-            .into_os_string()
-            .into_string()
-            .unwrap_or_default();
+    /// Hands the current state to the background writer if anything changed since the
+    /// last call. The write itself is atomic and happens off the UI thread. If a passphrase
+    /// has been set, the state is encrypted before it leaves memory.
+    pub fn persist(&mut self) -> Result<(), Box<dyn std::error::Error>> {
+        // A locked state is a placeholder, it must never overwrite the encrypted file. The
+        // same goes for the empty state shown while no profile is open.
+        if !self.dirty || self.locked || profiles::active().is_none() {
+            return Ok(());
+        }
 
-        fs::write(cache_path, serialized)?;
+        let serialized = migrations::serialize(self)?;
+        let contents = match &self.key {
+            Some(key) => key.encrypt(&serialized)?,
+            None => serialized,
+        };
+
+        persistence::write(state_path(), contents);
+        self.dirty = false;
         Ok(())
     }
 
-    /// Loads the state from a file on disk, if it exists.
+    /// Loads the state from a file on disk, if it exists, migrating it from older schema
+    /// versions. A file that can't be loaded is backed up before an error is returned. Without
+    /// a file, a new profile starts out empty and MultiPass fills its identity in once messaging
+    /// starts. Builds with the `mock` feature start from mock data instead.
+    ///
+    /// An encrypted file is left untouched and returns a `PermissionDenied` error, it has to
+    /// be opened with `State::unlock` instead.
     pub fn load() -> Result<Self, std::io::Error> {
-        let cache_path = dirs::home_dir()
-            .unwrap_or_default()
-            .join(".uplink/state.json")
-            .into_os_string()
-            .into_string()
-            .unwrap_or_default();
-        match fs::read_to_string(cache_path) {
-            Ok(contents) => {
-                let state: State = serde_json::from_str(&contents)?;
-                Ok(state)
-            }
-            Err(_) => Ok(generate_mock()),
+        State::load_from(&state_path())
+    }
+
+    fn load_from(cache_path: &std::path::Path) -> Result<Self, std::io::Error> {
+        let contents = match fs::read_to_string(cache_path) {
+            Ok(contents) => contents,
+            #[cfg(feature = "mock")]
+            Err(_) => return Ok(generate_mock()),
+            #[cfg(not(feature = "mock"))]
+            Err(_) => return Ok(State::default()),
+        };
+
+        if encryption::is_encrypted(&contents) {
+            return Err(std::io::Error::new(
+                std::io::ErrorKind::PermissionDenied,
+                "state file is encrypted",
+            ));
         }
+
+        migrations::parse(&contents).or_else(|e| {
+            migrations::backup(cache_path)?;
+            Err(std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+        })
+    }
+
+    /// Keeps the state locked until the profile's passphrase is entered with `State::unlock`.
+    /// Nothing is saved in the meantime.
+    pub fn lock(&mut self) {
+        self.locked = true;
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.locked
+    }
+
+    /// Decrypts the state on disk with the profile's passphrase, if it's encrypted. Deriving
+    /// the key takes a while, so this is meant to run off the UI thread before `State::unlock`.
+    /// A wrong passphrase returns an error.
+    pub fn decrypt_saved(passphrase: &str) -> Result<Option<(String, StateKey)>, EncryptionError> {
+        let contents = fs::read_to_string(state_path()).unwrap_or_default();
+        if !encryption::is_encrypted(&contents) {
+            return Ok(None);
+        }
+        StateKey::decrypt(&contents, passphrase).map(Some)
+    }
+
+    /// Unlocks the profile with its passphrase, which is then held in memory for warp's
+    /// keystore. `decrypted` is what `State::decrypt_saved` returned for the same passphrase,
+    /// it replaces this state while keeping the hooks registered on the placeholder.
+    pub fn unlock(
+        &mut self,
+        passphrase: Zeroizing<String>,
+        decrypted: Option<(String, StateKey)>,
+    ) -> Result<(), std::io::Error> {
+        if let Some((plaintext, key)) = decrypted {
+            let mut unlocked = migrations::parse(&plaintext).or_else(|e| {
+                migrations::backup(&state_path())?;
+                Err(std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+            })?;
+            unlocked.hooks = std::mem::take(&mut self.hooks);
+            unlocked.key = Some(key);
+            *self = unlocked;
+        }
+
+        self.account.keystore_passphrase = passphrase;
+        self.locked = false;
+        Ok(())
+    }
+
+    /// Encrypts the state file at rest with `key`, or stores it in plain text again when
+    /// `None` is given. Takes effect on the next save.
+    pub fn set_key(&mut self, key: Option<StateKey>) {
+        self.key = key;
+        self.dirty = true;
+    }
+
+    pub fn is_encrypted(&self) -> bool {
+        self.key.is_some()
     }
 
     pub fn mock() -> State {
@@ -764,6 +1560,19 @@ impl State {
     }
 }
 
+// The calendar day a message was sent on, in the user's timezone.
+fn local_day(message: &Message) -> chrono::NaiveDate {
+    message
+        .date()
+        .with_timezone(&chrono::Local)
+        .naive_local()
+        .date()
+}
+
+fn state_path() -> std::path::PathBuf {
+    profiles::data_dir().join("state.json")
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
 pub enum Action {
     // UI
@@ -772,6 +1581,12 @@ pub enum Action {
     ToggleSilence,
     ToggleMute,
     ToggleMedia(Chat),
+    /// Shows an error in a toast, given its locale key
+    ShowError(String),
+    DismissError,
+    /// Opens the profile panel of the given user
+    ShowProfile(DID),
+    HideProfile,
     // Account
     /// Sets the ID for the user.
     SetId(Identity),
@@ -779,29 +1594,47 @@ pub enum Action {
     // Settings
     /// Sets the selected language.
     SetLanguage(String),
+    /// Turns Markdown rendering of messages off or back on.
+    SetPlainText(bool),
+    /// Turns preview cards for links in messages on or off.
+    SetLinkPreviews(bool),
+    /// Sets the number of days before unanswered outgoing friend requests are dropped.
+    SetRequestExpiry(i64),
+    /// Sets the number of quiet minutes after which a new message group is started.
+    SetGroupGap(i64),
 
     // Routes
     /// Set the active route
     Navigate(To),
     // Requests
+    /// Looks someone up through MultiPass, a request with the optional note is sent if they're found
+    FindFriend(FriendQuery, Option<String>),
     /// Send a new friend request
-    SendRequest(Identity),
+    SendRequest(FriendRequest),
     /// To be fired when a friend request you sent is accepted
     RequestAccepted(Identity),
     /// Cancel an outgoing request
     CancelRequest(Identity),
+    /// A friend request couldn't be sent, it's taken back and an error is shown
+    RequestFailed(DID),
 
     /// Handle a new incoming friend request
-    IncomingRequest(Identity),
+    IncomingRequest(FriendRequest),
     /// Accept an incoming friend request
     AcceptRequest(Identity),
     /// Deny a incoming friend request
     DenyRequest(Identity),
+    /// Drops outgoing requests that have gone unanswered for too long
+    ExpireRequests,
 
     // Friends
+    /// Adds an identity straight to the friends list, used to restore a removed friend
+    AddFriend(Identity),
     RemoveFriend(Identity),
     Block(Identity),
     UnBlock(Identity),
+    /// Gives someone a local nickname, or clears it
+    SetNickname(DID, Option<String>),
     /// Handles the display of "favorite" chats
     Favorite(Chat),
     UnFavorite(Chat),
@@ -813,20 +1646,58 @@ pub enum Action {
     RemoveFromSidebar(Chat),
     /// Adds or removes a chat from the favorites page
     ToggleFavorite(Chat),
+    /// Asks RayGun for a group conversation with the given friends, optionally named
+    CreateGroup(Vec<Identity>, Option<String>),
+    /// To be fired when RayGun created a group conversation, opens the new chat
+    GroupCreated(Chat),
+    /// Adds a member to a group chat
+    AddMember(Chat, Identity),
+    /// Removes a member from a group chat
+    RemoveMember(Chat, Identity),
+    /// Takes back adding a member RayGun couldn't add
+    AddMemberFailed(Chat, DID),
+    /// Puts back a member RayGun couldn't remove
+    RemoveMemberFailed(Chat, Identity),
 
     // Messaging
     /// Records a new message and plays associated notifications
     NewMessage(Chat, Message),
-    /// React to a given message by ID
+    /// Toggles a reaction on a message for the reaction's users, normally just us
     React(Chat, Message, Reaction),
+    /// Replaces the reactions on a message with the ones reported by RayGun
+    ReactionsUpdated(Chat, Uuid, Vec<Reaction>),
     /// Reply to a given message by ID
     Reply(Chat, Message),
+    /// Replaces the text of one of our messages with the text of the given message
+    Edit(Chat, Message),
+    /// Deletes one of our messages, leaving a tombstone in its place
+    Delete(Chat, Message),
+    /// To be fired when RayGun reports a message was edited
+    MessageEdited(Chat, Message),
+    /// To be fired when RayGun reports a message was deleted
+    MessageDeleted(Chat, Uuid),
     /// Prep the UI for a message reply.
     StartReplying(Chat, Message),
     /// Clears the reply for a given chat
     CancelReply(Chat),
+    /// Keeps the text typed into the chatbar of the given chat
+    SetDraft(Chat, String),
+    /// To be fired when RayGun reports someone is typing in the given chat
+    StartedTyping(Chat, DID),
+    /// To be fired when RayGun reports someone stopped typing in the given chat
+    StoppedTyping(Chat, DID),
+    /// Records someone's availability and device, as reported by MultiPass
+    PresenceChanged(DID, Presence),
+    /// Drops typing indicators that weren't refreshed in time
+    ExpireTyping,
     /// Sends a message to the given chat
     Send(Chat, Message),
+    /// Requests a page of history next to the messages currently loaded for the chat
+    LoadMessages(Chat, Page),
+    /// Adds a page of history loaded from RayGun to the chat
+    MessagesLoaded(Chat, Page, Vec<Message>),
+    /// Scrolls the chat to a message, loading the history around it if needed
+    JumpTo(Chat, Uuid),
     ClearUnreads(Chat),
 }
 
@@ -834,4 +1705,109 @@ impl Action {
     fn compare_discriminant(&self, other: &Action) -> bool {
         std::mem::discriminant(self) == std::mem::discriminant(other)
     }
+
+    /// Actions the user is offered to undo right after dispatching them.
+    pub fn is_destructive(&self) -> bool {
+        matches!(
+            self,
+            Action::RemoveFriend(_)
+                | Action::RemoveMember(_, _)
+                | Action::Block(_)
+                | Action::RemoveFromSidebar(_)
+                | Action::UnFavorite(_)
+        )
+    }
+}
+
+/// Lowercases a name and strips its accents, for sorting, indexing and filtering names.
+pub(crate) fn name_key(name: &str) -> String {
+    name.nfd()
+        .filter(|c| !is_combining_mark(*c))
+        .flat_map(char::to_lowercase)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message_from(sender: &Identity) -> Message {
+        let mut message = Message::default();
+        message.set_sender(sender.did_key());
+        message
+    }
+
+    fn state_with_chat(me: &Identity, chat: &Chat) -> State {
+        let mut state = State::default();
+        state.account.identity = me.clone();
+        state.chats.all.insert(chat.id, chat.clone());
+        state
+    }
+
+    #[cfg(not(feature = "mock"))]
+    #[test]
+    fn profiles_without_a_state_file_start_out_empty() {
+        let path = std::env::temp_dir()
+            .join(format!("uplink-new-profile-{}", Uuid::new_v4()))
+            .join("state.json");
+        let state = State::load_from(&path).unwrap();
+        assert!(state.friends.all.is_empty());
+        assert!(state.chats.all.is_empty());
+    }
+
+    #[test]
+    fn never_read_chats_are_divided_where_others_started_writing() {
+        let me = Identity::default();
+        let friend = Identity::default();
+        let messages = vec![
+            message_from(&me),
+            message_from(&friend),
+            message_from(&friend),
+        ];
+        let chat = Chat {
+            id: Uuid::new_v4(),
+            participants: vec![me.clone(), friend],
+            messages: messages.clone(),
+            ..Chat::default()
+        };
+        let mut state = state_with_chat(&me, &chat);
+
+        state.mutate(Action::ChatWith(chat.clone()));
+        let opened = &state.chats.all[&chat.id];
+        assert_eq!(opened.first_unread, Some(messages[1].id()));
+        let divided: Vec<bool> = state
+            .get_sort_messages(opened)
+            .iter()
+            .map(|group| group.first_unread)
+            .collect();
+        assert_eq!(divided, vec![false, true]);
+
+        // Everything has been read by the time it's opened again.
+        state.mutate(Action::ChatWith(chat.clone()));
+        assert_eq!(state.chats.all[&chat.id].first_unread, None);
+    }
+
+    #[test]
+    fn reacting_twice_with_the_same_emoji_takes_the_reaction_back() {
+        let me = Identity::default();
+        let message = message_from(&me);
+        let chat = Chat {
+            id: Uuid::new_v4(),
+            messages: vec![message.clone()],
+            ..Chat::default()
+        };
+        let mut state = state_with_chat(&me, &chat);
+        let mut reaction = Reaction::default();
+        reaction.set_emoji("👍");
+        reaction.set_users(vec![me.did_key()]);
+        let react = Action::React(chat.clone(), message.clone(), reaction);
+
+        state.mutate(react.clone());
+        let reactions = state.chats.all[&chat.id].messages[0].reactions();
+        assert_eq!(reactions.len(), 1);
+        assert_eq!(reactions[0].users(), vec![me.did_key()]);
+
+        state.mutate(react);
+        assert!(state.chats.all[&chat.id].messages[0].reactions().is_empty());
+    }
 }
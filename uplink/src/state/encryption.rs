@@ -0,0 +1,239 @@
+use std::fmt;
+
+use argon2::{Algorithm, Argon2, Params, Version};
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+use warp::crypto::cipher::Cipher;
+use zeroize::Zeroizing;
+
+// Identifies the cipher used in the envelope, so it can be changed later.
+const CIPHER: &str = "aes-256-gcm";
+
+// Identifies the function stretching the passphrase into a key, same as the cipher.
+const KDF: &str = "argon2id";
+
+// Cost of deriving new keys. The parameters are stored in the envelope, so they can be
+// raised later without locking anyone out.
+const KDF_MEMORY_KIB: u32 = 64 * 1024;
+const KDF_ITERATIONS: u32 = 3;
+const KDF_PARALLELISM: u32 = 1;
+
+// The parameters come from the file being opened, so anything past these is refused before
+// it can exhaust memory or hang the app.
+const KDF_MAX_MEMORY_KIB: u32 = 1024 * 1024;
+const KDF_MAX_ITERATIONS: u32 = 64;
+const KDF_MAX_PARALLELISM: u32 = 16;
+
+const KEY_LEN: usize = 32;
+
+const SALT_LEN: usize = 16;
+
+/// How a key was derived from the passphrase.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+struct KdfParams {
+    algorithm: String,
+    memory_kib: u32,
+    iterations: u32,
+    parallelism: u32,
+}
+
+impl KdfParams {
+    fn within_limits(&self) -> bool {
+        self.memory_kib <= KDF_MAX_MEMORY_KIB
+            && self.iterations <= KDF_MAX_ITERATIONS
+            && self.parallelism <= KDF_MAX_PARALLELISM
+    }
+}
+
+impl Default for KdfParams {
+    fn default() -> Self {
+        KdfParams {
+            algorithm: KDF.into(),
+            memory_kib: KDF_MEMORY_KIB,
+            iterations: KDF_ITERATIONS,
+            parallelism: KDF_PARALLELISM,
+        }
+    }
+}
+
+/// The on-disk format of an encrypted state file.
+#[derive(Deserialize, Serialize)]
+struct Envelope {
+    cipher: String,
+    kdf: KdfParams,
+    salt: String,
+    data: String,
+}
+
+#[derive(Debug)]
+pub enum EncryptionError {
+    /// The contents aren't an encrypted state file.
+    NotEncrypted,
+    /// The passphrase is wrong or the file was tampered with.
+    Decrypt,
+    Encrypt,
+}
+
+impl fmt::Display for EncryptionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            EncryptionError::NotEncrypted => write!(f, "state file is not encrypted"),
+            EncryptionError::Decrypt => write!(f, "unable to decrypt the state file"),
+            EncryptionError::Encrypt => write!(f, "unable to encrypt the state file"),
+        }
+    }
+}
+
+impl std::error::Error for EncryptionError {}
+
+/// A key derived from the user's passphrase, used to encrypt the state file at rest.
+/// It only ever lives in memory, and is wiped from it once dropped.
+#[derive(Clone)]
+pub struct StateKey {
+    key: Zeroizing<Vec<u8>>,
+    salt: Vec<u8>,
+    kdf: KdfParams,
+}
+
+impl fmt::Debug for StateKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("StateKey").finish_non_exhaustive()
+    }
+}
+
+impl StateKey {
+    /// Derives a key from `passphrase` with a fresh random salt. This takes a while, so it's
+    /// best done off the UI thread.
+    pub fn new(passphrase: &str) -> Self {
+        let salt: [u8; SALT_LEN] = rand::thread_rng().gen();
+        Self::derive(passphrase, &salt, KdfParams::default())
+            .expect("the default key derivation parameters are valid")
+    }
+
+    fn derive(passphrase: &str, salt: &[u8], kdf: KdfParams) -> Result<Self, EncryptionError> {
+        if kdf.algorithm != KDF || !kdf.within_limits() {
+            return Err(EncryptionError::Decrypt);
+        }
+        let params = Params::new(
+            kdf.memory_kib,
+            kdf.iterations,
+            kdf.parallelism,
+            Some(KEY_LEN),
+        )
+        .map_err(|_| EncryptionError::Decrypt)?;
+
+        let mut key = Zeroizing::new(vec![0; KEY_LEN]);
+        Argon2::new(Algorithm::Argon2id, Version::V0x13, params)
+            .hash_password_into(passphrase.as_bytes(), salt, &mut key)
+            .map_err(|_| EncryptionError::Decrypt)?;
+
+        Ok(StateKey {
+            key,
+            salt: salt.to_vec(),
+            kdf,
+        })
+    }
+
+    /// Encrypts the serialized state into an envelope ready to be written to disk.
+    pub fn encrypt(&self, plaintext: &str) -> Result<String, EncryptionError> {
+        let data = Cipher::direct_encrypt(plaintext.as_bytes(), &self.key)
+            .map_err(|_| EncryptionError::Encrypt)?;
+
+        let envelope = Envelope {
+            cipher: CIPHER.into(),
+            kdf: self.kdf.clone(),
+            salt: base64::encode(&self.salt),
+            data: base64::encode(data),
+        };
+        serde_json::to_string(&envelope).map_err(|_| EncryptionError::Encrypt)
+    }
+
+    /// Decrypts an envelope with `passphrase`. Returns the plaintext along with the key,
+    /// so that later saves are encrypted the same way.
+    pub fn decrypt(contents: &str, passphrase: &str) -> Result<(String, Self), EncryptionError> {
+        let envelope = parse_envelope(contents).ok_or(EncryptionError::NotEncrypted)?;
+        let salt = base64::decode(envelope.salt).map_err(|_| EncryptionError::Decrypt)?;
+        let data = base64::decode(envelope.data).map_err(|_| EncryptionError::Decrypt)?;
+
+        let key = Self::derive(passphrase, &salt, envelope.kdf)?;
+        let plaintext =
+            Cipher::direct_decrypt(&data, &key.key).map_err(|_| EncryptionError::Decrypt)?;
+        let plaintext = String::from_utf8(plaintext).map_err(|_| EncryptionError::Decrypt)?;
+
+        Ok((plaintext, key))
+    }
+
+    /// Decrypts an envelope that was encrypted with this key, such as other files kept
+    /// next to the state.
+    pub fn decrypt_with_key(&self, contents: &str) -> Result<String, EncryptionError> {
+        let envelope = parse_envelope(contents).ok_or(EncryptionError::NotEncrypted)?;
+        let data = base64::decode(envelope.data).map_err(|_| EncryptionError::Decrypt)?;
+
+        let plaintext =
+            Cipher::direct_decrypt(&data, &self.key).map_err(|_| EncryptionError::Decrypt)?;
+        String::from_utf8(plaintext).map_err(|_| EncryptionError::Decrypt)
+    }
+}
+
+fn parse_envelope(contents: &str) -> Option<Envelope> {
+    serde_json::from_str::<Envelope>(contents)
+        .ok()
+        .filter(|envelope| envelope.cipher == CIPHER)
+}
+
+/// Checks whether the contents of a state file are encrypted.
+pub fn is_encrypted(contents: &str) -> bool {
+    parse_envelope(contents).is_some()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn envelopes_open_with_the_same_passphrase_only() {
+        let encrypted = StateKey::new("correct horse").encrypt("{}").unwrap();
+        assert!(is_encrypted(&encrypted));
+
+        let (plaintext, key) = StateKey::decrypt(&encrypted, "correct horse").unwrap();
+        assert_eq!(plaintext, "{}");
+        assert_eq!(key.kdf, KdfParams::default());
+        assert!(StateKey::decrypt(&encrypted, "wrong horse").is_err());
+    }
+
+    #[test]
+    fn keys_are_derived_with_the_stored_parameters() {
+        let kdf = KdfParams {
+            memory_kib: 1024,
+            iterations: 1,
+            ..KdfParams::default()
+        };
+        let key = StateKey::derive("passphrase", &[0; SALT_LEN], kdf).unwrap();
+        let encrypted = key.encrypt("{}").unwrap();
+
+        let (_, reopened) = StateKey::decrypt(&encrypted, "passphrase").unwrap();
+        assert_eq!(reopened.kdf.memory_kib, 1024);
+        assert_eq!(*reopened.key, *key.key);
+    }
+
+    #[test]
+    fn parameters_past_the_limits_are_refused() {
+        let too_costly = [
+            KdfParams {
+                memory_kib: KDF_MAX_MEMORY_KIB + 1,
+                ..KdfParams::default()
+            },
+            KdfParams {
+                iterations: KDF_MAX_ITERATIONS + 1,
+                ..KdfParams::default()
+            },
+            KdfParams {
+                parallelism: KDF_MAX_PARALLELISM + 1,
+                ..KdfParams::default()
+            },
+        ];
+        for kdf in too_costly {
+            assert!(StateKey::derive("passphrase", &[0; SALT_LEN], kdf).is_err());
+        }
+    }
+}
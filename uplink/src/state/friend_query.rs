@@ -0,0 +1,154 @@
+use std::str::FromStr;
+
+use serde::{Deserialize, Serialize};
+use warp::{crypto::DID, multipass::identity::Identity};
+
+use super::name_key;
+
+const DID_PREFIX: &str = "did:key:";
+const SHORT_ID_LEN: usize = 4;
+const USERNAME_MIN_LEN: usize = 4;
+const USERNAME_MAX_LEN: usize = 32;
+
+/// Someone to look up before sending them a friend request, either by their DID key or by
+/// their username along with the short ID that tells apart users sharing it.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum FriendQuery {
+    Did(DID),
+    Username { username: String, short_id: u16 },
+}
+
+/// Why the text typed in couldn't be turned into a `FriendQuery`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ParseError {
+    /// Neither a DID key nor `Username#0000`.
+    InvalidFormat,
+    /// Starts like a DID key but isn't one.
+    InvalidDid,
+    /// The username is too short or too long.
+    InvalidUsername,
+    /// The part after `#` isn't four digits.
+    InvalidShortId,
+}
+
+impl ParseError {
+    /// Locale key describing the error.
+    pub fn locale_key(&self) -> &'static str {
+        match self {
+            ParseError::InvalidFormat => "friends.invalid-format",
+            ParseError::InvalidDid => "friends.invalid-did",
+            ParseError::InvalidUsername => "friends.invalid-username",
+            ParseError::InvalidShortId => "friends.invalid-short-id",
+        }
+    }
+}
+
+impl FromStr for FriendQuery {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim();
+
+        if s.starts_with(DID_PREFIX) {
+            return DID::from_str(s)
+                .map(FriendQuery::Did)
+                .map_err(|_| ParseError::InvalidDid);
+        }
+
+        let (username, short_id) = s.rsplit_once('#').ok_or(ParseError::InvalidFormat)?;
+        let username = username.trim();
+        let length = username.chars().count();
+        if !(USERNAME_MIN_LEN..=USERNAME_MAX_LEN).contains(&length) {
+            return Err(ParseError::InvalidUsername);
+        }
+        if short_id.len() != SHORT_ID_LEN || !short_id.chars().all(|c| c.is_ascii_digit()) {
+            return Err(ParseError::InvalidShortId);
+        }
+
+        Ok(FriendQuery::Username {
+            username: username.to_string(),
+            short_id: short_id.parse().map_err(|_| ParseError::InvalidShortId)?,
+        })
+    }
+}
+
+impl FriendQuery {
+    /// Whether `identity` is the one being looked for. Usernames are matched regardless of case
+    /// and accents.
+    pub fn matches(&self, identity: &Identity) -> bool {
+        match self {
+            FriendQuery::Did(did) => identity.did_key() == *did,
+            FriendQuery::Username { username, short_id } => {
+                name_key(&identity.username()) == name_key(username)
+                    && identity.short_id() == *short_id
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn did_keys_are_parsed() {
+        let did = DID::default();
+        assert_eq!(
+            format!("  {}  ", did).parse::<FriendQuery>(),
+            Ok(FriendQuery::Did(did.clone()))
+        );
+        assert_eq!(
+            "did:key:not-a-key".parse::<FriendQuery>(),
+            Err(ParseError::InvalidDid)
+        );
+    }
+
+    #[test]
+    fn usernames_need_a_short_id() {
+        assert_eq!(
+            "name#0000".parse::<FriendQuery>(),
+            Ok(FriendQuery::Username {
+                username: "name".into(),
+                short_id: 0,
+            })
+        );
+        assert_eq!(
+            "name".parse::<FriendQuery>(),
+            Err(ParseError::InvalidFormat)
+        );
+        for short_id in ["", "123", "12345", "12a4", "+123"] {
+            assert_eq!(
+                format!("name#{}", short_id).parse::<FriendQuery>(),
+                Err(ParseError::InvalidShortId)
+            );
+        }
+    }
+
+    #[test]
+    fn usernames_are_4_to_32_characters_long() {
+        for length in [3, 33] {
+            assert_eq!(
+                format!("{}#1234", "a".repeat(length)).parse::<FriendQuery>(),
+                Err(ParseError::InvalidUsername)
+            );
+        }
+        for length in [4, 32] {
+            assert!(format!("{}#1234", "a".repeat(length))
+                .parse::<FriendQuery>()
+                .is_ok());
+        }
+        // Length is counted in characters, not bytes.
+        assert!("ñandú#1234".parse::<FriendQuery>().is_ok());
+    }
+
+    #[test]
+    fn usernames_match_regardless_of_case_and_accents() {
+        let mut identity = Identity::default();
+        identity.set_username("José");
+        identity.set_short_id(1234);
+        let query: FriendQuery = "jose#1234".parse().unwrap();
+        assert!(query.matches(&identity));
+        let other: FriendQuery = "jose#4321".parse().unwrap();
+        assert!(!other.matches(&identity));
+    }
+}
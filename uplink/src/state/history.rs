@@ -0,0 +1,269 @@
+use serde::{Deserialize, Serialize, Serializer};
+use warp::raygun::Message;
+
+use super::Chat;
+
+/// How many messages are requested from RayGun at a time.
+pub const PAGE_SIZE: usize = 50;
+
+/// How many pages a chat keeps in memory. Past that, pages furthest from the one being
+/// loaded are evicted.
+pub const MAX_PAGES: usize = 4;
+
+const MAX_WINDOW: usize = PAGE_SIZE * MAX_PAGES;
+
+/// Which side of a chat's message window a page of history belongs to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Page {
+    /// Messages right before the oldest one in the window.
+    Older,
+    /// Messages right after the newest one in the window.
+    Newer,
+    /// Messages surrounding a given message, replacing the window. Used to jump to a
+    /// message that isn't loaded.
+    Around,
+}
+
+/// Only the most recent page of a chat is written to disk, the rest can be loaded from
+/// RayGun again.
+pub(super) fn serialize_recent<S>(messages: &[Message], serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    let start = messages.len().saturating_sub(PAGE_SIZE);
+    serializer.collect_seq(&messages[start..])
+}
+
+impl Chat {
+    /// The message a page on the given side should be loaded relative to.
+    pub fn page_anchor(&self, page: Page) -> Option<&Message> {
+        match page {
+            Page::Older => self.messages.first(),
+            Page::Newer => self.messages.last(),
+            Page::Around => None,
+        }
+    }
+
+    /// Whether there could be more history on the given side of the window.
+    pub fn has_more(&self, page: Page) -> bool {
+        match page {
+            Page::Older => !self.reached_start,
+            Page::Newer => self.detached,
+            Page::Around => false,
+        }
+    }
+
+    /// The newest message of the conversation, even when the window is scrolled back.
+    pub fn last_message(&self) -> Option<&Message> {
+        self.missed.last().or_else(|| self.messages.last())
+    }
+
+    /// Appends a live message. While the window is scrolled back, the message is kept aside
+    /// so it still counts as unread and shows in the sidebar, until the window catches up.
+    pub(super) fn push_message(&mut self, message: Message) {
+        if self.detached {
+            if !self.missed.iter().any(|m| m.id() == message.id()) {
+                self.missed.push(message);
+            }
+            if self.missed.len() > MAX_WINDOW {
+                self.missed.drain(..self.missed.len() - MAX_WINDOW);
+            }
+            return;
+        }
+
+        self.messages.push(message);
+        if self.messages.len() > MAX_WINDOW {
+            self.messages.drain(..self.messages.len() - MAX_WINDOW);
+            self.reached_start = false;
+        }
+    }
+
+    /// Adds a page of history to the window, evicting pages on the opposite side to keep
+    /// the window bounded.
+    pub(super) fn load_page(&mut self, page: Page, messages: Vec<Message>) {
+        let complete = messages.len() < PAGE_SIZE;
+        let messages: Vec<Message> = messages
+            .into_iter()
+            .filter(|message| !self.messages.iter().any(|m| m.id() == message.id()))
+            .collect();
+
+        match page {
+            Page::Older => {
+                self.reached_start = complete;
+                self.messages.splice(..0, messages);
+                if self.messages.len() > MAX_WINDOW {
+                    self.messages.truncate(MAX_WINDOW);
+                    self.detached = true;
+                }
+            }
+            Page::Around => {
+                if messages.is_empty() {
+                    return;
+                }
+                // Where the loaded page sits in the conversation isn't known, the sentinels
+                // on both sides find out.
+                self.messages = messages;
+                self.reached_start = false;
+                self.detached = true;
+            }
+            Page::Newer => {
+                self.detached = !complete;
+                self.messages.extend(messages);
+                // Caught up, whatever came in meanwhile and isn't on the page goes after it.
+                if complete {
+                    for message in std::mem::take(&mut self.missed) {
+                        if !self.messages.iter().any(|m| m.id() == message.id()) {
+                            self.messages.push(message);
+                        }
+                    }
+                }
+                if self.messages.len() > MAX_WINDOW {
+                    self.messages.drain(..self.messages.len() - MAX_WINDOW);
+                    self.reached_start = false;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn messages(count: usize) -> Vec<Message> {
+        (0..count)
+            .map(|i| {
+                let mut message = Message::default();
+                message.set_value(vec![i.to_string()]);
+                message
+            })
+            .collect()
+    }
+
+    // A chat showing the newest page of `conversation`.
+    fn chat_at_the_end(conversation: &[Message]) -> Chat {
+        Chat {
+            messages: conversation[conversation.len() - PAGE_SIZE..].to_vec(),
+            ..Chat::default()
+        }
+    }
+
+    // Scrolls back until the window is full and the newest page gets evicted.
+    fn scroll_back_past_the_window(chat: &mut Chat, conversation: &[Message]) {
+        let mut end = conversation.len() - PAGE_SIZE;
+        for _ in 0..MAX_PAGES {
+            chat.load_page(Page::Older, conversation[end - PAGE_SIZE..end].to_vec());
+            end -= PAGE_SIZE;
+        }
+    }
+
+    #[test]
+    fn older_pages_evict_the_newest_once_the_window_is_full() {
+        let conversation = messages(PAGE_SIZE * 8);
+        let mut chat = chat_at_the_end(&conversation);
+        let len = conversation.len();
+
+        for page in 1..MAX_PAGES {
+            let end = len - PAGE_SIZE * page;
+            chat.load_page(Page::Older, conversation[end - PAGE_SIZE..end].to_vec());
+        }
+        assert_eq!(chat.messages, conversation[len - MAX_WINDOW..]);
+        assert!(!chat.detached);
+
+        let end = len - MAX_WINDOW;
+        chat.load_page(Page::Older, conversation[end - PAGE_SIZE..end].to_vec());
+        assert_eq!(chat.messages.len(), MAX_WINDOW);
+        assert_eq!(
+            chat.messages,
+            conversation[end - PAGE_SIZE..len - PAGE_SIZE]
+        );
+        assert!(chat.detached);
+        assert!(chat.has_more(Page::Newer));
+        assert!(chat.has_more(Page::Older));
+
+        // A short page is the start of the conversation.
+        chat.load_page(Page::Older, conversation[..10].to_vec());
+        assert!(!chat.has_more(Page::Older));
+        assert_eq!(chat.messages.len(), MAX_WINDOW);
+    }
+
+    #[test]
+    fn newer_pages_reattach_the_window() {
+        let conversation = messages(PAGE_SIZE * 8);
+        let len = conversation.len();
+        let mut chat = chat_at_the_end(&conversation);
+        scroll_back_past_the_window(&mut chat, &conversation);
+        assert!(chat.detached);
+
+        // A full page could have more after it.
+        chat.load_page(Page::Newer, conversation[len - PAGE_SIZE..].to_vec());
+        assert!(chat.detached);
+        assert_eq!(chat.messages, conversation[len - MAX_WINDOW..]);
+        assert!(chat.has_more(Page::Older));
+
+        chat.load_page(Page::Newer, vec![]);
+        assert!(!chat.detached);
+        assert!(!chat.has_more(Page::Newer));
+        assert_eq!(chat.messages, conversation[len - MAX_WINDOW..]);
+    }
+
+    #[test]
+    fn live_messages_wait_while_scrolled_back() {
+        let conversation = messages(PAGE_SIZE * 8);
+        let len = conversation.len();
+        let mut chat = chat_at_the_end(&conversation);
+        scroll_back_past_the_window(&mut chat, &conversation);
+        let window = chat.messages.clone();
+
+        let live = messages(1).remove(0);
+        chat.push_message(live.clone());
+        chat.push_message(live.clone());
+        assert_eq!(chat.messages, window);
+        assert_eq!(chat.missed, vec![live.clone()]);
+        assert_eq!(chat.last_message(), Some(&live));
+
+        chat.load_page(Page::Newer, conversation[len - PAGE_SIZE..].to_vec());
+        chat.load_page(Page::Newer, vec![]);
+        assert_eq!(chat.messages.last(), Some(&live));
+        assert_eq!(chat.messages.len(), MAX_WINDOW);
+        assert!(chat.missed.is_empty());
+    }
+
+    #[test]
+    fn jumping_outside_the_window_replaces_it() {
+        let conversation = messages(PAGE_SIZE * 8);
+        let mut chat = chat_at_the_end(&conversation);
+        let target = &conversation[PAGE_SIZE * 2];
+
+        let around =
+            conversation[PAGE_SIZE * 2 - PAGE_SIZE / 2..PAGE_SIZE * 2 + PAGE_SIZE / 2].to_vec();
+        chat.load_page(Page::Around, around.clone());
+        assert_eq!(chat.messages, around);
+        assert!(chat.messages.iter().any(|m| m.id() == target.id()));
+        assert!(chat.has_more(Page::Older));
+        assert!(chat.has_more(Page::Newer));
+
+        // Nothing found leaves the window where it was.
+        chat.load_page(Page::Around, vec![]);
+        assert_eq!(chat.messages, around);
+    }
+
+    #[test]
+    fn saved_chats_come_back_attached_to_the_conversation() {
+        let chat = Chat {
+            messages: messages(PAGE_SIZE * 2),
+            ..Chat::default()
+        };
+
+        let saved = serde_json::to_string(&chat).unwrap();
+        let mut loaded: Chat = serde_json::from_str(&saved).unwrap();
+        assert_eq!(loaded.messages, chat.messages[PAGE_SIZE..]);
+        assert!(!loaded.detached);
+        assert!(loaded.has_more(Page::Older));
+
+        let live = messages(1).remove(0);
+        loaded.push_message(live.clone());
+        assert_eq!(loaded.messages.last(), Some(&live));
+        assert_eq!(loaded.last_message(), Some(&live));
+    }
+}
@@ -0,0 +1,295 @@
+use std::sync::Arc;
+
+use fluent_templates::once_cell::sync::Lazy;
+use tokio::sync::{
+    mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
+    Mutex,
+};
+use warp::multipass::identity::Identity;
+
+use super::{Action, Chat, State};
+
+// How many undoable actions we keep around.
+const MAX_ENTRIES: usize = 100;
+
+/// Commands coming from outside the UI tree, such as the Edit menu.
+pub enum JournalCmd {
+    Undo,
+    Redo,
+}
+
+pub struct JournalChannels {
+    pub tx: UnboundedSender<JournalCmd>,
+    pub rx: Arc<Mutex<UnboundedReceiver<JournalCmd>>>,
+}
+
+pub static JOURNAL_CH: Lazy<JournalChannels> = Lazy::new(|| {
+    let (tx, rx) = unbounded_channel();
+    JournalChannels {
+        tx,
+        rx: Arc::new(Mutex::new(rx)),
+    }
+});
+
+/// An applied action along with the actions that revert it.
+#[derive(Clone, Debug)]
+pub struct Entry {
+    pub action: Action,
+    pub inverse: Vec<Action>,
+}
+
+/// History of undoable actions applied to `State`.
+#[derive(Default)]
+pub struct Journal {
+    undo: Vec<Entry>,
+    redo: Vec<Entry>,
+    // Whether the user should be offered to undo the most recent entry.
+    prompt: bool,
+}
+
+impl Journal {
+    /// Records a freshly dispatched action. Any redo history is discarded.
+    pub fn record(&mut self, entry: Entry) {
+        self.prompt = entry.action.is_destructive();
+        self.push_undo(entry);
+        self.redo.clear();
+    }
+
+    /// The entry the user should be offered to undo, if any.
+    pub fn prompt(&self) -> Option<&Entry> {
+        if self.prompt {
+            self.undo.last()
+        } else {
+            None
+        }
+    }
+
+    pub fn dismiss_prompt(&mut self) {
+        self.prompt = false;
+    }
+
+    fn push_undo(&mut self, entry: Entry) {
+        self.undo.push(entry);
+        if self.undo.len() > MAX_ENTRIES {
+            self.undo.remove(0);
+        }
+    }
+
+    pub(super) fn take_undo(&mut self) -> Option<Entry> {
+        self.prompt = false;
+        let entry = self.undo.pop()?;
+        self.redo.push(entry.clone());
+        Some(entry)
+    }
+
+    pub(super) fn take_redo(&mut self) -> Option<Action> {
+        self.prompt = false;
+        self.redo.pop().map(|entry| entry.action)
+    }
+
+    pub(super) fn record_redo(&mut self, entry: Entry) {
+        self.push_undo(entry);
+    }
+}
+
+/// Computes the actions that revert `action` given the state it's about to be applied to.
+/// Returns `None` for actions that can't or shouldn't be undone, and for actions that
+/// wouldn't change anything.
+pub(super) fn inverse(state: &State, action: &Action) -> Option<Vec<Action>> {
+    match action {
+        Action::Favorite(chat) if !state.is_favorite(chat) => {
+            Some(vec![Action::UnFavorite(chat.clone())])
+        }
+        Action::UnFavorite(chat) if state.is_favorite(chat) => {
+            Some(vec![Action::Favorite(chat.clone())])
+        }
+        Action::ToggleFavorite(chat) => Some(vec![Action::ToggleFavorite(chat.clone())]),
+        Action::SendRequest(request) if !has_outgoing_request(state, &request.identity) => {
+            Some(vec![Action::CancelRequest(request.identity.clone())])
+        }
+        Action::AddToSidebar(chat) if !state.chats.in_sidebar.contains(&chat.id) => {
+            Some(vec![Action::RemoveFromSidebar(chat.clone())])
+        }
+        Action::RemoveFromSidebar(chat) if state.chats.in_sidebar.contains(&chat.id) => {
+            let mut inverse = vec![Action::AddToSidebar(chat.clone())];
+            if state.chats.active == Some(chat.id) {
+                inverse.push(Action::ChatWith(chat.clone()));
+            }
+            Some(inverse)
+        }
+        Action::RemoveFriend(friend) if state.has_friend_with_did(&friend.did_key()) => {
+            Some(restore_friend(state, friend))
+        }
+        Action::Block(identity) if !state.friends.blocked.contains(identity) => {
+            let mut inverse = vec![Action::UnBlock(identity.clone())];
+            if state.has_friend_with_did(&identity.did_key()) {
+                inverse.extend(restore_friend(state, identity));
+            }
+            // Blocking drops their pending request, undoing it brings it back.
+            inverse.extend(
+                state
+                    .friends
+                    .incoming_requests
+                    .iter()
+                    .filter(|request| request.identity.did_key() == identity.did_key())
+                    .map(|request| Action::IncomingRequest(request.clone())),
+            );
+            Some(inverse)
+        }
+        Action::UnBlock(identity) if state.friends.blocked.contains(identity) => {
+            Some(vec![Action::Block(identity.clone())])
+        }
+        Action::SetNickname(did, nickname)
+            if state.friends.nicknames.get(did) != nickname.as_ref() =>
+        {
+            Some(vec![Action::SetNickname(
+                did.clone(),
+                state.friends.nicknames.get(did).cloned(),
+            )])
+        }
+        Action::AddMember(chat, identity) if !is_member(state, chat, identity) => {
+            Some(vec![Action::RemoveMember(chat.clone(), identity.clone())])
+        }
+        Action::RemoveMember(chat, identity) if is_member(state, chat, identity) => {
+            Some(vec![Action::AddMember(chat.clone(), identity.clone())])
+        }
+        _ => None,
+    }
+}
+
+/// Re-adds a friend along with the sidebar, favorite and active status of their direct chat.
+fn restore_friend(state: &State, friend: &Identity) -> Vec<Action> {
+    let mut inverse = vec![Action::AddFriend(friend.clone())];
+
+    let direct_chat: Option<&Chat> = state.chats.all.values().find(|chat| {
+        !chat.group
+            && chat.participants.len() == 2
+            && chat
+                .participants
+                .iter()
+                .any(|participant| participant.did_key() == friend.did_key())
+    });
+
+    if let Some(chat) = direct_chat {
+        if state.chats.in_sidebar.contains(&chat.id) {
+            inverse.push(Action::AddToSidebar(chat.clone()));
+        }
+        if state.is_favorite(chat) {
+            inverse.push(Action::Favorite(chat.clone()));
+        }
+        if state.chats.active == Some(chat.id) {
+            inverse.push(Action::ChatWith(chat.clone()));
+        }
+    }
+
+    inverse
+}
+
+fn has_outgoing_request(state: &State, identity: &Identity) -> bool {
+    state
+        .friends
+        .outgoing_requests
+        .iter()
+        .any(|request| request.identity.did_key() == identity.did_key())
+}
+
+fn is_member(state: &State, chat: &Chat, identity: &Identity) -> bool {
+    state.chats.all.get(&chat.id).map_or(false, |chat| {
+        chat.participants
+            .iter()
+            .any(|participant| participant.did_key() == identity.did_key())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::BTreeSet;
+
+    use uuid::Uuid;
+
+    use super::*;
+    use crate::state::FriendRequest;
+
+    // What the journal is expected to put back. The sidebar and favorites are compared as sets,
+    // undoing brings a chat back at their end.
+    #[derive(Debug, PartialEq)]
+    struct Snapshot {
+        friends: BTreeSet<String>,
+        outgoing_requests: Vec<FriendRequest>,
+        in_sidebar: BTreeSet<Uuid>,
+        favorites: BTreeSet<Uuid>,
+        active: Option<Uuid>,
+    }
+
+    fn snapshot(state: &State) -> Snapshot {
+        Snapshot {
+            friends: state
+                .friends
+                .all
+                .keys()
+                .map(|did| did.to_string())
+                .collect(),
+            outgoing_requests: state.friends.outgoing_requests.clone(),
+            in_sidebar: state.chats.in_sidebar.iter().cloned().collect(),
+            favorites: state.chats.favorites.iter().cloned().collect(),
+            active: state.chats.active,
+        }
+    }
+
+    // A friend whose direct chat is open, favorited and in the sidebar, next to another chat.
+    fn state_with_friend() -> (State, Identity, Chat) {
+        let me = Identity::default();
+        let friend = Identity::default();
+        let direct_chat = Chat {
+            id: Uuid::new_v4(),
+            participants: vec![me.clone(), friend.clone()],
+            ..Chat::default()
+        };
+        let other_chat = Chat {
+            id: Uuid::new_v4(),
+            participants: vec![me.clone(), Identity::default()],
+            ..Chat::default()
+        };
+
+        let mut state = State::default();
+        state.account.identity = me;
+        state.friends.all.insert(friend.did_key(), friend.clone());
+        for chat in [&direct_chat, &other_chat] {
+            state.chats.all.insert(chat.id, chat.clone());
+            state.chats.in_sidebar.push(chat.id);
+        }
+        state.chats.favorites.push(direct_chat.id);
+        state.chats.active = Some(direct_chat.id);
+        (state, friend, direct_chat)
+    }
+
+    fn assert_undone(mut state: State, action: Action) {
+        let before = snapshot(&state);
+        state.mutate(action);
+        assert_ne!(snapshot(&state), before, "the action changed nothing");
+        state.undo();
+        assert_eq!(snapshot(&state), before);
+    }
+
+    #[test]
+    fn sent_requests_are_undone() {
+        let (state, _, _) = state_with_friend();
+        let stranger = Identity::default();
+        assert_undone(
+            state,
+            Action::SendRequest(FriendRequest::new(stranger, None)),
+        );
+    }
+
+    #[test]
+    fn favorites_are_toggled_back() {
+        let (state, _, chat) = state_with_friend();
+        assert_undone(state, Action::ToggleFavorite(chat));
+    }
+
+    #[test]
+    fn removed_friends_come_back_with_their_chat() {
+        let (state, friend, _) = state_with_friend();
+        assert_undone(state, Action::RemoveFriend(friend));
+    }
+}
@@ -0,0 +1,329 @@
+use std::{
+    fmt, fs,
+    path::{Path, PathBuf},
+};
+
+use chrono::Utc;
+use serde_json::Value;
+
+use super::State;
+
+/// The `state.json` schema version this build reads and writes.
+pub const CURRENT_VERSION: u64 = 3;
+
+// Key the schema version is stored under at the root of the document.
+const VERSION_KEY: &str = "version";
+
+type Migration = fn(&mut Value) -> Result<(), String>;
+
+/// Ordered migrations. `MIGRATIONS[n]` upgrades a version `n` document to version `n + 1`,
+/// so adding a schema change means bumping `CURRENT_VERSION` and appending a migration.
+const MIGRATIONS: &[Migration] = &[v0_to_v1, v1_to_v2, v2_to_v3];
+
+#[derive(Debug)]
+pub enum MigrationError {
+    /// The file isn't valid JSON, or isn't a JSON object.
+    Malformed(String),
+    /// The file was written by a newer version of Uplink.
+    Unsupported(u64),
+    /// A migration step failed.
+    Failed { from: u64, reason: String },
+    /// The migrated document doesn't match `State`.
+    Invalid(String),
+}
+
+impl fmt::Display for MigrationError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MigrationError::Malformed(e) => write!(f, "state file is malformed: {}", e),
+            MigrationError::Unsupported(v) => {
+                write!(
+                    f,
+                    "state file version {} is newer than {}",
+                    v, CURRENT_VERSION
+                )
+            }
+            MigrationError::Failed { from, reason } => {
+                write!(f, "migration from version {} failed: {}", from, reason)
+            }
+            MigrationError::Invalid(e) => write!(f, "migrated state is invalid: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for MigrationError {}
+
+/// Parses the contents of a state file of any known version into the current `State`.
+pub fn parse(contents: &str) -> Result<State, MigrationError> {
+    let mut value: Value =
+        serde_json::from_str(contents).map_err(|e| MigrationError::Malformed(e.to_string()))?;
+
+    if !value.is_object() {
+        return Err(MigrationError::Malformed("expected an object".into()));
+    }
+
+    // Files written before versioning was introduced have no version key.
+    let mut version = value
+        .get(VERSION_KEY)
+        .and_then(Value::as_u64)
+        .unwrap_or_default();
+
+    if version > CURRENT_VERSION {
+        return Err(MigrationError::Unsupported(version));
+    }
+
+    while version < CURRENT_VERSION {
+        MIGRATIONS[version as usize](&mut value).map_err(|reason| MigrationError::Failed {
+            from: version,
+            reason,
+        })?;
+        version += 1;
+        value[VERSION_KEY] = version.into();
+    }
+
+    serde_json::from_value(value).map_err(|e| MigrationError::Invalid(e.to_string()))
+}
+
+/// Serializes the state, stamped with the current schema version.
+pub fn serialize(state: &State) -> Result<String, serde_json::Error> {
+    let mut value = serde_json::to_value(state)?;
+    value[VERSION_KEY] = CURRENT_VERSION.into();
+    serde_json::to_string(&value)
+}
+
+/// Copies a state file that couldn't be loaded next to the original, so that falling back
+/// to a fresh state doesn't lose the user's data.
+pub fn backup(path: &Path) -> std::io::Result<PathBuf> {
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let backup_path = path.with_file_name(format!("{}.{}.bak", file_name, Utc::now().timestamp()));
+    fs::copy(path, &backup_path)?;
+    Ok(backup_path)
+}
+
+/// Version 0 is the unversioned schema, version 1 only adds the version key itself.
+fn v0_to_v1(_: &mut Value) -> Result<(), String> {
+    Ok(())
+}
+
+/// Version 2 replaces the unread counter of each chat with a pointer to the last read message.
+/// The pointer is placed so that the same number of messages stays unread.
+fn v1_to_v2(value: &mut Value) -> Result<(), String> {
+    let chats = match value
+        .pointer_mut("/chats/all")
+        .and_then(Value::as_object_mut)
+    {
+        Some(chats) => chats,
+        None => return Ok(()),
+    };
+
+    for chat in chats.values_mut() {
+        let chat = chat
+            .as_object_mut()
+            .ok_or_else(|| "chat is not an object".to_string())?;
+        let unreads = chat
+            .remove("unreads")
+            .and_then(|unreads| unreads.as_u64())
+            .unwrap_or_default() as usize;
+
+        let messages = chat
+            .get("messages")
+            .and_then(Value::as_array)
+            .cloned()
+            .unwrap_or_default();
+        let last_read = messages
+            .len()
+            .checked_sub(unreads + 1)
+            .map(|i| &messages[i])
+            .and_then(|message| {
+                Some(serde_json::json!({
+                    "message_id": message.get("id")?,
+                    "date": message.get("date")?,
+                }))
+            })
+            .unwrap_or(Value::Null);
+
+        chat.insert("last_read".into(), last_read);
+    }
+
+    Ok(())
+}
+
+/// Version 3 stores friend requests with the time they were made and an optional note,
+/// instead of only the identity. Older requests are dated to the migration, since their
+/// real age is unknown.
+fn v2_to_v3(value: &mut Value) -> Result<(), String> {
+    let now = serde_json::to_value(Utc::now()).map_err(|e| e.to_string())?;
+
+    for pointer in ["/friends/incoming_requests", "/friends/outgoing_requests"] {
+        let requests = match value.pointer_mut(pointer).and_then(Value::as_array_mut) {
+            Some(requests) => requests,
+            None => continue,
+        };
+
+        for request in requests.iter_mut() {
+            let identity = request.take();
+            *request = serde_json::json!({
+                "identity": identity,
+                "date": now,
+                "note": null,
+            });
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+    use uuid::Uuid;
+    use warp::{multipass::identity::Identity, raygun::Message};
+
+    use super::*;
+    use crate::state::{Chat, FriendRequest};
+
+    // A current state with a chat two messages behind and a pending friend request, along
+    // with the chat's id.
+    fn current_state() -> (State, Uuid) {
+        let mut state = State::default();
+        let friend = Identity::default();
+        let mut chat = Chat {
+            id: Uuid::new_v4(),
+            participants: vec![state.account.identity.clone(), friend.clone()],
+            ..Chat::default()
+        };
+        for text in ["one", "two", "three"] {
+            let mut message = Message::default();
+            message.set_conversation_id(chat.id);
+            message.set_sender(friend.did_key());
+            message.set_value(vec![text.into()]);
+            chat.messages.push(message);
+        }
+        chat.last_read = chat.messages.first().map(Into::into);
+
+        let chat_id = chat.id;
+        state.chats.all.insert(chat_id, chat);
+        state
+            .friends
+            .outgoing_requests
+            .push(FriendRequest::new(friend, None));
+        (state, chat_id)
+    }
+
+    // Rewrites a current document the way a build writing `version` would have.
+    fn written_by(state: &State, version: u64) -> String {
+        let mut value: Value = serde_json::from_str(&serialize(state).unwrap()).unwrap();
+
+        if version < 3 {
+            let requests = value["friends"]["outgoing_requests"]
+                .as_array_mut()
+                .unwrap();
+            for request in requests.iter_mut() {
+                *request = request["identity"].take();
+            }
+        }
+        if version < 2 {
+            for chat in value["chats"]["all"].as_object_mut().unwrap().values_mut() {
+                let chat = chat.as_object_mut().unwrap();
+                chat.remove("last_read");
+                chat.insert("unreads".into(), json!(2));
+            }
+        }
+        if version < 1 {
+            value.as_object_mut().unwrap().remove(VERSION_KEY);
+        } else {
+            value[VERSION_KEY] = version.into();
+        }
+        value.to_string()
+    }
+
+    #[test]
+    fn older_documents_are_migrated() {
+        let (state, chat_id) = current_state();
+        let me = state.account.identity.did_key();
+        let chat = &state.chats.all[&chat_id];
+
+        for version in 0..CURRENT_VERSION {
+            let migrated = parse(&written_by(&state, version))
+                .unwrap_or_else(|e| panic!("version {}: {}", version, e));
+
+            let migrated_chat = &migrated.chats.all[&chat_id];
+            assert_eq!(migrated_chat.messages, chat.messages, "version {}", version);
+            assert_eq!(
+                migrated_chat.last_read, chat.last_read,
+                "version {}",
+                version
+            );
+            assert_eq!(migrated_chat.unreads(&me), 2, "version {}", version);
+
+            let requests = &migrated.friends.outgoing_requests;
+            assert_eq!(requests.len(), 1, "version {}", version);
+            assert_eq!(
+                requests[0].identity, state.friends.outgoing_requests[0].identity,
+                "version {}",
+                version
+            );
+        }
+    }
+
+    // `v1_to_v2` reads these keys straight out of the document, so a rename in warp would
+    // quietly leave every migrated chat unread.
+    #[test]
+    fn messages_keep_their_id_and_date_keys() {
+        let message = Message::default();
+        let value = serde_json::to_value(&message).unwrap();
+
+        assert_eq!(value["id"], json!(message.id()));
+        assert_eq!(value["date"], json!(message.date()));
+    }
+
+    #[test]
+    fn current_documents_are_read_as_they_are() {
+        let (state, chat_id) = current_state();
+        let parsed = parse(&serialize(&state).unwrap()).unwrap();
+
+        assert_eq!(
+            parsed.chats.all[&chat_id].last_read,
+            state.chats.all[&chat_id].last_read
+        );
+        assert_eq!(
+            parsed.friends.outgoing_requests[0].date,
+            state.friends.outgoing_requests[0].date
+        );
+    }
+
+    #[test]
+    fn newer_documents_are_backed_up_and_not_loaded() {
+        let dir = std::env::temp_dir().join(format!("uplink-migrations-{}", Uuid::new_v4()));
+        fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("state.json");
+        let mut newer = json!({});
+        newer[VERSION_KEY] = (CURRENT_VERSION + 1).into();
+        let contents = newer.to_string();
+        fs::write(&path, &contents).unwrap();
+
+        assert!(matches!(
+            parse(&contents),
+            Err(MigrationError::Unsupported(version)) if version == CURRENT_VERSION + 1
+        ));
+        let error = State::load_from(&path)
+            .err()
+            .expect("newer state was loaded");
+        assert_eq!(error.kind(), std::io::ErrorKind::InvalidData);
+
+        // The original stays where it was, with a copy next to it.
+        let backups: Vec<PathBuf> = fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .filter(|entry| *entry != path)
+            .collect();
+        assert_eq!(backups.len(), 1);
+        assert_eq!(fs::read_to_string(&backups[0]).unwrap(), contents);
+        assert_eq!(fs::read_to_string(&path).unwrap(), contents);
+
+        fs::remove_dir_all(&dir).unwrap();
+    }
+}
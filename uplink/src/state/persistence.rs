@@ -0,0 +1,117 @@
+use std::{
+    fs::{self, File},
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{
+        mpsc::{channel, Sender},
+        Arc,
+    },
+    thread,
+    time::Duration,
+};
+
+use fluent_templates::once_cell::sync::Lazy;
+use tokio::sync::{
+    mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
+    Mutex,
+};
+
+/// How often pending changes to `State` are written out. Every action dispatched within
+/// the interval ends up in a single save.
+pub const SAVE_INTERVAL: Duration = Duration::from_secs(1);
+
+// How long quitting waits for the final save before giving up.
+const FLUSH_TIMEOUT: Duration = Duration::from_secs(3);
+
+enum WriterCmd {
+    Write(PathBuf, String),
+    Flush(Sender<()>),
+}
+
+/// Commands for the UI thread, which is the only place the live `State` can be read from.
+pub enum PersistCmd {
+    /// Persist any pending changes right away, then acknowledge once they're on disk.
+    Flush(Sender<()>),
+}
+
+pub struct PersistChannels {
+    pub tx: UnboundedSender<PersistCmd>,
+    pub rx: Arc<Mutex<UnboundedReceiver<PersistCmd>>>,
+}
+
+pub static PERSIST_CH: Lazy<PersistChannels> = Lazy::new(|| {
+    let (tx, rx) = unbounded_channel();
+    PersistChannels {
+        tx,
+        rx: Arc::new(Mutex::new(rx)),
+    }
+});
+
+// The writer thread, started the first time something is saved.
+static WRITER: Lazy<std::sync::Mutex<Sender<WriterCmd>>> = Lazy::new(|| {
+    let (tx, rx) = channel();
+    thread::spawn(move || {
+        while let Ok(cmd) = rx.recv() {
+            match cmd {
+                WriterCmd::Write(path, contents) => {
+                    // TODO: surface failures to the user once we have a place for them.
+                    let _ = write_atomic(&path, contents.as_bytes());
+                }
+                WriterCmd::Flush(ack) => {
+                    let _ = ack.send(());
+                }
+            }
+        }
+    });
+    std::sync::Mutex::new(tx)
+});
+
+fn send(cmd: WriterCmd) {
+    if let Ok(tx) = WRITER.lock() {
+        let _ = tx.send(cmd);
+    }
+}
+
+/// Queues `contents` to be written to `path` on the writer thread.
+pub fn write(path: PathBuf, contents: String) {
+    send(WriterCmd::Write(path, contents));
+}
+
+/// Sends `ack` once every write queued so far has completed.
+pub fn flush(ack: Sender<()>) {
+    send(WriterCmd::Flush(ack));
+}
+
+/// Asks the UI to persist pending changes and blocks until they're written, or until
+/// `FLUSH_TIMEOUT` passes. Meant to be called from the event loop as the app quits.
+pub fn flush_blocking() {
+    let (ack_tx, ack_rx) = channel();
+    if PERSIST_CH.tx.send(PersistCmd::Flush(ack_tx)).is_ok() {
+        let _ = ack_rx.recv_timeout(FLUSH_TIMEOUT);
+    }
+}
+
+/// Blocks until every write queued so far has completed, or until `FLUSH_TIMEOUT` passes.
+/// Unlike `flush_blocking`, this doesn't go through the UI, so it can be called from it.
+pub fn wait_for_writer() {
+    let (ack_tx, ack_rx) = channel();
+    flush(ack_tx);
+    let _ = ack_rx.recv_timeout(FLUSH_TIMEOUT);
+}
+
+/// Writes to a temporary file next to `path` and renames it into place, so a crash
+/// mid-write leaves the previous file intact.
+pub fn write_atomic(path: &Path, contents: &[u8]) -> std::io::Result<()> {
+    let file_name = path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let tmp_path = path.with_file_name(format!("{}.tmp", file_name));
+
+    let mut file = File::create(&tmp_path)?;
+    file.write_all(contents)?;
+    file.sync_all()?;
+    drop(file);
+
+    fs::rename(&tmp_path, path)
+}
@@ -0,0 +1,56 @@
+use serde::{Deserialize, Serialize};
+use ui_kit::components::indicator::{Platform, Status};
+
+/// How available someone is, as last reported by MultiPass.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Availability {
+    Online,
+    Idle,
+    #[default]
+    Offline,
+    DoNotDisturb,
+}
+
+/// The kind of device someone is using.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub enum Device {
+    #[default]
+    Desktop,
+    Mobile,
+    Tv,
+    Headless,
+}
+
+/// Someone's availability along with the device they're on. Anyone we haven't heard about
+/// is considered offline.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+pub struct Presence {
+    pub status: Availability,
+    pub platform: Device,
+}
+
+impl Presence {
+    pub fn new(status: Availability, platform: Device) -> Self {
+        Presence { status, platform }
+    }
+
+    /// The status shown by the avatar indicator.
+    pub fn indicator_status(&self) -> Status {
+        match self.status {
+            Availability::Online => Status::Online,
+            Availability::Idle => Status::Idle,
+            Availability::Offline => Status::Offline,
+            Availability::DoNotDisturb => Status::DoNotDisturb,
+        }
+    }
+
+    /// The platform shown by the avatar indicator.
+    pub fn indicator_platform(&self) -> Platform {
+        match self.platform {
+            Device::Desktop => Platform::Desktop,
+            Device::Mobile => Platform::Mobile,
+            Device::Tv => Platform::Tv,
+            Device::Headless => Platform::Headless,
+        }
+    }
+}
@@ -1,5 +1,5 @@
 use std::{
-    collections::HashMap,
+    collections::{HashMap, HashSet},
     io::{BufWriter, Write},
 };
 
@@ -17,14 +17,17 @@ use warp::{
     raygun::Message,
 };
 
-use crate::state::{Account, Chat, Chats, Friends, Route, Settings, State, UI};
+use crate::state::{
+    journal::Journal, Account, Chat, Chats, FriendRequest, Friends, ReadMarker, Route, Settings,
+    State, UI,
+};
 
 pub fn generate_mock() -> State {
     let me = &generate_random_identities(1)[0];
     let identities = generate_random_identities(10);
     let blocked_identities = generate_random_identities(3);
-    let incoming_requests = generate_random_identities(2);
-    let outgoing_requests = generate_random_identities(1);
+    let incoming_requests = generate_random_requests(2);
+    let outgoing_requests = generate_random_requests(1);
 
     let mut all_chats: HashMap<Uuid, Chat> = HashMap::new();
 
@@ -33,25 +36,30 @@ pub fn generate_mock() -> State {
         all_chats.insert(chat.id, chat);
     }
 
-    // let group_chat = generate_random_chat(me.clone(), &identities);
-    // let group_chat_sidebar = group_chat.clone();
-
-    // all_chats.insert(group_chat.id, group_chat);
+    let mut group_chat = generate_random_chat(me.clone(), &identities);
+    group_chat.group = true;
+    let in_sidebar = vec![group_chat.id];
 
-    let in_sidebar = vec![];
-    // in_sidebar.push(group_chat_sidebar.id);
+    all_chats.insert(group_chat.id, group_chat);
 
     State {
         ui: UI {
             popout_player: false,
             silenced: false,
+            error: None,
+            profile: None,
             muted: false,
         },
         account: Account {
             identity: me.clone(),
+            ..Account::default()
         },
         settings: Settings {
             language: "en_US".into(),
+            group_gap_minutes: None,
+            plain_text: false,
+            link_previews: false,
+            request_expiry_days: None,
         },
         route: Route { active: "/".into() },
         chats: Chats {
@@ -68,8 +76,14 @@ pub fn generate_mock() -> State {
             blocked: blocked_identities.clone(),
             incoming_requests: incoming_requests.clone(),
             outgoing_requests: outgoing_requests.clone(),
+            presence: HashMap::new(),
+            nicknames: HashMap::new(),
         },
         hooks: Vec::new(),
+        journal: Journal::default(),
+        dirty: false,
+        key: None,
+        locked: false,
     }
 }
 
@@ -97,10 +111,22 @@ fn generate_fake_chat(participants: Vec<Identity>, conversation: Uuid) -> Chat {
     Chat {
         id: conversation,
         participants,
+        group: false,
+        name: None,
         messages,
+        // Mock chats only exist locally, there's no more history to load.
+        reached_start: true,
+        detached: false,
+        missed: vec![],
+        scroll_to: None,
         active_media: false,
-        unreads: rng.gen_range(0..2),
+        last_read: None,
+        first_unread: None,
+        edited: HashSet::new(),
+        deleted: HashSet::new(),
         replying_to: None,
+        draft: String::new(),
+        typing: HashMap::new(),
     }
 }
 
@@ -125,6 +151,14 @@ fn generate_random_chat(me: Identity, identities: &[Identity]) -> Chat {
         chat.messages.push(message);
     }
 
+    // Leave up to one message unread.
+    chat.last_read = chat
+        .messages
+        .iter()
+        .rev()
+        .nth(rng.gen_range(0..2))
+        .map(ReadMarker::from);
+
     chat
 }
 
@@ -139,10 +173,25 @@ fn fake_id() -> Identity {
     let status_msg = lipsum(status_len).to_string();
 
     id.set_username(&username);
+    id.set_short_id(rng.gen_range(0..10000));
     id.set_status_message(Some(status_msg));
     id
 }
 
+// Requests made within the last week, some of them with a note.
+fn generate_random_requests(count: usize) -> Vec<FriendRequest> {
+    let mut rng = rand::thread_rng();
+    generate_random_identities(count)
+        .into_iter()
+        .map(|identity| {
+            let note = rng.gen_bool(0.5).then(|| lipsum(rng.gen_range(3..10)));
+            let mut request = FriendRequest::new(identity, note);
+            request.date = Utc::now() - Duration::minutes(rng.gen_range(1..7 * 24 * 60));
+            request
+        })
+        .collect()
+}
+
 fn generate_random_identities(count: usize) -> Vec<Identity> {
     let mut identities: Vec<Identity> = Vec::new();
 
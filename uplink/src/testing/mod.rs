@@ -1 +1,7 @@
 pub mod mock;
+#[cfg(any(test, feature = "mock"))]
+pub mod multipass;
+#[cfg(test)]
+pub mod previews;
+#[cfg(any(test, feature = "mock"))]
+pub mod raygun;
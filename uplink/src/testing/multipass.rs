@@ -0,0 +1,196 @@
+use std::collections::{HashMap, HashSet};
+
+use futures::StreamExt;
+use warp::{
+    crypto::DID,
+    error::Error,
+    module::Module,
+    multipass::{
+        identity::{Identifier, Identity, IdentityStatus, IdentityUpdate, Platform},
+        Friends, FriendsEvent, IdentityInformation, MultiPass, MultiPassEvent, MultiPassEventKind,
+        MultiPassEventStream,
+    },
+    Extension, SingleHandle,
+};
+
+use crate::state::name_key;
+
+/// An in-process MultiPass that only knows about the identities it was given. Lets the
+/// warp runner look people up without a network or a real account. Everyone is offline
+/// unless given a presence with `set_presence`, which is announced once the runner subscribes.
+pub struct MockMultiPass {
+    me: Identity,
+    directory: Vec<Identity>,
+    friends: HashSet<DID>,
+    incoming_requests: HashSet<DID>,
+    outgoing_requests: HashSet<DID>,
+    presence: HashMap<DID, (IdentityStatus, Platform)>,
+}
+
+impl MockMultiPass {
+    pub fn new(me: Identity, directory: Vec<Identity>) -> Self {
+        MockMultiPass {
+            me,
+            directory,
+            friends: HashSet::new(),
+            incoming_requests: HashSet::new(),
+            outgoing_requests: HashSet::new(),
+            presence: HashMap::new(),
+        }
+    }
+
+    pub fn add_friend(&mut self, did: DID) {
+        self.friends.insert(did);
+    }
+
+    pub fn receive_request(&mut self, did: DID) {
+        self.incoming_requests.insert(did);
+    }
+
+    pub fn set_presence(&mut self, did: DID, status: IdentityStatus, platform: Platform) {
+        self.presence.insert(did, (status, platform));
+    }
+
+    fn everyone(&self) -> impl Iterator<Item = &Identity> {
+        std::iter::once(&self.me).chain(self.directory.iter())
+    }
+}
+
+impl Extension for MockMultiPass {
+    fn id(&self) -> String {
+        "mock-multipass".into()
+    }
+
+    fn name(&self) -> String {
+        "Mock MultiPass".into()
+    }
+
+    fn module(&self) -> Module {
+        Module::Accounts
+    }
+}
+
+impl SingleHandle for MockMultiPass {}
+
+#[async_trait::async_trait]
+impl MultiPass for MockMultiPass {
+    async fn create_identity(&mut self, _: Option<&str>, _: Option<&str>) -> Result<DID, Error> {
+        Err(Error::Unimplemented)
+    }
+
+    async fn get_identity(&self, id: Identifier) -> Result<Vec<Identity>, Error> {
+        let found = match id {
+            Identifier::DID(did) => self
+                .everyone()
+                .filter(|identity| identity.did_key() == did)
+                .cloned()
+                .collect(),
+            Identifier::DIDList(dids) => self
+                .everyone()
+                .filter(|identity| dids.contains(&identity.did_key()))
+                .cloned()
+                .collect(),
+            Identifier::Username(username) => self
+                .everyone()
+                .filter(|identity| name_key(&identity.username()) == name_key(&username))
+                .cloned()
+                .collect(),
+            Identifier::Own => vec![self.me.clone()],
+        };
+        Ok(found)
+    }
+
+    async fn update_identity(&mut self, _: IdentityUpdate) -> Result<(), Error> {
+        Err(Error::Unimplemented)
+    }
+
+    fn decrypt_private_key(&self, _: Option<&str>) -> Result<DID, Error> {
+        Err(Error::Unimplemented)
+    }
+
+    fn refresh_cache(&mut self) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+#[async_trait::async_trait]
+impl Friends for MockMultiPass {
+    async fn send_request(&mut self, pubkey: &DID) -> Result<(), Error> {
+        if !self
+            .everyone()
+            .any(|identity| identity.did_key() == *pubkey)
+        {
+            return Err(Error::IdentityDoesntExist);
+        }
+        self.outgoing_requests.insert(pubkey.clone());
+        Ok(())
+    }
+
+    async fn close_request(&mut self, pubkey: &DID) -> Result<(), Error> {
+        self.outgoing_requests.remove(pubkey);
+        Ok(())
+    }
+
+    async fn accept_request(&mut self, pubkey: &DID) -> Result<(), Error> {
+        if !self.incoming_requests.remove(pubkey) {
+            return Err(Error::CannotFindFriendRequest);
+        }
+        self.friends.insert(pubkey.clone());
+        Ok(())
+    }
+
+    async fn deny_request(&mut self, pubkey: &DID) -> Result<(), Error> {
+        if !self.incoming_requests.remove(pubkey) {
+            return Err(Error::CannotFindFriendRequest);
+        }
+        Ok(())
+    }
+
+    async fn list_friends(&self) -> Result<Vec<DID>, Error> {
+        Ok(self.friends.iter().cloned().collect())
+    }
+}
+
+impl FriendsEvent for MockMultiPass {}
+
+#[async_trait::async_trait]
+impl MultiPassEvent for MockMultiPass {
+    async fn subscribe(&mut self) -> Result<MultiPassEventStream, Error> {
+        let online: Vec<MultiPassEventKind> = self
+            .presence
+            .iter()
+            .filter(|(_, (status, _))| *status != IdentityStatus::Offline)
+            .map(|(did, _)| MultiPassEventKind::IdentityOnline { did: did.clone() })
+            .collect();
+        Ok(MultiPassEventStream(futures::stream::iter(online).boxed()))
+    }
+}
+
+#[async_trait::async_trait]
+impl IdentityInformation for MockMultiPass {
+    async fn identity_status(&self, did: &DID) -> Result<IdentityStatus, Error> {
+        if *did == self.me.did_key() {
+            return Ok(IdentityStatus::Online);
+        }
+        if !self.everyone().any(|identity| identity.did_key() == *did) {
+            return Err(Error::IdentityDoesntExist);
+        }
+        Ok(self
+            .presence
+            .get(did)
+            .map_or(IdentityStatus::Offline, |(status, _)| *status))
+    }
+
+    async fn identity_platform(&self, did: &DID) -> Result<Platform, Error> {
+        if *did == self.me.did_key() {
+            return Ok(Platform::Desktop);
+        }
+        if !self.everyone().any(|identity| identity.did_key() == *did) {
+            return Err(Error::IdentityDoesntExist);
+        }
+        Ok(self
+            .presence
+            .get(did)
+            .map_or(Platform::Unknown, |(_, platform)| *platform))
+    }
+}
@@ -0,0 +1,311 @@
+use std::collections::HashMap;
+
+use futures::{
+    channel::mpsc::{unbounded, UnboundedSender},
+    StreamExt,
+};
+use uuid::Uuid;
+use warp::{
+    crypto::DID,
+    error::Error,
+    module::Module,
+    raygun::{
+        Conversation, EmbedState, Message, MessageEventKind, MessageEventStream, MessageOptions,
+        PinState, RayGun, RayGunAttachment, RayGunEvents, RayGunGroupConversation, RayGunStream,
+        Reaction, ReactionState,
+    },
+    Extension, SingleHandle,
+};
+
+/// An in-process RayGun that keeps every conversation in memory and loops sent messages
+/// straight back through the conversation streams. Lets the warp runner be exercised
+/// without a network or a real account.
+pub struct MockRayGun {
+    did: DID,
+    conversations: HashMap<Uuid, Conversation>,
+    messages: HashMap<Uuid, Vec<Message>>,
+    listeners: HashMap<Uuid, Vec<UnboundedSender<MessageEventKind>>>,
+}
+
+impl MockRayGun {
+    pub fn new(did: DID) -> Self {
+        MockRayGun {
+            did,
+            conversations: HashMap::new(),
+            messages: HashMap::new(),
+            listeners: HashMap::new(),
+        }
+    }
+
+    fn emit(&mut self, conversation_id: Uuid, event: MessageEventKind) {
+        if let Some(listeners) = self.listeners.get_mut(&conversation_id) {
+            listeners.retain(|tx| tx.unbounded_send(event.clone()).is_ok());
+        }
+    }
+
+    fn find_message(
+        &mut self,
+        conversation_id: Uuid,
+        message_id: Uuid,
+    ) -> Result<&mut Message, Error> {
+        self.messages
+            .get_mut(&conversation_id)
+            .and_then(|messages| messages.iter_mut().find(|m| m.id() == message_id))
+            .ok_or(Error::InvalidMessage)
+    }
+
+    fn push_message(&mut self, conversation_id: Uuid, value: Vec<String>, replied: Option<Uuid>) {
+        let mut message = Message::default();
+        message.set_conversation_id(conversation_id);
+        message.set_sender(self.did.clone());
+        message.set_reactions(vec![]);
+        message.set_replied(replied);
+        message.set_value(value);
+
+        let message_id = message.id();
+        self.messages
+            .entry(conversation_id)
+            .or_insert_with(Vec::new)
+            .push(message);
+
+        self.emit(
+            conversation_id,
+            MessageEventKind::MessageSent {
+                conversation_id,
+                message_id,
+            },
+        );
+    }
+}
+
+impl Extension for MockRayGun {
+    fn id(&self) -> String {
+        "mock-raygun".into()
+    }
+
+    fn name(&self) -> String {
+        "Mock RayGun".into()
+    }
+
+    fn module(&self) -> Module {
+        Module::Messaging
+    }
+}
+
+impl SingleHandle for MockRayGun {}
+
+#[async_trait::async_trait]
+impl RayGun for MockRayGun {
+    async fn create_conversation(&mut self, did_key: &DID) -> Result<Conversation, Error> {
+        let mut conversation = Conversation::default();
+        conversation.set_recipients(vec![self.did.clone(), did_key.clone()]);
+        self.conversations
+            .insert(conversation.id(), conversation.clone());
+        Ok(conversation)
+    }
+
+    async fn create_group_conversation(
+        &mut self,
+        recipients: Vec<DID>,
+    ) -> Result<Conversation, Error> {
+        let mut conversation = Conversation::default();
+        let mut members = vec![self.did.clone()];
+        members.extend(recipients.into_iter().filter(|did| *did != self.did));
+        conversation.set_recipients(members);
+        self.conversations
+            .insert(conversation.id(), conversation.clone());
+        Ok(conversation)
+    }
+
+    async fn list_conversations(&self) -> Result<Vec<Conversation>, Error> {
+        Ok(self.conversations.values().cloned().collect())
+    }
+
+    async fn get_message(&self, conversation_id: Uuid, message_id: Uuid) -> Result<Message, Error> {
+        self.messages
+            .get(&conversation_id)
+            .and_then(|messages| messages.iter().find(|m| m.id() == message_id))
+            .cloned()
+            .ok_or(Error::InvalidMessage)
+    }
+
+    async fn get_messages(
+        &self,
+        conversation_id: Uuid,
+        _: MessageOptions,
+    ) -> Result<Vec<Message>, Error> {
+        Ok(self
+            .messages
+            .get(&conversation_id)
+            .cloned()
+            .unwrap_or_default())
+    }
+
+    async fn send(
+        &mut self,
+        conversation_id: Uuid,
+        message_id: Option<Uuid>,
+        message: Vec<String>,
+    ) -> Result<(), Error> {
+        match message_id {
+            Some(message_id) => {
+                self.find_message(conversation_id, message_id)?
+                    .set_value(message);
+                self.emit(
+                    conversation_id,
+                    MessageEventKind::MessageEdited {
+                        conversation_id,
+                        message_id,
+                    },
+                );
+            }
+            None => self.push_message(conversation_id, message, None),
+        }
+        Ok(())
+    }
+
+    async fn delete(
+        &mut self,
+        conversation_id: Uuid,
+        message_id: Option<Uuid>,
+    ) -> Result<(), Error> {
+        match message_id {
+            Some(message_id) => {
+                if let Some(messages) = self.messages.get_mut(&conversation_id) {
+                    messages.retain(|m| m.id() != message_id);
+                }
+                self.emit(
+                    conversation_id,
+                    MessageEventKind::MessageDeleted {
+                        conversation_id,
+                        message_id,
+                    },
+                );
+            }
+            None => {
+                self.conversations.remove(&conversation_id);
+                self.messages.remove(&conversation_id);
+                self.listeners.remove(&conversation_id);
+            }
+        }
+        Ok(())
+    }
+
+    async fn react(
+        &mut self,
+        conversation_id: Uuid,
+        message_id: Uuid,
+        state: ReactionState,
+        emoji: String,
+    ) -> Result<(), Error> {
+        let did = self.did.clone();
+        let message = self.find_message(conversation_id, message_id)?;
+        let mut reactions = message.reactions();
+
+        match reactions.iter_mut().find(|r| r.emoji() == emoji) {
+            Some(reaction) => {
+                let mut users = reaction.users();
+                users.retain(|user| *user != did);
+                if let ReactionState::Add = state {
+                    users.push(did.clone());
+                }
+                reaction.set_users(users);
+            }
+            None => {
+                if let ReactionState::Add = state {
+                    let mut reaction = Reaction::default();
+                    reaction.set_emoji(&emoji);
+                    reaction.set_users(vec![did.clone()]);
+                    reactions.push(reaction);
+                }
+            }
+        }
+        reactions.retain(|r| !r.users().is_empty());
+        message.set_reactions(reactions);
+
+        let event = match state {
+            ReactionState::Add => MessageEventKind::MessageReactionAdded {
+                conversation_id,
+                message_id,
+                did_key: did,
+                reaction: emoji,
+            },
+            ReactionState::Remove => MessageEventKind::MessageReactionRemoved {
+                conversation_id,
+                message_id,
+                did_key: did,
+                reaction: emoji,
+            },
+        };
+        self.emit(conversation_id, event);
+        Ok(())
+    }
+
+    async fn pin(&mut self, _: Uuid, _: Uuid, _: PinState) -> Result<(), Error> {
+        Err(Error::Unimplemented)
+    }
+
+    async fn reply(
+        &mut self,
+        conversation_id: Uuid,
+        message_id: Uuid,
+        message: Vec<String>,
+    ) -> Result<(), Error> {
+        self.push_message(conversation_id, message, Some(message_id));
+        Ok(())
+    }
+
+    async fn embeds(&mut self, _: Uuid, _: Uuid, _: EmbedState) -> Result<(), Error> {
+        Err(Error::Unimplemented)
+    }
+}
+
+#[async_trait::async_trait]
+impl RayGunStream for MockRayGun {
+    async fn get_conversation_stream(
+        &mut self,
+        conversation_id: Uuid,
+    ) -> Result<MessageEventStream, Error> {
+        let (tx, rx) = unbounded();
+        self.listeners
+            .entry(conversation_id)
+            .or_insert_with(Vec::new)
+            .push(tx);
+        Ok(MessageEventStream(rx.boxed()))
+    }
+}
+
+impl RayGunAttachment for MockRayGun {}
+
+impl RayGunEvents for MockRayGun {}
+
+#[async_trait::async_trait]
+impl RayGunGroupConversation for MockRayGun {
+    async fn add_recipient(&mut self, conversation_id: Uuid, did_key: &DID) -> Result<(), Error> {
+        let conversation = self
+            .conversations
+            .get_mut(&conversation_id)
+            .ok_or(Error::InvalidConversation)?;
+        let mut recipients = conversation.recipients();
+        if !recipients.contains(did_key) {
+            recipients.push(did_key.clone());
+            conversation.set_recipients(recipients);
+        }
+        Ok(())
+    }
+
+    async fn remove_recipient(
+        &mut self,
+        conversation_id: Uuid,
+        did_key: &DID,
+    ) -> Result<(), Error> {
+        let conversation = self
+            .conversations
+            .get_mut(&conversation_id)
+            .ok_or(Error::InvalidConversation)?;
+        let mut recipients = conversation.recipients();
+        recipients.retain(|did| did != did_key);
+        conversation.set_recipients(recipients);
+        Ok(())
+    }
+}
@@ -1,5 +1,6 @@
 use std::collections::HashMap;
 
+use chrono::Locale;
 use fluent_templates::{LanguageIdentifier, once_cell::sync::Lazy};
 use unic_langid::langid;
 use warp::sync::RwLock;
@@ -29,6 +30,15 @@ pub fn change_language(new_language: String) -> String {
     }
 }
 
+/// The locale dates are written in, following the app's language.
+pub fn date_locale() -> Locale {
+    if *APP_LANG.read() == BR_PORTUGUESE {
+        Locale::pt_BR
+    } else {
+        Locale::en_US
+    }
+}
+
 pub fn get_available_languages() -> Vec<String> {
     vec![US_ENGLISH.to_string(), BR_PORTUGUESE.to_string()]
 }
\ No newline at end of file
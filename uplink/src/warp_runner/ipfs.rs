@@ -0,0 +1,57 @@
+use std::{path::Path, sync::Arc};
+
+use warp::{
+    multipass::{identity::Identifier, MultiPass},
+    raygun::RayGun,
+    sync::RwLock,
+    tesseract::Tesseract,
+};
+use warp_mp_ipfs::config::MpIpfsConfig;
+use warp_rg_ipfs::{config::RgIpfsConfig, IpfsMessaging, Persistent};
+
+// Everything warp keeps lives in its own directory within the profile's data directory.
+const WARP_DIR: &str = "warp";
+
+const KEYSTORE_FILE: &str = "keystore";
+
+pub type StartError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Starts MultiPass and RayGun on IPFS, keeping their keys and data in `data_dir`. The
+/// keystore is unlocked with `passphrase`. An identity is created the first time around.
+pub async fn start(
+    data_dir: &Path,
+    passphrase: &str,
+) -> Result<(Box<dyn RayGun>, Box<dyn MultiPass>), StartError> {
+    let path = data_dir.join(WARP_DIR);
+    std::fs::create_dir_all(&path)?;
+
+    let keystore = path.join(KEYSTORE_FILE);
+    let tesseract = Tesseract::from_file(&keystore).unwrap_or_default();
+    tesseract.set_file(&keystore);
+    tesseract.set_autosave();
+    tesseract.unlock(passphrase.as_bytes())?;
+
+    let mut multipass =
+        warp_mp_ipfs::ipfs_identity_persistent(MpIpfsConfig::production(&path), tesseract, None)
+            .await?;
+    let has_identity = multipass
+        .get_identity(Identifier::Own)
+        .await
+        .map_or(false, |identities| !identities.is_empty());
+    if !has_identity {
+        multipass.create_identity(None, None).await?;
+    }
+
+    // RayGun looks participants up through MultiPass, so it gets a handle to the same node.
+    let account: Arc<RwLock<Box<dyn MultiPass>>> =
+        Arc::new(RwLock::new(Box::new(multipass.clone())));
+    let raygun = IpfsMessaging::<Persistent>::new(
+        Some(RgIpfsConfig::production(&path)),
+        account,
+        None,
+        None,
+    )
+    .await?;
+
+    Ok((Box::new(raygun), Box::new(multipass)))
+}
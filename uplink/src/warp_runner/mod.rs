@@ -0,0 +1,1339 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::PathBuf,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use fluent_templates::once_cell::sync::Lazy;
+use futures::{
+    stream::{BoxStream, SelectAll},
+    StreamExt,
+};
+use tokio::sync::{
+    mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender},
+    Mutex,
+};
+use uuid::Uuid;
+use warp::{
+    crypto::DID,
+    multipass::{
+        identity::{Identifier, Identity, IdentityStatus, Platform},
+        IdentityInformation, MultiPass, MultiPassEvent, MultiPassEventKind,
+    },
+    raygun::{
+        Message, MessageEvent, MessageEventKind, MessageOptions, RayGun, Reaction, ReactionState,
+    },
+};
+use zeroize::Zeroizing;
+
+use crate::state::{
+    friend_query::FriendQuery,
+    history::{Page, PAGE_SIZE},
+    presence::{Availability, Device, Presence},
+    Action, ActionHook, Chat, FriendRequest, State,
+};
+
+mod ipfs;
+
+// Peers stop showing us as typing after `TYPING_TIMEOUT_SECS`, so while we keep typing the
+// event is sent again well before that.
+const TYPING_RESEND: Duration = Duration::from_secs(3);
+
+/// Commands the UI sends to the warp runner. Each one maps onto a single RayGun or
+/// MultiPass call.
+#[derive(Debug)]
+pub enum WarpCmd {
+    /// Sends a new message to the given conversation.
+    Send {
+        conversation_id: Uuid,
+        message: Vec<String>,
+    },
+    /// Replies to `message_id` within the given conversation.
+    Reply {
+        conversation_id: Uuid,
+        message_id: Uuid,
+        message: Vec<String>,
+    },
+    /// Replaces the text of one of our messages.
+    Edit {
+        conversation_id: Uuid,
+        message_id: Uuid,
+        message: Vec<String>,
+    },
+    /// Deletes one of our messages.
+    Delete {
+        conversation_id: Uuid,
+        message_id: Uuid,
+    },
+    /// Adds or removes a reaction on a message.
+    React {
+        conversation_id: Uuid,
+        message_id: Uuid,
+        state: ReactionState,
+        emoji: String,
+    },
+    /// Creates a group conversation with the given recipients.
+    CreateGroup {
+        recipients: Vec<DID>,
+        name: Option<String>,
+    },
+    /// Adds a recipient to a group conversation.
+    AddRecipient { conversation_id: Uuid, did_key: DID },
+    /// Removes a recipient from a group conversation.
+    RemoveRecipient { conversation_id: Uuid, did_key: DID },
+    /// Lets the other participants know we're typing.
+    Typing { conversation_id: Uuid },
+    /// Lets the other participants know we stopped typing.
+    StopTyping { conversation_id: Uuid },
+    /// Loads a page of history next to `anchor`, or the latest page if there's no anchor.
+    FetchMessages {
+        conversation_id: Uuid,
+        anchor: Option<Uuid>,
+        page: Page,
+    },
+    /// Looks up the identity a friend request is meant for. The note is handed back along
+    /// with it.
+    FindIdentity {
+        query: FriendQuery,
+        note: Option<String>,
+    },
+    /// Sends a friend request to the given identity.
+    SendFriendRequest(DID),
+    /// Takes back the friend request we sent to the given identity.
+    CancelFriendRequest(DID),
+    /// Accepts the friend request the given identity sent us.
+    AcceptFriendRequest(DID),
+    /// Turns down the friend request the given identity sent us.
+    DenyFriendRequest(DID),
+    /// Asks for someone's presence, e.g. when they just became a friend.
+    FetchPresence(DID),
+    /// Stops the runner, e.g. when switching to another profile.
+    Shutdown,
+}
+
+/// Events the warp runner hands back to the UI, to be turned into `Action`s.
+#[derive(Debug)]
+pub enum WarpEvent {
+    /// MultiPass is up and this is our identity.
+    AccountReady(Identity),
+    /// Something we asked for failed, the locale key of the error to show is attached.
+    Failed(&'static str),
+    /// A message was sent by us or received from a peer.
+    NewMessage(Message),
+    /// A page of history was loaded for a conversation.
+    MessagesLoaded {
+        conversation_id: Uuid,
+        page: Page,
+        messages: Vec<Message>,
+    },
+    /// A message was edited, by us or by a peer.
+    MessageEdited(Message),
+    /// A message was deleted, by us or by a peer.
+    MessageDeleted {
+        conversation_id: Uuid,
+        message_id: Uuid,
+    },
+    /// Someone added or removed a reaction, `reactions` are all of the message's reactions.
+    ReactionsUpdated {
+        conversation_id: Uuid,
+        message_id: Uuid,
+        reactions: Vec<Reaction>,
+    },
+    /// A group conversation we asked for was created. RayGun doesn't know about names, so the
+    /// one picked by the user is handed back along with it. The name is never sent to anyone,
+    /// other members see the group named after its participants.
+    GroupCreated {
+        conversation_id: Uuid,
+        recipients: Vec<DID>,
+        name: Option<String>,
+    },
+    /// The identity looked up for a friend request was found.
+    IdentityFound {
+        identity: Identity,
+        note: Option<String>,
+    },
+    /// Nobody matches the identity looked up for a friend request.
+    IdentityNotFound,
+    /// Looking up an identity failed.
+    LookupFailed,
+    /// A participant started typing, or is still typing.
+    TypingStarted { conversation_id: Uuid, did_key: DID },
+    /// Someone's availability or device changed.
+    PresenceChanged { did_key: DID, presence: Presence },
+    /// Someone sent us a friend request.
+    RequestReceived(Identity),
+    /// The friend request we sent to someone didn't go through.
+    RequestFailed(DID),
+    /// Adding someone to a group, or removing them from it, didn't go through.
+    MemberChangeFailed {
+        conversation_id: Uuid,
+        did_key: DID,
+        added: bool,
+    },
+    /// A participant stopped typing.
+    TypingStopped { conversation_id: Uuid, did_key: DID },
+}
+
+pub struct WarpCmdChannels {
+    pub tx: UnboundedSender<WarpCmd>,
+    pub rx: Arc<Mutex<UnboundedReceiver<WarpCmd>>>,
+}
+
+pub struct WarpEventChannels {
+    pub tx: UnboundedSender<WarpEvent>,
+    pub rx: Arc<Mutex<UnboundedReceiver<WarpEvent>>>,
+}
+
+pub static WARP_CMD_CH: Lazy<WarpCmdChannels> = Lazy::new(|| {
+    let (tx, rx) = unbounded_channel();
+    WarpCmdChannels {
+        tx,
+        rx: Arc::new(Mutex::new(rx)),
+    }
+});
+
+pub static WARP_EVENT_CH: Lazy<WarpEventChannels> = Lazy::new(|| {
+    let (tx, rx) = unbounded_channel();
+    WarpEventChannels {
+        tx,
+        rx: Arc::new(Mutex::new(rx)),
+    }
+});
+
+impl WarpEvent {
+    /// Who the event comes from, for events caused by someone else.
+    fn sender(&self) -> Option<DID> {
+        match self {
+            WarpEvent::NewMessage(message) | WarpEvent::MessageEdited(message) => {
+                Some(message.sender())
+            }
+            WarpEvent::TypingStarted { did_key, .. }
+            | WarpEvent::TypingStopped { did_key, .. }
+            | WarpEvent::PresenceChanged { did_key, .. } => Some(did_key.clone()),
+            WarpEvent::RequestReceived(identity) => Some(identity.did_key()),
+            _ => None,
+        }
+    }
+}
+
+/// Owns the RayGun and MultiPass instances on a dedicated thread. Commands are read from
+/// `WARP_CMD_CH` and anything they report back is forwarded to `WARP_EVENT_CH`.
+pub struct WarpRunner {
+    raygun: Box<dyn RayGun>,
+    multipass: Box<dyn MultiPass>,
+    events: UnboundedSender<WarpEvent>,
+    streams: SelectAll<BoxStream<'static, MessageEventKind>>,
+    subscribed: HashSet<Uuid>,
+    // Friend and presence updates from MultiPass, pending until we've subscribed.
+    identity_events: BoxStream<'static, MultiPassEventKind>,
+    // When we last told each conversation we're typing.
+    typing: HashMap<Uuid, Instant>,
+}
+
+impl WarpRunner {
+    pub fn new(raygun: Box<dyn RayGun>, multipass: Box<dyn MultiPass>) -> Self {
+        WarpRunner {
+            raygun,
+            multipass,
+            events: WARP_EVENT_CH.tx.clone(),
+            streams: SelectAll::new(),
+            subscribed: HashSet::new(),
+            identity_events: futures::stream::pending().boxed(),
+            typing: HashMap::new(),
+        }
+    }
+
+    /// Sends events somewhere else than `WARP_EVENT_CH`.
+    #[cfg(test)]
+    fn with_events(mut self, events: UnboundedSender<WarpEvent>) -> Self {
+        self.events = events;
+        self
+    }
+
+    /// Starts MultiPass and RayGun for the profile whose data lives in `data_dir`, then runs
+    /// them on their own thread and tokio runtime.
+    pub fn start(data_dir: PathBuf, passphrase: Zeroizing<String>) {
+        spawn(async move {
+            match ipfs::start(&data_dir, &passphrase).await {
+                Ok((raygun, multipass)) => WarpRunner::new(raygun, multipass).event_loop().await,
+                Err(_) => {
+                    let _ = WARP_EVENT_CH
+                        .tx
+                        .send(WarpEvent::Failed("uplink.messaging-failed"));
+                }
+            }
+        });
+    }
+
+    /// Spawns the runner on its own thread and tokio runtime.
+    #[cfg(feature = "mock")]
+    pub fn run(self) {
+        spawn(self.event_loop());
+    }
+
+    async fn event_loop(mut self) {
+        let mut cmd_rx = WARP_CMD_CH.rx.lock().await;
+
+        match self.multipass.get_identity(Identifier::Own).await {
+            Ok(identities) => {
+                if let Some(identity) = identities.into_iter().next() {
+                    let _ = self.events.send(WarpEvent::AccountReady(identity));
+                }
+            }
+            Err(_) => {
+                let _ = self
+                    .events
+                    .send(WarpEvent::Failed("uplink.messaging-failed"));
+            }
+        }
+
+        if let Ok(conversations) = self.raygun.list_conversations().await {
+            for conversation in conversations {
+                self.subscribe(conversation.id()).await;
+            }
+        }
+
+        if let Ok(stream) = self.multipass.subscribe().await {
+            self.identity_events = stream.0;
+        }
+        self.report_friends_presence().await;
+
+        loop {
+            tokio::select! {
+                cmd = cmd_rx.recv() => match cmd {
+                    Some(WarpCmd::Shutdown) => break,
+                    Some(cmd) => self.handle_cmd(cmd).await,
+                    // The UI went away, nothing left to do.
+                    None => break,
+                },
+                Some(event) = self.streams.next() => self.handle_event(event).await,
+                Some(event) = self.identity_events.next() => self.handle_identity_event(event).await,
+            }
+        }
+    }
+
+    /// Starts listening to a conversation's message stream, if we aren't already.
+    async fn subscribe(&mut self, conversation_id: Uuid) {
+        if self.subscribed.contains(&conversation_id) {
+            return;
+        }
+
+        if let Ok(stream) = self.raygun.get_conversation_stream(conversation_id).await {
+            self.streams.push(stream.0);
+            self.subscribed.insert(conversation_id);
+        }
+    }
+
+    /// Reports the presence of every friend, so they don't all show as offline until their
+    /// status changes.
+    async fn report_friends_presence(&mut self) {
+        if let Ok(friends) = self.multipass.list_friends().await {
+            for did_key in friends {
+                self.report_presence(did_key).await;
+            }
+        }
+    }
+
+    /// Reports incoming friend requests, and the presence of whoever came online, went
+    /// offline or changed their status.
+    async fn handle_identity_event(&mut self, event: MultiPassEventKind) {
+        let did_key = match event {
+            MultiPassEventKind::FriendRequestReceived { from } => {
+                let identity = self
+                    .multipass
+                    .get_identity(Identifier::DID(from))
+                    .await
+                    .ok()
+                    .and_then(|identities| identities.into_iter().next());
+                if let Some(identity) = identity {
+                    let _ = self.events.send(WarpEvent::RequestReceived(identity));
+                }
+                return;
+            }
+            MultiPassEventKind::IdentityOnline { did } => did,
+            MultiPassEventKind::IdentityOffline { did } => did,
+            MultiPassEventKind::IdentityUpdate { did } => did,
+            _ => return,
+        };
+        self.report_presence(did_key).await;
+    }
+
+    /// Reports someone's current availability and device. Anyone MultiPass can't tell us
+    /// about is offline.
+    async fn report_presence(&mut self, did_key: DID) {
+        let status = self
+            .multipass
+            .identity_status(&did_key)
+            .await
+            .unwrap_or(IdentityStatus::Offline);
+        let platform = self
+            .multipass
+            .identity_platform(&did_key)
+            .await
+            .unwrap_or(Platform::Unknown);
+        let presence = Presence::new(to_availability(status), to_device(platform));
+        let _ = self
+            .events
+            .send(WarpEvent::PresenceChanged { did_key, presence });
+    }
+
+    /// Tells the conversation we stopped typing, if we told it we were.
+    async fn stop_typing(&mut self, conversation_id: Uuid) -> Result<(), warp::error::Error> {
+        if self.typing.remove(&conversation_id).is_none() {
+            return Ok(());
+        }
+        self.raygun
+            .cancel_event(conversation_id, MessageEvent::Typing)
+            .await
+    }
+
+    async fn handle_cmd(&mut self, cmd: WarpCmd) {
+        let failed = failure(&cmd);
+        let result = match cmd {
+            WarpCmd::Send {
+                conversation_id,
+                message,
+            } => {
+                self.subscribe(conversation_id).await;
+                let _ = self.stop_typing(conversation_id).await;
+                self.raygun.send(conversation_id, None, message).await
+            }
+            WarpCmd::Reply {
+                conversation_id,
+                message_id,
+                message,
+            } => {
+                self.subscribe(conversation_id).await;
+                let _ = self.stop_typing(conversation_id).await;
+                self.raygun
+                    .reply(conversation_id, message_id, message)
+                    .await
+            }
+            WarpCmd::Edit {
+                conversation_id,
+                message_id,
+                message,
+            } => {
+                self.subscribe(conversation_id).await;
+                self.raygun
+                    .send(conversation_id, Some(message_id), message)
+                    .await
+            }
+            WarpCmd::Delete {
+                conversation_id,
+                message_id,
+            } => {
+                self.subscribe(conversation_id).await;
+                self.raygun.delete(conversation_id, Some(message_id)).await
+            }
+            WarpCmd::React {
+                conversation_id,
+                message_id,
+                state,
+                emoji,
+            } => {
+                self.subscribe(conversation_id).await;
+                self.raygun
+                    .react(conversation_id, message_id, state, emoji)
+                    .await
+            }
+            WarpCmd::CreateGroup { recipients, name } => {
+                match self.raygun.create_group_conversation(recipients).await {
+                    Ok(conversation) => {
+                        self.subscribe(conversation.id()).await;
+                        let _ = self.events.send(WarpEvent::GroupCreated {
+                            conversation_id: conversation.id(),
+                            recipients: conversation.recipients(),
+                            name,
+                        });
+                        Ok(())
+                    }
+                    Err(e) => Err(e),
+                }
+            }
+            WarpCmd::AddRecipient {
+                conversation_id,
+                did_key,
+            } => {
+                if self
+                    .raygun
+                    .add_recipient(conversation_id, &did_key)
+                    .await
+                    .is_err()
+                {
+                    let _ = self.events.send(WarpEvent::MemberChangeFailed {
+                        conversation_id,
+                        did_key,
+                        added: true,
+                    });
+                }
+                Ok(())
+            }
+            WarpCmd::RemoveRecipient {
+                conversation_id,
+                did_key,
+            } => {
+                if self
+                    .raygun
+                    .remove_recipient(conversation_id, &did_key)
+                    .await
+                    .is_err()
+                {
+                    let _ = self.events.send(WarpEvent::MemberChangeFailed {
+                        conversation_id,
+                        did_key,
+                        added: false,
+                    });
+                }
+                Ok(())
+            }
+            WarpCmd::Typing { conversation_id } => {
+                let recently_sent = self
+                    .typing
+                    .get(&conversation_id)
+                    .map_or(false, |sent| sent.elapsed() < TYPING_RESEND);
+                if recently_sent {
+                    return;
+                }
+                self.subscribe(conversation_id).await;
+                self.typing.insert(conversation_id, Instant::now());
+                self.raygun
+                    .send_event(conversation_id, MessageEvent::Typing)
+                    .await
+            }
+            WarpCmd::StopTyping { conversation_id } => self.stop_typing(conversation_id).await,
+            WarpCmd::FetchMessages {
+                conversation_id,
+                anchor,
+                page,
+            } => {
+                let messages = self.fetch_page(conversation_id, anchor, page).await;
+                let _ = self.events.send(WarpEvent::MessagesLoaded {
+                    conversation_id,
+                    page,
+                    messages,
+                });
+                Ok(())
+            }
+            WarpCmd::FindIdentity { query, note } => {
+                let identifier = match &query {
+                    FriendQuery::Did(did) => Identifier::DID(did.clone()),
+                    FriendQuery::Username { username, .. } => {
+                        Identifier::Username(username.clone())
+                    }
+                };
+                let event = match self.multipass.get_identity(identifier).await {
+                    Ok(identities) => match identities.into_iter().find(|i| query.matches(i)) {
+                        Some(identity) => WarpEvent::IdentityFound { identity, note },
+                        None => WarpEvent::IdentityNotFound,
+                    },
+                    Err(_) => WarpEvent::LookupFailed,
+                };
+                let _ = self.events.send(event);
+                Ok(())
+            }
+            WarpCmd::SendFriendRequest(did) => {
+                if self.multipass.send_request(&did).await.is_err() {
+                    let _ = self.events.send(WarpEvent::RequestFailed(did));
+                }
+                Ok(())
+            }
+            WarpCmd::CancelFriendRequest(did) => self.multipass.close_request(&did).await,
+            // New friends are shown as offline until MultiPass tells us otherwise.
+            WarpCmd::AcceptFriendRequest(did) => {
+                let result = self.multipass.accept_request(&did).await;
+                if result.is_ok() {
+                    self.report_presence(did).await;
+                }
+                result
+            }
+            WarpCmd::DenyFriendRequest(did) => self.multipass.deny_request(&did).await,
+            WarpCmd::FetchPresence(did) => {
+                self.report_presence(did).await;
+                Ok(())
+            }
+            WarpCmd::Shutdown => return,
+        };
+
+        if let (Err(_), Some(error)) = (result, failed) {
+            let _ = self.events.send(WarpEvent::Failed(error));
+        }
+    }
+
+    /// Returns up to `PAGE_SIZE` messages on the `page` side of `anchor`. A conversation
+    /// RayGun doesn't know about has no history to load.
+    async fn fetch_page(
+        &mut self,
+        conversation_id: Uuid,
+        anchor: Option<Uuid>,
+        page: Page,
+    ) -> Vec<Message> {
+        // TODO: ask RayGun for the range directly instead of slicing the whole history.
+        let messages = self
+            .raygun
+            .get_messages(conversation_id, MessageOptions::default())
+            .await
+            .unwrap_or_default();
+
+        let position = match anchor {
+            Some(id) => match messages.iter().position(|m| m.id() == id) {
+                Some(i) => Some(i),
+                None => return vec![],
+            },
+            None => None,
+        };
+        let range = match (page, position) {
+            (Page::Older, Some(i)) => i.saturating_sub(PAGE_SIZE)..i,
+            (Page::Newer, Some(i)) => i + 1..(i + 1 + PAGE_SIZE).min(messages.len()),
+            (Page::Around, Some(i)) => {
+                i.saturating_sub(PAGE_SIZE / 2)..(i + 1 + PAGE_SIZE / 2).min(messages.len())
+            }
+            // Without an anchor, start from the end of the conversation.
+            (_, None) => messages.len().saturating_sub(PAGE_SIZE)..messages.len(),
+        };
+
+        messages[range].to_vec()
+    }
+
+    async fn handle_event(&mut self, event: MessageEventKind) {
+        match event {
+            MessageEventKind::MessageSent {
+                conversation_id,
+                message_id,
+            }
+            | MessageEventKind::MessageReceived {
+                conversation_id,
+                message_id,
+            } => {
+                if let Ok(message) = self.raygun.get_message(conversation_id, message_id).await {
+                    let _ = self.events.send(WarpEvent::NewMessage(message));
+                }
+            }
+            MessageEventKind::MessageEdited {
+                conversation_id,
+                message_id,
+            } => {
+                if let Ok(message) = self.raygun.get_message(conversation_id, message_id).await {
+                    let _ = self.events.send(WarpEvent::MessageEdited(message));
+                }
+            }
+            MessageEventKind::MessageDeleted {
+                conversation_id,
+                message_id,
+            } => {
+                let _ = self.events.send(WarpEvent::MessageDeleted {
+                    conversation_id,
+                    message_id,
+                });
+            }
+            MessageEventKind::MessageReactionAdded {
+                conversation_id,
+                message_id,
+                ..
+            }
+            | MessageEventKind::MessageReactionRemoved {
+                conversation_id,
+                message_id,
+                ..
+            } => {
+                if let Ok(message) = self.raygun.get_message(conversation_id, message_id).await {
+                    let _ = self.events.send(WarpEvent::ReactionsUpdated {
+                        conversation_id,
+                        message_id,
+                        reactions: message.reactions(),
+                    });
+                }
+            }
+            MessageEventKind::EventReceived {
+                conversation_id,
+                did_key,
+                event: MessageEvent::Typing,
+            } => {
+                let _ = self.events.send(WarpEvent::TypingStarted {
+                    conversation_id,
+                    did_key,
+                });
+            }
+            MessageEventKind::EventCancelled {
+                conversation_id,
+                did_key,
+                event: MessageEvent::Typing,
+            } => {
+                let _ = self.events.send(WarpEvent::TypingStopped {
+                    conversation_id,
+                    did_key,
+                });
+            }
+            _ => {}
+        }
+    }
+}
+
+// Runs a future to completion on a thread and tokio runtime of its own.
+fn spawn(future: impl std::future::Future<Output = ()> + Send + 'static) {
+    std::thread::spawn(move || {
+        let runtime = match tokio::runtime::Runtime::new() {
+            Ok(runtime) => runtime,
+            Err(_) => return,
+        };
+        runtime.block_on(future);
+    });
+}
+
+// The locale key of the error shown when `cmd` fails. Typing updates failing isn't worth
+// bothering anyone with.
+fn failure(cmd: &WarpCmd) -> Option<&'static str> {
+    match cmd {
+        WarpCmd::Send { .. } | WarpCmd::Reply { .. } => Some("messages.send-failed"),
+        WarpCmd::Edit { .. } => Some("messages.edit-failed"),
+        WarpCmd::Delete { .. } => Some("messages.delete-failed"),
+        WarpCmd::React { .. } => Some("messages.react-failed"),
+        WarpCmd::CreateGroup { .. } => Some("groups.create-failed"),
+        // Failed member changes and friend requests are taken back, which shows the error.
+        WarpCmd::AddRecipient { .. } | WarpCmd::RemoveRecipient { .. } => None,
+        WarpCmd::SendFriendRequest(_) => None,
+        WarpCmd::AcceptFriendRequest(_) => Some("friends.accept-failed"),
+        WarpCmd::DenyFriendRequest(_) => Some("friends.deny-failed"),
+        WarpCmd::Typing { .. }
+        | WarpCmd::StopTyping { .. }
+        | WarpCmd::FetchMessages { .. }
+        | WarpCmd::FindIdentity { .. }
+        | WarpCmd::CancelFriendRequest(_)
+        | WarpCmd::FetchPresence(_)
+        | WarpCmd::Shutdown => None,
+    }
+}
+
+/// Lets a conversation know whether we're typing. It's called from the chatbar on every
+/// keystroke, so unlike other commands it doesn't go through `State`. The runner keeps how
+/// often peers are told in check.
+pub fn typing(conversation_id: Uuid, typing: bool) {
+    let cmd = if typing {
+        WarpCmd::Typing { conversation_id }
+    } else {
+        WarpCmd::StopTyping { conversation_id }
+    };
+    let _ = WARP_CMD_CH.tx.send(cmd);
+}
+
+/// Builds the `ActionHook` that forwards outgoing messaging actions to the warp runner.
+pub fn action_hook() -> ActionHook {
+    ActionHook::new(
+        vec![
+            Action::Send(Chat::default(), Message::default()),
+            Action::Reply(Chat::default(), Message::default()),
+            Action::Edit(Chat::default(), Message::default()),
+            Action::Delete(Chat::default(), Message::default()),
+            Action::React(Chat::default(), Message::default(), Default::default()),
+            Action::LoadMessages(Chat::default(), Page::Older),
+            Action::JumpTo(Chat::default(), Uuid::nil()),
+            Action::CreateGroup(vec![], None),
+            Action::AddMember(Chat::default(), Identity::default()),
+            Action::RemoveMember(Chat::default(), Identity::default()),
+            Action::FindFriend(FriendQuery::Did(DID::default()), None),
+            Action::SendRequest(FriendRequest::new(Identity::default(), None)),
+            Action::CancelRequest(Identity::default()),
+            Action::ExpireRequests,
+            Action::RequestAccepted(Identity::default()),
+            Action::AcceptRequest(Identity::default()),
+            Action::DenyRequest(Identity::default()),
+            Action::AddFriend(Identity::default()),
+            Action::ChatWith(Chat::default()),
+            Action::GroupCreated(Chat::default()),
+            Action::RemoveFromSidebar(Chat::default()),
+        ],
+        |state, action| match action {
+            // Whatever was typed in the chat we're leaving stays there as a draft, so we stop
+            // typing in it. The runner ignores chats we weren't typing in.
+            Action::ChatWith(chat) | Action::GroupCreated(chat) => {
+                if let Some(active) = state.chats.active.filter(|id| *id != chat.id) {
+                    typing(active, false);
+                }
+            }
+            Action::RemoveFromSidebar(chat) if state.chats.active == Some(chat.id) => {
+                typing(chat.id, false);
+            }
+            // Hooks run before the action is applied, so the requests are still there.
+            Action::ExpireRequests => {
+                for request in state.expired_requests() {
+                    let did = request.identity.did_key();
+                    let _ = WARP_CMD_CH.tx.send(WarpCmd::CancelFriendRequest(did));
+                }
+            }
+            _ => {
+                if let Some(cmd) = to_cmd(action) {
+                    let _ = WARP_CMD_CH.tx.send(cmd);
+                }
+            }
+        },
+    )
+}
+
+fn to_cmd(action: &Action) -> Option<WarpCmd> {
+    match action {
+        Action::Send(chat, message) => Some(WarpCmd::Send {
+            conversation_id: chat.id,
+            message: message.value(),
+        }),
+        Action::Reply(chat, message) => message.replied().map(|message_id| WarpCmd::Reply {
+            conversation_id: chat.id,
+            message_id,
+            message: message.value(),
+        }),
+        Action::Edit(chat, message) => Some(WarpCmd::Edit {
+            conversation_id: chat.id,
+            message_id: message.id(),
+            message: message.value(),
+        }),
+        Action::Delete(chat, message) => Some(WarpCmd::Delete {
+            conversation_id: chat.id,
+            message_id: message.id(),
+        }),
+        // Reacting again with the same emoji takes the reaction back.
+        Action::React(chat, message, reaction) => {
+            let reacted = message.reactions().iter().any(|r| {
+                r.emoji() == reaction.emoji()
+                    && r.users().iter().any(|user| reaction.users().contains(user))
+            });
+            Some(WarpCmd::React {
+                conversation_id: chat.id,
+                message_id: message.id(),
+                state: if reacted {
+                    ReactionState::Remove
+                } else {
+                    ReactionState::Add
+                },
+                emoji: reaction.emoji(),
+            })
+        }
+        Action::LoadMessages(chat, page) => Some(WarpCmd::FetchMessages {
+            conversation_id: chat.id,
+            anchor: chat.page_anchor(*page).map(|message| message.id()),
+            page: *page,
+        }),
+        // Only messages outside of the window need to be loaded.
+        Action::JumpTo(chat, message_id) => {
+            let loaded = chat.messages.iter().any(|m| m.id() == *message_id);
+            (!loaded).then(|| WarpCmd::FetchMessages {
+                conversation_id: chat.id,
+                anchor: Some(*message_id),
+                page: Page::Around,
+            })
+        }
+        Action::CreateGroup(friends, name) => Some(WarpCmd::CreateGroup {
+            recipients: friends.iter().map(|friend| friend.did_key()).collect(),
+            name: name.clone(),
+        }),
+        Action::AddMember(chat, identity) => Some(WarpCmd::AddRecipient {
+            conversation_id: chat.id,
+            did_key: identity.did_key(),
+        }),
+        Action::RemoveMember(chat, identity) => Some(WarpCmd::RemoveRecipient {
+            conversation_id: chat.id,
+            did_key: identity.did_key(),
+        }),
+        Action::FindFriend(query, note) => Some(WarpCmd::FindIdentity {
+            query: query.clone(),
+            note: note.clone(),
+        }),
+        Action::SendRequest(request) => {
+            Some(WarpCmd::SendFriendRequest(request.identity.did_key()))
+        }
+        Action::CancelRequest(identity) => Some(WarpCmd::CancelFriendRequest(identity.did_key())),
+        Action::AcceptRequest(identity) => Some(WarpCmd::AcceptFriendRequest(identity.did_key())),
+        Action::DenyRequest(identity) => Some(WarpCmd::DenyFriendRequest(identity.did_key())),
+        // New friends are shown as offline until MultiPass tells us otherwise.
+        Action::RequestAccepted(identity) | Action::AddFriend(identity) => {
+            Some(WarpCmd::FetchPresence(identity.did_key()))
+        }
+        _ => None,
+    }
+}
+
+/// Translates an event coming from the warp runner into the `Action` that records it in `State`.
+/// Anything coming from a blocked user is dropped here.
+pub fn to_action(state: &State, event: WarpEvent) -> Option<Action> {
+    if event.sender().map_or(false, |did| state.is_blocked(&did)) {
+        return None;
+    }
+
+    match event {
+        WarpEvent::AccountReady(identity) => Some(Action::SetId(identity)),
+        WarpEvent::Failed(error) => Some(Action::ShowError(error.into())),
+        WarpEvent::NewMessage(message) => state
+            .chats
+            .all
+            .get(&message.conversation_id())
+            .cloned()
+            .map(|chat| Action::NewMessage(chat, message)),
+        WarpEvent::MessagesLoaded {
+            conversation_id,
+            page,
+            messages,
+        } => state.chats.all.get(&conversation_id).cloned().map(|chat| {
+            let messages = messages
+                .into_iter()
+                .filter(|message| !state.is_blocked(&message.sender()))
+                .collect();
+            Action::MessagesLoaded(chat, page, messages)
+        }),
+        WarpEvent::MessageEdited(message) => state
+            .chats
+            .all
+            .get(&message.conversation_id())
+            .cloned()
+            .map(|chat| Action::MessageEdited(chat, message)),
+        WarpEvent::MessageDeleted {
+            conversation_id,
+            message_id,
+        } => state
+            .chats
+            .all
+            .get(&conversation_id)
+            .cloned()
+            .map(|chat| Action::MessageDeleted(chat, message_id)),
+        WarpEvent::ReactionsUpdated {
+            conversation_id,
+            message_id,
+            reactions,
+        } => state.chats.all.get(&conversation_id).cloned().map(|chat| {
+            let reactions = without_blocked_reactions(state, reactions);
+            Action::ReactionsUpdated(chat, message_id, reactions)
+        }),
+        // Members we don't know are left out until they become friends.
+        WarpEvent::GroupCreated {
+            conversation_id,
+            recipients,
+            name,
+        } => {
+            let me = &state.account.identity;
+            let participants = recipients
+                .iter()
+                .filter_map(|did| {
+                    if *did == me.did_key() {
+                        Some(me.clone())
+                    } else {
+                        state.friends.all.get(did).cloned()
+                    }
+                })
+                .collect();
+            Some(Action::GroupCreated(Chat {
+                id: conversation_id,
+                participants,
+                group: true,
+                name,
+                ..Chat::default()
+            }))
+        }
+        WarpEvent::IdentityFound { identity, note } => Some(friend_request(state, identity, note)),
+        WarpEvent::IdentityNotFound => Some(Action::ShowError("friends.not-found".into())),
+        WarpEvent::LookupFailed => Some(Action::ShowError("friends.lookup-failed".into())),
+        // We don't need to be shown our own typing.
+        WarpEvent::TypingStarted {
+            conversation_id,
+            did_key,
+        } if did_key != state.account.identity.did_key() => state
+            .chats
+            .all
+            .get(&conversation_id)
+            .cloned()
+            .map(|chat| Action::StartedTyping(chat, did_key)),
+        WarpEvent::TypingStopped {
+            conversation_id,
+            did_key,
+        } => state
+            .chats
+            .all
+            .get(&conversation_id)
+            .cloned()
+            .map(|chat| Action::StoppedTyping(chat, did_key)),
+        WarpEvent::TypingStarted { .. } => None,
+        WarpEvent::PresenceChanged { did_key, presence } => {
+            Some(Action::PresenceChanged(did_key, presence))
+        }
+        WarpEvent::RequestReceived(identity) => {
+            Some(Action::IncomingRequest(FriendRequest::new(identity, None)))
+        }
+        WarpEvent::RequestFailed(did) => Some(Action::RequestFailed(did)),
+        WarpEvent::MemberChangeFailed {
+            conversation_id,
+            did_key,
+            added,
+        } => {
+            let chat = state.chats.all.get(&conversation_id).cloned()?;
+            if added {
+                return Some(Action::AddMemberFailed(chat, did_key));
+            }
+            // Whoever couldn't be removed is put back, as long as we still know who they are.
+            match state.friends.all.get(&did_key) {
+                Some(identity) => Some(Action::RemoveMemberFailed(chat, identity.clone())),
+                None => Some(Action::ShowError("groups.members-failed".into())),
+            }
+        }
+    }
+}
+
+// Leaves blocked users out of reactions, dropping the ones only they had reacted with.
+fn without_blocked_reactions(state: &State, reactions: Vec<Reaction>) -> Vec<Reaction> {
+    reactions
+        .into_iter()
+        .filter_map(|mut reaction| {
+            let users: Vec<DID> = reaction
+                .users()
+                .into_iter()
+                .filter(|did| !state.is_blocked(did))
+                .collect();
+            if users.is_empty() {
+                return None;
+            }
+            reaction.set_users(users);
+            Some(reaction)
+        })
+        .collect()
+}
+
+fn to_availability(status: IdentityStatus) -> Availability {
+    match status {
+        IdentityStatus::Online => Availability::Online,
+        IdentityStatus::Away => Availability::Idle,
+        IdentityStatus::Busy => Availability::DoNotDisturb,
+        IdentityStatus::Offline => Availability::Offline,
+    }
+}
+
+// MultiPass doesn't tell TVs and headless devices apart, the web client counts as a desktop.
+fn to_device(platform: Platform) -> Device {
+    match platform {
+        Platform::Mobile => Device::Mobile,
+        Platform::Desktop | Platform::Web | Platform::Unknown => Device::Desktop,
+    }
+}
+
+/// Sends a friend request to someone that was looked up, unless there's a reason not to.
+/// Someone who already asked to be our friend is accepted instead.
+fn friend_request(state: &State, identity: Identity, note: Option<String>) -> Action {
+    let did = identity.did_key();
+    let error = if did == state.account.identity.did_key() {
+        "friends.add-self"
+    } else if state.has_friend_with_did(&did) {
+        "friends.already-friends"
+    } else if state.is_blocked(&did) {
+        "friends.add-blocked"
+    } else if state
+        .friends
+        .outgoing_requests
+        .iter()
+        .any(|r| r.identity.did_key() == did)
+    {
+        "friends.already-requested"
+    } else if state
+        .friends
+        .incoming_requests
+        .iter()
+        .any(|r| r.identity.did_key() == did)
+    {
+        return Action::AcceptRequest(identity);
+    } else {
+        return Action::SendRequest(FriendRequest::new(identity, note));
+    };
+    Action::ShowError(error.into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testing::{multipass::MockMultiPass, raygun::MockRayGun};
+    use futures::FutureExt;
+
+    struct Harness {
+        runner: WarpRunner,
+        events: UnboundedReceiver<WarpEvent>,
+        state: State,
+    }
+
+    impl Harness {
+        fn new() -> Self {
+            let me = Identity::default();
+            Harness::with_multipass(me.clone(), MockMultiPass::new(me, vec![]))
+        }
+
+        fn with_multipass(me: Identity, multipass: MockMultiPass) -> Self {
+            let raygun = MockRayGun::new(me.did_key());
+            let (tx, events) = unbounded_channel();
+            let mut state = State::default();
+            state.account.identity = me;
+            Harness {
+                runner: WarpRunner::new(Box::new(raygun), Box::new(multipass)).with_events(tx),
+                events,
+                state,
+            }
+        }
+
+        async fn chat(&mut self) -> Chat {
+            let conversation = self
+                .runner
+                .raygun
+                .create_group_conversation(vec![])
+                .await
+                .unwrap();
+            let chat = Chat {
+                id: conversation.id(),
+                ..Chat::default()
+            };
+            self.state.chats.all.insert(chat.id, chat.clone());
+            chat
+        }
+
+        // Hands `action` to the runner the way the action hook does, lets whatever the
+        // conversation streams loop back be handled, and translates the event that comes out.
+        async fn round_trip(&mut self, action: Action) -> Option<Action> {
+            let cmd = to_cmd(&action).expect("action isn't sent to the runner");
+            self.runner.handle_cmd(cmd).await;
+            if let Some(Some(event)) = self.runner.streams.next().now_or_never() {
+                self.runner.handle_event(event).await;
+            }
+            let event = self.events.try_recv().ok()?;
+            to_action(&self.state, event)
+        }
+    }
+
+    fn text_message(text: &str) -> Message {
+        let mut message = Message::default();
+        message.set_value(vec![text.into()]);
+        message
+    }
+
+    #[tokio::test]
+    async fn groups_are_created_through_raygun() {
+        let mut harness = Harness::new();
+
+        match harness
+            .round_trip(Action::CreateGroup(vec![], Some("Team".into())))
+            .await
+        {
+            Some(Action::GroupCreated(chat)) => {
+                assert!(chat.group);
+                assert_eq!(chat.name.as_deref(), Some("Team"));
+                assert_eq!(chat.participants, vec![harness.state.account.identity]);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn sent_and_edited_messages_come_back() {
+        let mut harness = Harness::new();
+        let chat = harness.chat().await;
+
+        let sent = match harness
+            .round_trip(Action::Send(chat.clone(), text_message("hello")))
+            .await
+        {
+            Some(Action::NewMessage(to, message)) => {
+                assert_eq!(to.id, chat.id);
+                assert_eq!(message.value(), vec!["hello".to_string()]);
+                message
+            }
+            other => panic!("unexpected {:?}", other),
+        };
+
+        let mut edited = sent.clone();
+        edited.set_value(vec!["hello again".into()]);
+        match harness.round_trip(Action::Edit(chat, edited)).await {
+            Some(Action::MessageEdited(_, message)) => {
+                assert_eq!(message.id(), sent.id());
+                assert_eq!(message.value(), vec!["hello again".to_string()]);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn reacting_twice_takes_the_reaction_back() {
+        let mut harness = Harness::new();
+        let chat = harness.chat().await;
+        let mut message = match harness
+            .round_trip(Action::Send(chat.clone(), text_message("hello")))
+            .await
+        {
+            Some(Action::NewMessage(_, message)) => message,
+            other => panic!("unexpected {:?}", other),
+        };
+
+        let mut reaction = Reaction::default();
+        reaction.set_emoji("👍");
+        reaction.set_users(vec![harness.state.account.identity.did_key()]);
+
+        let react = Action::React(chat.clone(), message.clone(), reaction.clone());
+        match harness.round_trip(react).await {
+            Some(Action::ReactionsUpdated(_, message_id, reactions)) => {
+                assert_eq!(message_id, message.id());
+                assert_eq!(reactions.len(), 1);
+                assert_eq!(reactions[0].emoji(), "👍");
+                message.set_reactions(reactions);
+            }
+            other => panic!("unexpected {:?}", other),
+        }
+
+        match harness
+            .round_trip(Action::React(chat, message, reaction))
+            .await
+        {
+            Some(Action::ReactionsUpdated(_, _, reactions)) => assert!(reactions.is_empty()),
+            other => panic!("unexpected {:?}", other),
+        }
+    }
+
+    #[tokio::test]
+    async fn failed_commands_are_shown() {
+        let mut harness = Harness::new();
+
+        // RayGun doesn't know about the message.
+        let edit = Action::Edit(Chat::default(), text_message("hello"));
+        assert_eq!(
+            harness.round_trip(edit).await,
+            Some(Action::ShowError("messages.edit-failed".into()))
+        );
+    }
+
+    #[tokio::test]
+    async fn failed_friend_requests_are_taken_back() {
+        let mut harness = Harness::new();
+
+        // MultiPass only knows us.
+        let stranger = Identity::default();
+        let request = Action::SendRequest(FriendRequest::new(stranger.clone(), None));
+        assert_eq!(
+            harness.round_trip(request).await,
+            Some(Action::RequestFailed(stranger.did_key()))
+        );
+    }
+
+    #[tokio::test]
+    async fn failed_member_changes_are_taken_back() {
+        let mut harness = Harness::new();
+        let friend = Identity::default();
+        harness
+            .state
+            .friends
+            .all
+            .insert(friend.did_key(), friend.clone());
+
+        // RayGun doesn't know about the group.
+        let chat = Chat {
+            group: true,
+            ..Chat::default()
+        };
+        harness.state.chats.all.insert(chat.id, chat.clone());
+
+        assert_eq!(
+            harness
+                .round_trip(Action::AddMember(chat.clone(), friend.clone()))
+                .await,
+            Some(Action::AddMemberFailed(chat.clone(), friend.did_key()))
+        );
+        assert_eq!(
+            harness
+                .round_trip(Action::RemoveMember(chat.clone(), friend.clone()))
+                .await,
+            Some(Action::RemoveMemberFailed(chat, friend))
+        );
+    }
+
+    #[tokio::test]
+    async fn friends_presence_is_reported_on_startup_and_on_updates() {
+        let me = Identity::default();
+        let friend = Identity::default();
+        let mut multipass = MockMultiPass::new(me.clone(), vec![friend.clone()]);
+        multipass.add_friend(friend.did_key());
+        multipass.set_presence(friend.did_key(), IdentityStatus::Away, Platform::Desktop);
+        let mut harness = Harness::with_multipass(me, multipass);
+
+        harness.runner.report_friends_presence().await;
+        let event = harness.events.try_recv().unwrap();
+        assert_eq!(
+            to_action(&harness.state, event),
+            Some(Action::PresenceChanged(
+                friend.did_key(),
+                Presence::new(Availability::Idle, Device::Desktop)
+            ))
+        );
+
+        harness
+            .runner
+            .handle_identity_event(MultiPassEventKind::IdentityUpdate {
+                did: friend.did_key(),
+            })
+            .await;
+        assert!(matches!(
+            harness.events.try_recv(),
+            Ok(WarpEvent::PresenceChanged { did_key, .. }) if did_key == friend.did_key()
+        ));
+    }
+
+    #[tokio::test]
+    async fn new_friends_get_their_presence_fetched() {
+        let me = Identity::default();
+        let friend = Identity::default();
+        let mut multipass = MockMultiPass::new(me.clone(), vec![friend.clone()]);
+        multipass.set_presence(friend.did_key(), IdentityStatus::Busy, Platform::Mobile);
+        let mut harness = Harness::with_multipass(me, multipass);
+
+        assert_eq!(
+            harness.round_trip(Action::AddFriend(friend.clone())).await,
+            Some(Action::PresenceChanged(
+                friend.did_key(),
+                Presence::new(Availability::DoNotDisturb, Device::Mobile)
+            ))
+        );
+
+        // Nothing is made up for anyone MultiPass doesn't know about.
+        let stranger = Identity::default();
+        assert_eq!(
+            harness
+                .round_trip(Action::AddFriend(stranger.clone()))
+                .await,
+            Some(Action::PresenceChanged(
+                stranger.did_key(),
+                Presence::default()
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn answered_requests_go_through_multipass() {
+        let me = Identity::default();
+        let accepted = Identity::default();
+        let denied = Identity::default();
+        let mut multipass = MockMultiPass::new(me.clone(), vec![accepted.clone(), denied.clone()]);
+        multipass.receive_request(accepted.did_key());
+        multipass.receive_request(denied.did_key());
+        let mut harness = Harness::with_multipass(me, multipass);
+
+        assert_eq!(
+            harness
+                .round_trip(Action::AcceptRequest(accepted.clone()))
+                .await,
+            Some(Action::PresenceChanged(
+                accepted.did_key(),
+                Presence::default()
+            ))
+        );
+        assert_eq!(
+            harness
+                .round_trip(Action::DenyRequest(denied.clone()))
+                .await,
+            None
+        );
+
+        let friends = harness.runner.multipass.list_friends().await.unwrap();
+        assert_eq!(friends, vec![accepted.did_key()]);
+
+        // The denied request is gone, so there's nothing left to accept.
+        assert_eq!(
+            harness.round_trip(Action::AcceptRequest(denied)).await,
+            Some(Action::ShowError("friends.accept-failed".into()))
+        );
+    }
+}